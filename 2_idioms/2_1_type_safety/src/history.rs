@@ -0,0 +1,203 @@
+use std::fmt;
+
+use crate::post;
+
+/// A single version of a post's content.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Revision {
+    pub title: post::Title,
+    pub body: post::Body,
+}
+
+/// All revisions of a post, from the original one to the current one.
+///
+/// Always contains at least one revision.
+#[derive(Clone, Debug, PartialEq)]
+pub struct History(Vec<Revision>);
+
+impl History {
+    pub fn new(title: post::Title, body: post::Body) -> Self {
+        Self(vec![Revision { title, body }])
+    }
+
+    pub fn current(&self) -> &Revision {
+        self.0
+            .last()
+            .expect("history always has at least one revision")
+    }
+
+    pub fn get(&self, revision: usize) -> Option<&Revision> {
+        self.0.get(revision)
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Revision> {
+        self.0.iter()
+    }
+
+    /// Describes what changed between the `from` and `to` revisions.
+    ///
+    /// Returns `None` if either of the revisions doesn't exist.
+    pub fn diff(&self, from: usize, to: usize) -> Option<RevisionDiff<'_>> {
+        let from = self.get(from)?;
+        let to = self.get(to)?;
+
+        Some(RevisionDiff {
+            title: diff_lines(&from.title.0, &to.title.0),
+            body: diff_lines(&from.body.0, &to.body.0),
+        })
+    }
+
+    pub(crate) fn push(&mut self, title: post::Title, body: post::Body) {
+        self.0.push(Revision { title, body })
+    }
+}
+
+/// A line-based difference between two revisions.
+#[derive(Debug, PartialEq)]
+pub struct RevisionDiff<'a> {
+    pub title: Vec<Change<'a>>,
+    pub body: Vec<Change<'a>>,
+}
+
+impl<'a> RevisionDiff<'a> {
+    pub fn has_changes(&self) -> bool {
+        self.title
+            .iter()
+            .chain(&self.body)
+            .any(|c| !matches!(c, Change::Unchanged(_)))
+    }
+}
+
+impl<'a> fmt::Display for RevisionDiff<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "@@ title @@")?;
+        for change in &self.title {
+            writeln!(f, "{change}")?;
+        }
+        writeln!(f, "@@ body @@")?;
+        for change in &self.body {
+            writeln!(f, "{change}")?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Change<'a> {
+    Unchanged(&'a str),
+    Removed(&'a str),
+    Added(&'a str),
+}
+
+impl<'a> fmt::Display for Change<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Change::Unchanged(line) => write!(f, " {line}"),
+            Change::Removed(line) => write!(f, "-{line}"),
+            Change::Added(line) => write!(f, "+{line}"),
+        }
+    }
+}
+
+/// Diffs two texts line by line using the longest common subsequence.
+fn diff_lines<'a>(old: &'a str, new: &'a str) -> Vec<Change<'a>> {
+    let old = old.lines().collect::<Vec<_>>();
+    let new = new.lines().collect::<Vec<_>>();
+
+    // `lcs[i][j]` is the length of the longest common subsequence of `old[i..]` and `new[j..]`.
+    let mut lcs = vec![vec![0usize; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            lcs[i][j] = if old[i] == new[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let mut changes = Vec::with_capacity(old.len().max(new.len()));
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() && j < new.len() {
+        if old[i] == new[j] {
+            changes.push(Change::Unchanged(old[i]));
+            i += 1;
+            j += 1;
+        } else if lcs[i + 1][j] >= lcs[i][j + 1] {
+            changes.push(Change::Removed(old[i]));
+            i += 1;
+        } else {
+            changes.push(Change::Added(new[j]));
+            j += 1;
+        }
+    }
+    changes.extend(old[i..].iter().map(|l| Change::Removed(l)));
+    changes.extend(new[j..].iter().map(|l| Change::Added(l)));
+
+    changes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn history() -> History {
+        let mut history = History::new(
+            post::Title(String::from("title")),
+            post::Body(String::from("first\nsecond\nthird")),
+        );
+        history.push(
+            post::Title(String::from("new title")),
+            post::Body(String::from("first\n2nd\nthird\nfourth")),
+        );
+        history
+    }
+
+    #[test]
+    fn should_keep_all_revisions() {
+        let history = history();
+
+        assert_eq!(history.len(), 2);
+        assert_eq!(history.get(0).unwrap().title.0, "title");
+        assert_eq!(history.current().title.0, "new title");
+        assert!(history.get(2).is_none());
+    }
+
+    #[test]
+    fn should_diff_revisions_line_by_line() {
+        let history = history();
+        let diff = history.diff(0, 1).unwrap();
+
+        assert_eq!(
+            diff.title,
+            vec![Change::Removed("title"), Change::Added("new title")]
+        );
+        assert_eq!(
+            diff.body,
+            vec![
+                Change::Unchanged("first"),
+                Change::Removed("second"),
+                Change::Added("2nd"),
+                Change::Unchanged("third"),
+                Change::Added("fourth"),
+            ]
+        );
+        assert!(diff.has_changes());
+        assert_eq!(
+            diff.to_string(),
+            "@@ title @@\n-title\n+new title\n@@ body @@\n first\n-second\n+2nd\n third\n+fourth\n"
+        );
+    }
+
+    #[test]
+    fn should_report_no_changes_for_same_revision() {
+        let history = history();
+
+        assert!(!history.diff(1, 1).unwrap().has_changes());
+        assert!(history.diff(0, 5).is_none());
+    }
+}
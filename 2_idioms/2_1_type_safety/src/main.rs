@@ -1,3 +1,8 @@
+mod history;
+mod moderation;
+
+use self::{history::History, moderation::DenyReason};
+
 mod post {
     #[derive(Clone, Debug, PartialEq)]
     pub struct Id(pub u64);
@@ -19,73 +24,181 @@ struct Unmoderated;
 struct Published;
 struct Deleted;
 
+/// Post that was edited after publishing and awaits to be sent back to moderation.
+struct Edited;
+
+struct InReview {
+    reviewer: user::Id,
+}
+
+struct Denied {
+    reviewer: user::Id,
+    reason: DenyReason,
+}
+
 #[derive(Clone)]
 pub struct Post<S> {
     id: post::Id,
     user_id: user::Id,
-    title: post::Title,
-    body: post::Body,
+    history: History,
     state: S,
 }
 
+impl<S> Post<S> {
+    pub fn title(&self) -> &post::Title {
+        &self.history.current().title
+    }
+
+    pub fn body(&self) -> &post::Body {
+        &self.history.current().body
+    }
+
+    pub fn history(&self) -> &History {
+        &self.history
+    }
+
+    fn transition<T>(self, state: T) -> Post<T> {
+        Post {
+            state,
+            id: self.id,
+            user_id: self.user_id,
+            history: self.history,
+        }
+    }
+
+    fn edited(mut self, title: post::Title, body: post::Body) -> Post<Edited> {
+        self.history.push(title, body);
+        self.transition(Edited)
+    }
+}
+
 impl Post<New> {
     pub fn new(id: post::Id, user_id: user::Id, title: post::Title, body: post::Body) -> Self {
         Self {
             id,
             user_id,
-            title,
-            body,
+            history: History::new(title, body),
             state: New,
         }
     }
 
     pub fn publish(self) -> Post<Unmoderated> {
-        Post {
-            state: Unmoderated,
-            id: self.id,
-            user_id: self.user_id,
-            title: self.title,
-            body: self.body,
-        }
+        self.transition(Unmoderated)
     }
 }
 
 impl Post<Unmoderated> {
     pub fn allow(self) -> Post<Published> {
-        Post {
-            state: Published,
-            id: self.id,
-            user_id: self.user_id,
-            title: self.title,
-            body: self.body,
-        }
+        self.transition(Published)
     }
 
     pub fn deny(self) -> Post<Deleted> {
-        Post {
-            state: Deleted,
-            id: self.id,
-            user_id: self.user_id,
-            title: self.title,
-            body: self.body,
+        self.transition(Deleted)
+    }
+
+    /// Assigns the post to the `reviewer`, unless it's the author of the post, as nobody moderates their own posts.
+    ///
+    /// The post is given back, when it can't be assigned.
+    pub fn assign(self, reviewer: user::Id) -> Result<Post<InReview>, Self> {
+        if self.user_id == reviewer {
+            return Err(self);
         }
+        Ok(self.transition(InReview { reviewer }))
+    }
+}
+
+impl Post<InReview> {
+    pub fn reviewer(&self) -> &user::Id {
+        &self.state.reviewer
+    }
+
+    pub fn allow(self) -> Post<Published> {
+        self.transition(Published)
+    }
+
+    pub fn deny(self, reason: DenyReason) -> Post<Denied> {
+        let reviewer = self.state.reviewer.clone();
+        self.transition(Denied { reviewer, reason })
+    }
+
+    pub fn release(self) -> Post<Unmoderated> {
+        self.transition(Unmoderated)
+    }
+}
+
+impl Post<Denied> {
+    pub fn reviewer(&self) -> &user::Id {
+        &self.state.reviewer
+    }
+
+    pub fn reason(&self) -> &DenyReason {
+        &self.state.reason
+    }
+
+    pub fn edit(self, title: post::Title, body: post::Body) -> Post<Edited> {
+        self.edited(title, body)
+    }
+
+    pub fn delete(self) -> Post<Deleted> {
+        self.transition(Deleted)
     }
 }
 
 impl Post<Published> {
+    pub fn edit(self, title: post::Title, body: post::Body) -> Post<Edited> {
+        self.edited(title, body)
+    }
+
     pub fn delete(self) -> Post<Deleted> {
-        Post {
-            state: Deleted,
-            id: self.id,
-            user_id: self.user_id,
-            title: self.title,
-            body: self.body,
-        }
+        self.transition(Deleted)
+    }
+}
+
+impl Post<Edited> {
+    pub fn edit(self, title: post::Title, body: post::Body) -> Post<Edited> {
+        self.edited(title, body)
+    }
+
+    pub fn publish(self) -> Post<Unmoderated> {
+        self.transition(Unmoderated)
     }
 }
 
 fn main() {
-    println!("Implement me!");
+    let mut queue = moderation::Queue::new();
+    queue.submit(
+        Post::new(
+            post::Id(1),
+            user::Id(1),
+            post::Title(String::from("Hello")),
+            post::Body(String::from("My first post")),
+        )
+        .publish(),
+    );
+    println!("posts awaiting moderation: {}", queue.len());
+
+    // The first reviewer gives up on the post, so it goes to another one.
+    let post = queue.assign_next(user::Id(2)).unwrap();
+    queue.release(post);
+    let published = queue.assign_next(user::Id(3)).unwrap().allow();
+
+    let edited = published.edit(
+        post::Title(String::from("Hello, world")),
+        post::Body(String::from("My first post\nNow edited")),
+    );
+    println!("{}", edited.history().diff(0, 1).unwrap());
+
+    queue.submit(edited.publish());
+    let denied = queue
+        .assign_next(user::Id(3))
+        .unwrap()
+        .deny(DenyReason::OffTopic);
+    println!(
+        "post denied by {:?}: {}, queue is empty: {}",
+        denied.reviewer(),
+        denied.reason(),
+        queue.is_empty(),
+    );
 }
 
 #[cfg(test)]
@@ -118,4 +231,97 @@ mod tests {
         let unmoderated_post = post.publish();
         let denied_post = unmoderated_post.deny();
     }
+
+    #[test]
+    fn should_not_assign_post_to_its_author() {
+        let post = Post::new(
+            post::Id(1),
+            user::Id(1),
+            post::Title(String::from("title")),
+            post::Body(String::from("body")),
+        );
+
+        let Err(unmoderated_post) = post.publish().assign(user::Id(1)) else {
+            panic!("assigned to its author");
+        };
+        assert!(unmoderated_post.assign(user::Id(2)).is_ok());
+    }
+
+    #[test]
+    fn should_deny_post_with_reason_on_review() {
+        let post = Post::new(
+            post::Id(1),
+            user::Id(1),
+            post::Title(String::from("title")),
+            post::Body(String::from("body")),
+        );
+
+        let in_review_post = post.publish().assign(user::Id(2)).ok().unwrap();
+        let denied_post = in_review_post.deny(DenyReason::Spam);
+
+        assert_eq!(denied_post.reviewer(), &user::Id(2));
+        assert_eq!(denied_post.reason(), &DenyReason::Spam);
+
+        denied_post.delete();
+    }
+
+    #[test]
+    fn should_send_edited_post_back_to_moderation() {
+        let post = Post::new(
+            post::Id(1),
+            user::Id(1),
+            post::Title(String::from("title")),
+            post::Body(String::from("body")),
+        );
+
+        let published_post = post.publish().assign(user::Id(2)).ok().unwrap().allow();
+        let edited_post = published_post.edit(
+            post::Title(String::from("new title")),
+            post::Body(String::from("new body")),
+        );
+        let unmoderated_post = edited_post.publish();
+
+        assert_eq!(
+            unmoderated_post.title(),
+            &post::Title(String::from("new title"))
+        );
+        assert_eq!(
+            unmoderated_post.body(),
+            &post::Body(String::from("new body"))
+        );
+        assert_eq!(unmoderated_post.history().len(), 2);
+        assert!(unmoderated_post.history().diff(0, 1).unwrap().has_changes());
+
+        unmoderated_post.allow();
+    }
+
+    #[test]
+    fn should_resubmit_denied_post_after_edit() {
+        let post = Post::new(
+            post::Id(1),
+            user::Id(1),
+            post::Title(String::from("title")),
+            post::Body(String::from("body")),
+        );
+
+        let denied_post = post
+            .publish()
+            .assign(user::Id(2))
+            .ok()
+            .unwrap()
+            .deny(DenyReason::Other(String::from("too short")));
+        let unmoderated_post = denied_post
+            .edit(
+                post::Title(String::from("title")),
+                post::Body(String::from("much longer body")),
+            )
+            .publish();
+
+        let history = unmoderated_post.history();
+        assert_eq!(history.len(), 2);
+        assert_eq!(
+            history.get(0).unwrap().body,
+            post::Body(String::from("body"))
+        );
+    }
 }
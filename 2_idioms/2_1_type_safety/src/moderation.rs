@@ -0,0 +1,119 @@
+use std::{collections::VecDeque, fmt};
+
+use crate::{user, InReview, Post, Unmoderated};
+
+/// Why a reviewer has denied a post.
+#[derive(Clone, Debug, PartialEq)]
+pub enum DenyReason {
+    Spam,
+    Offensive,
+    OffTopic,
+    Other(String),
+}
+
+impl fmt::Display for DenyReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Spam => write!(f, "spam"),
+            Self::Offensive => write!(f, "offensive content"),
+            Self::OffTopic => write!(f, "off-topic"),
+            Self::Other(reason) => write!(f, "{reason}"),
+        }
+    }
+}
+
+/// Posts waiting for moderation, in the order they were submitted.
+#[derive(Default)]
+pub struct Queue {
+    pending: VecDeque<Post<Unmoderated>>,
+}
+
+impl Queue {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.pending.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pending.is_empty()
+    }
+
+    pub fn submit(&mut self, post: Post<Unmoderated>) {
+        self.pending.push_back(post);
+    }
+
+    /// Takes the oldest pending post and assigns it to `reviewer`.
+    ///
+    /// Posts authored by the `reviewer` are skipped, so nobody moderates their own posts.
+    pub fn assign_next(&mut self, reviewer: user::Id) -> Option<Post<InReview>> {
+        let position = self
+            .pending
+            .iter()
+            .position(|post| post.user_id != reviewer)?;
+
+        self.pending.remove(position)?.assign(reviewer).ok()
+    }
+
+    /// Puts a post the reviewer gave up on back to the head of the queue.
+    pub fn release(&mut self, post: Post<InReview>) {
+        self.pending.push_front(post.release());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::post;
+
+    fn unmoderated(id: u64, author: u64) -> Post<Unmoderated> {
+        Post::new(
+            post::Id(id),
+            user::Id(author),
+            post::Title(String::from("title")),
+            post::Body(String::from("body")),
+        )
+        .publish()
+    }
+
+    #[test]
+    fn should_assign_posts_in_submission_order() {
+        let mut queue = Queue::new();
+        queue.submit(unmoderated(1, 1));
+        queue.submit(unmoderated(2, 1));
+
+        let post = queue.assign_next(user::Id(10)).unwrap();
+        assert_eq!(post.id, post::Id(1));
+        assert_eq!(post.reviewer(), &user::Id(10));
+        assert_eq!(queue.len(), 1);
+    }
+
+    #[test]
+    fn should_not_assign_own_posts_to_reviewer() {
+        let mut queue = Queue::new();
+        queue.submit(unmoderated(1, 10));
+        queue.submit(unmoderated(2, 1));
+
+        let post = queue.assign_next(user::Id(10)).unwrap();
+        assert_eq!(post.id, post::Id(2));
+
+        assert!(queue.assign_next(user::Id(10)).is_none());
+        assert_eq!(queue.len(), 1);
+    }
+
+    #[test]
+    fn should_return_released_post_to_queue_head() {
+        let mut queue = Queue::new();
+        queue.submit(unmoderated(1, 1));
+        queue.submit(unmoderated(2, 1));
+
+        let post = queue.assign_next(user::Id(10)).unwrap();
+        queue.release(post);
+
+        let post = queue.assign_next(user::Id(11)).unwrap();
+        assert_eq!(post.id, post::Id(1));
+        assert_eq!(post.reviewer(), &user::Id(11));
+    }
+}
@@ -1,32 +1,266 @@
-use std::mem;
+use std::{array, collections::HashSet, mem};
 
-fn main() {}
+use rayon::prelude::*;
 
-#[derive(Clone, Debug, PartialEq)]
-struct Trinity<T> {
-    a: T,
-    b: T,
-    c: T,
+fn main() {
+    let mut trinities = Solver {
+        expected: Trinity::new([1, 2, 3]),
+        unsolved: vec![Trinity::new([2, 3, 1]), Trinity::new([2, 1, 3])],
+    };
+    trinities.resolve();
+    println!("unsolved trinities: {:?}", trinities.unsolved);
+
+    let mut mirrored = Ring::new([1, 2, 3, 4]);
+    mirrored.reflect();
+    let mut rings = Solver {
+        expected: Ring::new([1, 2, 3, 4]),
+        unsolved: vec![
+            Ring::new([3, 4, 1, 2]),
+            mirrored,
+            Ring::new([2, 1, 4, 3]),
+            Ring::new([1, 3, 2, 4]),
+        ],
+    };
+    for solution in rings.resolve_with(&Group::dihedral()) {
+        let t = solution.transformation().unwrap();
+        println!("{:?} is solved by {t:?}", solution.ring);
+        assert_eq!(solution.ring.permute(&t.into()), rings.expected);
+    }
+
+    // Swapping the middle items and the ring's halves.
+    let group = Group::generated_by(&[
+        Permutation::new([0, 2, 1, 3]).unwrap(),
+        Permutation::new([2, 3, 0, 1]).unwrap(),
+    ]);
+    println!(
+        "trying {} permutations on {:?}",
+        group.len(),
+        rings.unsolved
+    );
+    for solution in rings.par_resolve_with(&group) {
+        println!(
+            "{:?} is solved by {:?}",
+            solution.ring, solution.permutation
+        );
+    }
+    println!(
+        "out of {} possible permutations",
+        Group::<4>::symmetric().len()
+    );
 }
 
-impl<T> Trinity<T> {
+/// A fixed-size sequence of items whose ends are joined together.
+#[derive(Clone, Debug, PartialEq)]
+struct Ring<T, const N: usize>([T; N]);
+
+type Trinity<T> = Ring<T, 3>;
+
+impl<T, const N: usize> Ring<T, N> {
+    fn new(items: [T; N]) -> Self {
+        Self(items)
+    }
+
+    /// Shifts all items one position to the left, moving the first one to the end.
     fn rotate(&mut self) {
-        mem::swap(&mut self.a, &mut self.b);
-        mem::swap(&mut self.b, &mut self.c);
+        self.0.rotate_left(1);
+    }
+
+    /// Reverses the order of the items.
+    fn reflect(&mut self) {
+        self.0.reverse();
+    }
+
+    /// Rearranges the items, so that the `i`-th item becomes the `p[i]`-th one of the original.
+    fn permute(self, p: &Permutation<N>) -> Self {
+        let mut items = self.0.map(Some);
+        Self(array::from_fn(|i| items[p.0[i]].take().unwrap()))
+    }
+
+    /// Checks whether [`Ring::permute`] with `p` would turn this ring into `other`.
+    fn matches_after(&self, p: &Permutation<N>, other: &Self) -> bool
+    where
+        T: PartialEq,
+    {
+        (0..N).all(|i| self.0[p.0[i]] == other.0[i])
+    }
+}
+
+/// A rearrangement of `N` positions: the `i`-th position takes the item from `self.0[i]`.
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq)]
+struct Permutation<const N: usize>([usize; N]);
+
+impl<const N: usize> Permutation<N> {
+    fn identity() -> Self {
+        Self(array::from_fn(|i| i))
+    }
+
+    /// Rotates to the left `k` times.
+    fn rotation(k: usize) -> Self {
+        Self(array::from_fn(|i| (i + k) % N))
+    }
+
+    fn reflection() -> Self {
+        Self(array::from_fn(|i| N - 1 - i))
+    }
+
+    /// Creates a permutation, returning `None` if `positions` is not one.
+    fn new(positions: [usize; N]) -> Option<Self> {
+        let mut seen = [false; N];
+        for &p in &positions {
+            if p >= N || mem::replace(&mut seen[p], true) {
+                return None;
+            }
+        }
+        Some(Self(positions))
+    }
+
+    /// Applies `self` and then `next`.
+    fn then(&self, next: &Self) -> Self {
+        Self(array::from_fn(|i| self.0[next.0[i]]))
+    }
+}
+
+/// A set of permutations closed under composition.
+#[derive(Clone, Debug)]
+struct Group<const N: usize> {
+    elements: Vec<Permutation<N>>,
+}
+
+impl<const N: usize> Group<N> {
+    /// The smallest group containing all the `generators`.
+    ///
+    /// Elements are ordered by the number of generator applications needed to reach them, starting with the
+    /// identity.
+    fn generated_by(generators: &[Permutation<N>]) -> Self {
+        let identity = Permutation::identity();
+        let mut seen = HashSet::from([identity]);
+        let mut elements = vec![identity];
+
+        let mut i = 0;
+        while let Some(element) = elements.get(i).copied() {
+            for g in generators {
+                let next = element.then(g);
+                if seen.insert(next) {
+                    elements.push(next);
+                }
+            }
+            i += 1;
+        }
+
+        Self { elements }
+    }
+
+    /// All rotations, with and without reflection.
+    fn dihedral() -> Self {
+        Self::generated_by(&[Permutation::rotation(1), Permutation::reflection()])
+    }
+
+    /// All possible permutations.
+    ///
+    /// Note: This has `N!` elements, so should only be used for small `N`.
+    fn symmetric() -> Self {
+        let mut generators = vec![Permutation::rotation(1)];
+        if N > 1 {
+            let mut swap = Permutation::identity();
+            swap.0.swap(0, 1);
+            generators.push(swap);
+        }
+        Self::generated_by(&generators)
+    }
+
+    fn len(&self) -> usize {
+        self.elements.len()
+    }
+
+    /// Finds the first element turning `ring` into `expected`.
+    fn solve<T: PartialEq>(
+        &self,
+        ring: &Ring<T, N>,
+        expected: &Ring<T, N>,
+    ) -> Option<Permutation<N>> {
+        self.elements
+            .iter()
+            .find(|p| ring.matches_after(p, expected))
+            .copied()
+    }
+}
+
+/// A rotation and reflection turning one [`Ring`] into another.
+///
+/// The reflection, if any, is applied before the rotations.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct Transformation {
+    rotations: usize,
+    reflected: bool,
+}
+
+impl<const N: usize> From<Transformation> for Permutation<N> {
+    fn from(t: Transformation) -> Self {
+        let rotation = Permutation::rotation(t.rotations);
+        if t.reflected {
+            Permutation::reflection().then(&rotation)
+        } else {
+            rotation
+        }
+    }
+}
+
+impl<const N: usize> TryFrom<Permutation<N>> for Transformation {
+    type Error = Permutation<N>;
+
+    /// Fails if the permutation is not a member of [`Group::dihedral`].
+    fn try_from(p: Permutation<N>) -> Result<Self, Self::Error> {
+        let Some(&first) = p.0.first() else {
+            return Ok(Self {
+                rotations: 0,
+                reflected: false,
+            });
+        };
+
+        [
+            Self {
+                rotations: first,
+                reflected: false,
+            },
+            Self {
+                rotations: N - 1 - first,
+                reflected: true,
+            },
+        ]
+        .into_iter()
+        .find(|&t| Permutation::from(t) == p)
+        .ok_or(p)
+    }
+}
+
+/// A ring solved by the [`Solver`].
+#[derive(Debug, PartialEq)]
+struct Solution<T, const N: usize> {
+    /// The ring as it was before solving.
+    ring: Ring<T, N>,
+    /// The permutation turning `ring` into the expected one.
+    permutation: Permutation<N>,
+}
+
+impl<T, const N: usize> Solution<T, N> {
+    /// Describes the `permutation` in rotations and reflections, if possible.
+    fn transformation(&self) -> Option<Transformation> {
+        Transformation::try_from(self.permutation).ok()
     }
 }
 
 #[derive(Debug)]
-struct Solver<T> {
-    expected: Trinity<T>,
-    unsolved: Vec<Trinity<T>>,
+struct Solver<T, const N: usize> {
+    expected: Ring<T, N>,
+    unsolved: Vec<Ring<T, N>>,
 }
 
-impl<T: PartialEq> Solver<T> {
+impl<T: PartialEq, const N: usize> Solver<T, N> {
+    /// Drops all the rings which may be rotated into the `expected` one.
     fn resolve(&mut self) {
         let mut unsolved = Vec::with_capacity(self.unsolved.len());
         'l: for mut t in mem::take(&mut self.unsolved) {
-            for _ in 0..3 {
+            for _ in 0..N {
                 if t == self.expected {
                     continue 'l;
                 }
@@ -37,6 +271,36 @@ impl<T: PartialEq> Solver<T> {
         }
         self.unsolved = unsolved;
     }
+
+    /// Takes out all the rings which may be turned into the `expected` one by some element of the `group`.
+    fn resolve_with(&mut self, group: &Group<N>) -> Vec<Solution<T, N>> {
+        let mut solved = Vec::new();
+        let mut unsolved = Vec::with_capacity(self.unsolved.len());
+        for ring in mem::take(&mut self.unsolved) {
+            match group.solve(&ring, &self.expected) {
+                Some(permutation) => solved.push(Solution { ring, permutation }),
+                None => unsolved.push(ring),
+            }
+        }
+        self.unsolved = unsolved;
+        solved
+    }
+
+    /// Parallel version of [`Solver::resolve_with`], worth it for large batches of rings.
+    fn par_resolve_with(&mut self, group: &Group<N>) -> Vec<Solution<T, N>>
+    where
+        T: Send + Sync,
+    {
+        let (solved, unsolved) =
+            mem::take(&mut self.unsolved)
+                .into_par_iter()
+                .partition_map(|ring| match group.solve(&ring, &self.expected) {
+                    Some(permutation) => rayon::iter::Either::Left(Solution { ring, permutation }),
+                    None => rayon::iter::Either::Right(ring),
+                });
+        self.unsolved = unsolved;
+        solved
+    }
 }
 
 #[cfg(test)]
@@ -46,17 +310,123 @@ mod tests {
     #[test]
     fn should_solve_three_of_four_cases() {
         let mut s = Solver {
-            expected: Trinity { a: 1, b: 2, c: 3 },
+            expected: Trinity::new([1, 2, 3]),
             unsolved: vec![
-                Trinity { a: 1, b: 2, c: 3 },
-                Trinity { a: 2, b: 1, c: 3 },
-                Trinity { a: 2, b: 3, c: 1 },
-                Trinity { a: 3, b: 1, c: 2 },
+                Trinity::new([1, 2, 3]),
+                Trinity::new([2, 1, 3]),
+                Trinity::new([2, 3, 1]),
+                Trinity::new([3, 1, 2]),
             ],
         };
 
         s.resolve();
 
-        assert_eq!(s.unsolved, vec![Trinity { a: 2, b: 1, c: 3 }]);
+        assert_eq!(s.unsolved, vec![Trinity::new([2, 1, 3])]);
+    }
+
+    #[test]
+    fn should_generate_groups_of_expected_size() {
+        assert_eq!(
+            Group::<5>::generated_by(&[Permutation::rotation(1)]).len(),
+            5
+        );
+        assert_eq!(Group::<5>::dihedral().len(), 10);
+        assert_eq!(Group::<4>::symmetric().len(), 24);
+        assert_eq!(Group::<1>::dihedral().len(), 1);
+    }
+
+    #[test]
+    fn should_report_transformations() {
+        let mut s = Solver {
+            expected: Ring::new([1, 2, 3, 4]),
+            unsolved: vec![
+                Ring::new([1, 2, 3, 4]),
+                Ring::new([3, 4, 1, 2]),
+                Ring::new([4, 3, 2, 1]),
+                Ring::new([2, 1, 4, 3]),
+                Ring::new([1, 3, 2, 4]),
+            ],
+        };
+
+        let transformations = s
+            .resolve_with(&Group::dihedral())
+            .iter()
+            .map(|s| s.transformation().unwrap())
+            .map(|t| (t.rotations, t.reflected))
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            transformations,
+            vec![(0, false), (2, false), (0, true), (2, true)]
+        );
+        assert_eq!(s.unsolved, vec![Ring::new([1, 3, 2, 4])]);
+    }
+
+    #[test]
+    fn should_solve_by_transformation() {
+        let expected = Ring::new(['a', 'b', 'c', 'd', 'e']);
+        let mut s = Solver {
+            expected: expected.clone(),
+            unsolved: vec![Ring::new(['c', 'b', 'a', 'e', 'd'])],
+        };
+
+        let solution = s.resolve_with(&Group::dihedral()).pop().unwrap();
+        let t = solution.transformation().unwrap();
+
+        assert_eq!(solution.ring.permute(&Permutation::from(t)), expected);
+    }
+
+    #[test]
+    fn should_solve_with_arbitrary_groups() {
+        let mut s = Solver {
+            expected: Ring::new([1, 2, 3, 4]),
+            unsolved: vec![Ring::new([1, 3, 2, 4]), Ring::new([2, 1, 3, 5])],
+        };
+
+        let solved = s.resolve_with(&Group::symmetric());
+
+        assert_eq!(solved.len(), 1);
+        assert_eq!(
+            solved[0].permutation,
+            Permutation::new([0, 2, 1, 3]).unwrap()
+        );
+        assert_eq!(solved[0].transformation(), None);
+        assert_eq!(s.unsolved, vec![Ring::new([2, 1, 3, 5])]);
+    }
+
+    #[test]
+    fn should_solve_in_parallel_same_as_sequentially() {
+        let rings = (0..1000u32)
+            .map(|i| {
+                let mut ring = Ring::new(array::from_fn::<_, 6, _>(|j| (i as usize + j) % 7));
+                if i % 3 == 0 {
+                    ring.reflect();
+                }
+                ring
+            })
+            .collect::<Vec<_>>();
+        let expected = Ring::new([0, 1, 2, 3, 4, 5]);
+
+        let mut sequential = Solver {
+            expected: expected.clone(),
+            unsolved: rings.clone(),
+        };
+        let mut parallel = Solver {
+            expected,
+            unsolved: rings,
+        };
+
+        assert_eq!(
+            sequential.resolve_with(&Group::dihedral()),
+            parallel.par_resolve_with(&Group::dihedral()),
+        );
+        assert_eq!(sequential.unsolved, parallel.unsolved);
+    }
+
+    #[test]
+    fn should_reject_invalid_permutations() {
+        assert!(Permutation::new([0, 0, 1]).is_none());
+        assert!(Permutation::new([0, 3, 1]).is_none());
+        assert!(Permutation::new([2, 0, 1]).is_some());
     }
 }
@@ -3,6 +3,11 @@ use std::{
     num::NonZeroU64,
 };
 
+pub mod projection;
+pub mod snapshot;
+pub mod store;
+pub mod upcast;
+
 fn main() {
     println!("Refactor me!");
 }
@@ -92,6 +97,15 @@ impl Version {
             .unwrap_or(Version::Initial)
     }
 
+    /// The number of events applied to the aggregate.
+    #[inline]
+    pub fn number(&self) -> u64 {
+        match self {
+            Version::Initial => 0,
+            Version::Number(en) => en.0.get(),
+        }
+    }
+
     /// Increments the version number to the next in sequence.
     #[inline]
     pub fn incr(&mut self) {
@@ -230,3 +244,116 @@ impl<I, A> BorrowMut<HydratedAggregate<A>> for Entity<I, A> {
         &mut self.aggregate
     }
 }
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use serde::{Deserialize, Serialize};
+    use serde_json::json;
+
+    use super::*;
+    use crate::{
+        snapshot::SnapshotAggregate,
+        upcast::{Envelope, UpcastError, Upcasters, VersionedEvent},
+    };
+
+    #[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+    pub struct Counter {
+        pub value: i64,
+    }
+
+    impl Aggregate for Counter {
+        fn aggregate_type() -> &'static str {
+            "counter"
+        }
+    }
+
+    impl SnapshotAggregate for Counter {
+        const SCHEMA_VERSION: u32 = 1;
+    }
+
+    pub struct CounterId(pub &'static str);
+
+    impl AggregateId<Counter> for CounterId {
+        fn as_str(&self) -> &str {
+            self.0
+        }
+    }
+
+    #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+    pub enum CounterEvent {
+        Added(i64),
+        Subtracted(i64),
+    }
+
+    impl Event for CounterEvent {
+        fn event_type(&self) -> &'static str {
+            match self {
+                CounterEvent::Added(_) => "counter_added",
+                CounterEvent::Subtracted(_) => "counter_subtracted",
+            }
+        }
+    }
+
+    /// Versions of the [`CounterEvent`] shape:
+    /// 1. `"Incremented"` and `"Decremented"`, changing the value by one;
+    /// 2. `{"Added": {"amount": n}}` and `{"Subtracted": {"amount": n}}`;
+    /// 3. `{"Added": n}` and `{"Subtracted": n}`.
+    impl VersionedEvent for CounterEvent {
+        const SCHEMA_VERSION: u32 = 3;
+    }
+
+    pub fn counter_upcasters() -> Upcasters {
+        fn to_amounts(envelope: &mut Envelope) -> Result<(), UpcastError> {
+            let (event_type, payload) = match envelope.payload.as_str() {
+                Some("Incremented") => ("counter_added", json!({"Added": {"amount": 1}})),
+                Some("Decremented") => ("counter_subtracted", json!({"Subtracted": {"amount": 1}})),
+                _ => return Err(envelope.malformed("unknown variant")),
+            };
+            envelope.event_type = event_type.to_owned();
+            envelope.payload = payload;
+            Ok(())
+        }
+
+        fn flatten_amount(envelope: &mut Envelope) -> Result<(), UpcastError> {
+            let Some((variant, fields)) = envelope
+                .payload
+                .as_object()
+                .and_then(|variants| variants.iter().next())
+            else {
+                return Err(envelope.malformed("expected an object"));
+            };
+            let Some(amount) = fields.get("amount") else {
+                return Err(envelope.malformed("missing `amount`"));
+            };
+            envelope.payload = json!({ variant: amount });
+            Ok(())
+        }
+
+        Upcasters::new()
+            .register("counter_incremented", 1, to_amounts)
+            .register("counter_decremented", 1, to_amounts)
+            .register("counter_added", 2, flatten_amount)
+            .register("counter_subtracted", 2, flatten_amount)
+    }
+
+    impl AggregateEvent<Counter> for CounterEvent {
+        fn apply_to(self, counter: &mut Counter) {
+            match self {
+                CounterEvent::Added(n) => counter.value += n,
+                CounterEvent::Subtracted(n) => counter.value -= n,
+            }
+        }
+    }
+
+    #[test]
+    fn version_counts_applied_events() {
+        let mut counter = HydratedAggregate::<Counter>::default();
+        assert_eq!(counter.version().number(), 0);
+
+        counter.apply_events([CounterEvent::Added(3), CounterEvent::Subtracted(1)]);
+
+        assert_eq!(counter.version(), Version::new(2));
+        assert_eq!(counter.version().number(), 2);
+        assert_eq!(counter.state().value, 2);
+    }
+}
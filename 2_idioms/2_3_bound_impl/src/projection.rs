@@ -0,0 +1,288 @@
+use std::{collections::HashMap, fs, io, path::PathBuf, sync::Mutex};
+
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use crate::store::{EventLog, Recorded, StoreError};
+
+/// A query-side view built from the events of all aggregates.
+pub trait Projection<E>: Default + Serialize + DeserializeOwned {
+    /// A unique name the projection's checkpoints are stored under.
+    ///
+    /// Note: This should effectively be a constant value, and should never change.
+    fn name() -> &'static str;
+
+    /// Updates the view with the next recorded event.
+    fn handle(&mut self, event: &Recorded<E>);
+}
+
+/// Serialized form of a checkpoint.
+#[derive(Serialize, Deserialize)]
+struct Checkpoint<P> {
+    position: u64,
+    state: P,
+}
+
+/// A storage of the latest projections' checkpoints.
+///
+/// A checkpoint holds the projection's state along with the position of the last handled event, so they are never
+/// out of sync.
+pub trait CheckpointStore {
+    fn save(&self, name: &str, checkpoint: &str) -> Result<(), StoreError>;
+
+    fn load(&self, name: &str) -> Result<Option<String>, StoreError>;
+}
+
+/// A [`CheckpointStore`] keeping checkpoints in memory, mostly useful for tests.
+#[derive(Debug, Default)]
+pub struct InMemoryCheckpointStore {
+    checkpoints: Mutex<HashMap<String, String>>,
+}
+
+impl InMemoryCheckpointStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl CheckpointStore for InMemoryCheckpointStore {
+    fn save(&self, name: &str, checkpoint: &str) -> Result<(), StoreError> {
+        self.checkpoints
+            .lock()
+            .unwrap()
+            .insert(name.to_owned(), checkpoint.to_owned());
+        Ok(())
+    }
+
+    fn load(&self, name: &str) -> Result<Option<String>, StoreError> {
+        Ok(self.checkpoints.lock().unwrap().get(name).cloned())
+    }
+}
+
+/// A [`CheckpointStore`] keeping every checkpoint in a separate JSON file inside a directory.
+#[derive(Debug)]
+pub struct FileCheckpointStore {
+    dir: PathBuf,
+}
+
+impl FileCheckpointStore {
+    /// Uses the directory at `dir`, creating it if it doesn't exist.
+    pub fn open(dir: impl Into<PathBuf>) -> Result<Self, StoreError> {
+        let dir = dir.into();
+        fs::create_dir_all(&dir)?;
+        Ok(Self { dir })
+    }
+}
+
+impl CheckpointStore for FileCheckpointStore {
+    fn save(&self, name: &str, checkpoint: &str) -> Result<(), StoreError> {
+        let path = self.dir.join(format!("{name}.json"));
+        let tmp = path.with_extension("json.tmp");
+
+        // Renaming is atomic, so a crash never leaves a half-written checkpoint.
+        fs::write(&tmp, checkpoint)?;
+        fs::rename(tmp, path)?;
+        Ok(())
+    }
+
+    fn load(&self, name: &str) -> Result<Option<String>, StoreError> {
+        match fs::read_to_string(self.dir.join(format!("{name}.json"))) {
+            Ok(checkpoint) => Ok(Some(checkpoint)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+/// Keeps a [`Projection`] up to date with an [`EventLog`], checkpointing its progress.
+#[derive(Debug)]
+pub struct Projector<P, C> {
+    projection: P,
+    position: u64,
+    checkpoints: C,
+}
+
+impl<P, C: CheckpointStore> Projector<P, C> {
+    /// Restores the projection from its latest checkpoint, or starts it from scratch if there is none.
+    pub fn resume<E>(checkpoints: C) -> Result<Self, StoreError>
+    where
+        P: Projection<E>,
+    {
+        let (projection, position) = match checkpoints.load(P::name())? {
+            Some(json) => {
+                let checkpoint = serde_json::from_str::<Checkpoint<P>>(&json)?;
+                (checkpoint.state, checkpoint.position)
+            }
+            None => (P::default(), 0),
+        };
+
+        Ok(Self {
+            projection,
+            position,
+            checkpoints,
+        })
+    }
+
+    /// The current state of the projection.
+    pub fn projection(&self) -> &P {
+        &self.projection
+    }
+
+    /// The position of the last event handled by the projection.
+    pub fn position(&self) -> u64 {
+        self.position
+    }
+
+    /// Handles all the events recorded since the last handled one, returning their number.
+    ///
+    /// A checkpoint is saved once all of them are handled.
+    pub fn catch_up<E, L>(&mut self, log: &L) -> Result<usize, StoreError>
+    where
+        P: Projection<E>,
+        L: EventLog<E>,
+    {
+        let events = log.read_all(self.position)?;
+        for event in &events {
+            self.projection.handle(event);
+            self.position = event.position;
+        }
+
+        if !events.is_empty() {
+            self.checkpoint::<E>()?;
+        }
+        Ok(events.len())
+    }
+
+    /// Drops the projection's state and builds it again from the very first event.
+    pub fn rebuild<E, L>(&mut self, log: &L) -> Result<usize, StoreError>
+    where
+        P: Projection<E>,
+        L: EventLog<E>,
+    {
+        self.projection = P::default();
+        self.position = 0;
+        self.checkpoint::<E>()?;
+
+        self.catch_up(log)
+    }
+
+    fn checkpoint<E>(&self) -> Result<(), StoreError>
+    where
+        P: Projection<E>,
+    {
+        let checkpoint = serde_json::to_string(&Checkpoint {
+            position: self.position,
+            state: &self.projection,
+        })?;
+        self.checkpoints.save(P::name(), &checkpoint)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::*;
+    use crate::{
+        store::{EventStore, InMemoryEventStore, JsonLinesEventStore},
+        tests::{Counter, CounterEvent, CounterId},
+        Version,
+    };
+
+    /// Which counters are positive at the moment.
+    #[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+    struct PositiveCounters {
+        values: BTreeMap<String, i64>,
+        handled: u64,
+    }
+
+    impl PositiveCounters {
+        fn count(&self) -> usize {
+            self.values.values().filter(|v| **v > 0).count()
+        }
+    }
+
+    impl Projection<CounterEvent> for PositiveCounters {
+        fn name() -> &'static str {
+            "positive_counters"
+        }
+
+        fn handle(&mut self, event: &Recorded<CounterEvent>) {
+            let value = self.values.entry(event.aggregate_id.clone()).or_default();
+            match event.event {
+                CounterEvent::Added(n) => *value += n,
+                CounterEvent::Subtracted(n) => *value -= n,
+            }
+            self.handled += 1;
+        }
+    }
+
+    fn append<S: EventStore<Counter, CounterEvent>>(
+        store: &S,
+        id: &'static str,
+        version: u64,
+        event: CounterEvent,
+    ) {
+        store
+            .append(&CounterId(id), Version::new(version), vec![event])
+            .unwrap();
+    }
+
+    #[test]
+    fn projects_events_of_all_aggregates() {
+        let store = InMemoryEventStore::new();
+        append(&store, "first", 0, CounterEvent::Added(1));
+        append(&store, "second", 0, CounterEvent::Added(2));
+        append(&store, "third", 0, CounterEvent::Subtracted(1));
+
+        let mut projector =
+            Projector::<PositiveCounters, _>::resume(InMemoryCheckpointStore::new()).unwrap();
+        assert_eq!(projector.catch_up(&store).unwrap(), 3);
+        assert_eq!(projector.projection().count(), 2);
+
+        append(&store, "first", 1, CounterEvent::Subtracted(1));
+        assert_eq!(projector.catch_up(&store).unwrap(), 1);
+        assert_eq!(projector.catch_up(&store).unwrap(), 0);
+        assert_eq!(projector.projection().count(), 1);
+        assert_eq!(projector.position(), 4);
+    }
+
+    #[test]
+    fn resumes_from_checkpoint_after_restart() {
+        let dir = tempfile::tempdir().unwrap();
+        let store =
+            JsonLinesEventStore::<CounterEvent>::open(dir.path().join("events.jsonl")).unwrap();
+        append(&store, "first", 0, CounterEvent::Added(1));
+        append(&store, "second", 0, CounterEvent::Added(1));
+
+        let checkpoints = || FileCheckpointStore::open(dir.path().join("checkpoints")).unwrap();
+
+        let mut projector = Projector::<PositiveCounters, _>::resume(checkpoints()).unwrap();
+        projector.catch_up(&store).unwrap();
+        drop(projector);
+
+        append(&store, "second", 1, CounterEvent::Subtracted(1));
+
+        let mut projector = Projector::<PositiveCounters, _>::resume(checkpoints()).unwrap();
+        assert_eq!(projector.position(), 2);
+        assert_eq!(projector.catch_up(&store).unwrap(), 1);
+        assert_eq!(projector.projection().count(), 1);
+        assert_eq!(projector.projection().handled, 3);
+    }
+
+    #[test]
+    fn rebuilds_from_scratch() {
+        let store = InMemoryEventStore::new();
+        append(&store, "first", 0, CounterEvent::Added(1));
+        append(&store, "second", 0, CounterEvent::Added(1));
+
+        let mut projector =
+            Projector::<PositiveCounters, _>::resume(InMemoryCheckpointStore::new()).unwrap();
+        projector.catch_up(&store).unwrap();
+        projector.catch_up(&store).unwrap();
+
+        assert_eq!(projector.rebuild(&store).unwrap(), 2);
+        assert_eq!(projector.projection().handled, 2);
+        assert_eq!(projector.projection().count(), 2);
+        assert_eq!(projector.position(), 2);
+    }
+}
@@ -0,0 +1,471 @@
+use std::{
+    collections::HashMap,
+    fs,
+    io::{self, Write},
+    marker::PhantomData,
+    num::NonZeroU64,
+    path::PathBuf,
+    sync::Mutex,
+};
+
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use crate::{
+    store::{EventStore, StoreError},
+    Aggregate, AggregateEvent, AggregateId, HydratedAggregate, Version,
+};
+
+/// An aggregate which state may be snapshotted.
+pub trait SnapshotAggregate: Aggregate + Serialize + DeserializeOwned {
+    /// The version of the state's serialized shape.
+    ///
+    /// Should be bumped whenever the shape changes, so that outdated snapshots are ignored rather than failing to
+    /// deserialize.
+    const SCHEMA_VERSION: u32;
+}
+
+/// Serialized form of a snapshot.
+#[derive(Serialize, Deserialize)]
+struct Snapshot<S> {
+    schema_version: u32,
+    version: u64,
+    state: S,
+}
+
+/// Encodes the aggregate's state along with its version and [`SnapshotAggregate::SCHEMA_VERSION`].
+fn encode<A: SnapshotAggregate>(aggregate: &HydratedAggregate<A>) -> Result<String, StoreError> {
+    Ok(serde_json::to_string(&Snapshot {
+        schema_version: A::SCHEMA_VERSION,
+        version: aggregate.version().number(),
+        state: aggregate.state(),
+    })?)
+}
+
+/// Decodes a snapshot, returning `None` if it has an outdated schema.
+fn decode<A: SnapshotAggregate>(json: &str) -> Result<Option<HydratedAggregate<A>>, StoreError> {
+    #[derive(Deserialize)]
+    struct Header {
+        schema_version: u32,
+    }
+
+    if serde_json::from_str::<Header>(json)?.schema_version != A::SCHEMA_VERSION {
+        return Ok(None);
+    }
+
+    let snapshot = serde_json::from_str::<Snapshot<A>>(json)?;
+    let version = Version::new(snapshot.version);
+    Ok(Some(HydratedAggregate {
+        version,
+        snapshot_version: Some(version),
+        state: snapshot.state,
+    }))
+}
+
+/// A storage of the latest snapshots of aggregates.
+pub trait SnapshotStore<A: SnapshotAggregate> {
+    /// Saves a snapshot of the aggregate, replacing the previous one.
+    fn save<I>(&self, id: &I, aggregate: &HydratedAggregate<A>) -> Result<(), StoreError>
+    where
+        I: AggregateId<A>;
+
+    /// Loads the latest snapshot of the aggregate.
+    ///
+    /// Returns `None` if there is no snapshot, or it has an outdated [`SnapshotAggregate::SCHEMA_VERSION`].
+    fn load<I>(&self, id: &I) -> Result<Option<HydratedAggregate<A>>, StoreError>
+    where
+        I: AggregateId<A>;
+}
+
+/// A [`SnapshotStore`] keeping snapshots in memory, mostly useful for tests.
+#[derive(Debug, Default)]
+pub struct InMemorySnapshotStore {
+    snapshots: Mutex<HashMap<(&'static str, String), String>>,
+}
+
+impl InMemorySnapshotStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl<A: SnapshotAggregate> SnapshotStore<A> for InMemorySnapshotStore {
+    fn save<I>(&self, id: &I, aggregate: &HydratedAggregate<A>) -> Result<(), StoreError>
+    where
+        I: AggregateId<A>,
+    {
+        let snapshot = encode(aggregate)?;
+        self.snapshots
+            .lock()
+            .unwrap()
+            .insert((A::aggregate_type(), id.as_str().to_owned()), snapshot);
+        Ok(())
+    }
+
+    fn load<I>(&self, id: &I) -> Result<Option<HydratedAggregate<A>>, StoreError>
+    where
+        I: AggregateId<A>,
+    {
+        let snapshots = self.snapshots.lock().unwrap();
+        snapshots
+            .get(&(A::aggregate_type(), id.as_str().to_owned()))
+            .map_or(Ok(None), |json| decode(json))
+    }
+}
+
+/// A [`SnapshotStore`] keeping every snapshot in a separate JSON file inside a directory.
+#[derive(Debug)]
+pub struct FileSnapshotStore {
+    dir: PathBuf,
+}
+
+impl FileSnapshotStore {
+    /// Uses the directory at `dir`, creating it if it doesn't exist.
+    pub fn open(dir: impl Into<PathBuf>) -> Result<Self, StoreError> {
+        let dir = dir.into();
+        fs::create_dir_all(&dir)?;
+        Ok(Self { dir })
+    }
+
+    fn path<A: Aggregate>(&self, id: &str) -> PathBuf {
+        // Escaping everything that may be meaningful for the file system.
+        let id = id
+            .bytes()
+            .map(|b| match b {
+                b'a'..=b'z' | b'A'..=b'Z' | b'0'..=b'9' | b'-' | b'_' => (b as char).to_string(),
+                _ => format!("%{b:02X}"),
+            })
+            .collect::<String>();
+
+        self.dir.join(format!("{}-{id}.json", A::aggregate_type()))
+    }
+}
+
+impl<A: SnapshotAggregate> SnapshotStore<A> for FileSnapshotStore {
+    fn save<I>(&self, id: &I, aggregate: &HydratedAggregate<A>) -> Result<(), StoreError>
+    where
+        I: AggregateId<A>,
+    {
+        let path = self.path::<A>(id.as_str());
+        let tmp = path.with_extension("json.tmp");
+
+        // Renaming is atomic, so a reader never sees a half-written snapshot.
+        let mut file = fs::File::create(&tmp)?;
+        file.write_all(encode(aggregate)?.as_bytes())?;
+        file.sync_all()?;
+        fs::rename(tmp, path)?;
+
+        Ok(())
+    }
+
+    fn load<I>(&self, id: &I) -> Result<Option<HydratedAggregate<A>>, StoreError>
+    where
+        I: AggregateId<A>,
+    {
+        match fs::read_to_string(self.path::<A>(id.as_str())) {
+            Ok(json) => decode(&json),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+/// When to take snapshots of aggregates.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SnapshotPolicy {
+    /// Never take snapshots.
+    #[default]
+    Never,
+
+    /// Take a snapshot on appending, once the given number of events was applied since the last snapshot.
+    EveryEvents(NonZeroU64),
+
+    /// Take a snapshot on loading, if more than the given number of events had to be replayed.
+    OnReplayLongerThan(u64),
+}
+
+/// An [`EventStore`] paired with a [`SnapshotStore`], loading aggregates from their latest snapshots.
+///
+/// Snapshots are only an optimization, so failing to save or load one never fails appending or loading, which fall
+/// back to replaying the events. The failure is kept to be [taken](SnapshottingStore::take_snapshot_error) instead.
+#[derive(Debug)]
+pub struct SnapshottingStore<A, E, ES, SS> {
+    events: ES,
+    snapshots: SS,
+    policy: SnapshotPolicy,
+    snapshot_error: Mutex<Option<StoreError>>,
+    _aggregate: PhantomData<fn() -> (A, E)>,
+}
+
+impl<A, E, ES, SS> SnapshottingStore<A, E, ES, SS>
+where
+    A: SnapshotAggregate,
+    E: AggregateEvent<A> + Clone,
+    ES: EventStore<A, E>,
+    SS: SnapshotStore<A>,
+{
+    pub fn new(events: ES, snapshots: SS, policy: SnapshotPolicy) -> Self {
+        Self {
+            events,
+            snapshots,
+            policy,
+            snapshot_error: Mutex::new(None),
+            _aggregate: PhantomData,
+        }
+    }
+
+    pub fn events(&self) -> &ES {
+        &self.events
+    }
+
+    pub fn snapshots(&self) -> &SS {
+        &self.snapshots
+    }
+
+    /// Takes the latest error of saving or loading a snapshot since the previous call, if any.
+    pub fn take_snapshot_error(&self) -> Option<StoreError> {
+        self.snapshot_error.lock().unwrap().take()
+    }
+
+    /// Loads the aggregate from its latest snapshot, replaying only the events which happened after it.
+    pub fn load<I>(&self, id: &I) -> Result<HydratedAggregate<A>, StoreError>
+    where
+        I: AggregateId<A>,
+    {
+        let snapshot = self.snapshots.load(id).unwrap_or_else(|e| {
+            self.snapshot_failed(e);
+            None
+        });
+        let mut aggregate = snapshot.unwrap_or_default();
+
+        let events = self.events.read(id, aggregate.version())?;
+        let replayed = events.len() as u64;
+        aggregate.apply_events(events);
+
+        if matches!(self.policy, SnapshotPolicy::OnReplayLongerThan(n) if replayed > n) {
+            self.snapshot(id, &mut aggregate);
+        }
+
+        Ok(aggregate)
+    }
+
+    /// Appends `events` to the store and applies them to the loaded `aggregate`.
+    ///
+    /// Fails with [`StoreError::VersionConflict`] if the `aggregate` is outdated, but never once the events are
+    /// appended.
+    pub fn append<I>(
+        &self,
+        id: &I,
+        aggregate: &mut HydratedAggregate<A>,
+        events: Vec<E>,
+    ) -> Result<Version, StoreError>
+    where
+        I: AggregateId<A>,
+    {
+        let version = self
+            .events
+            .append(id, aggregate.version(), events.clone())?;
+        aggregate.apply_events(events);
+
+        if let SnapshotPolicy::EveryEvents(n) = self.policy {
+            let snapshotted = aggregate.snapshot_version().unwrap_or_default();
+            if version.number() - snapshotted.number() >= n.get() {
+                self.snapshot(id, aggregate);
+            }
+        }
+
+        Ok(version)
+    }
+
+    fn snapshot<I>(&self, id: &I, aggregate: &mut HydratedAggregate<A>)
+    where
+        I: AggregateId<A>,
+    {
+        match self.snapshots.save(id, aggregate) {
+            Ok(()) => aggregate.set_snapshot_version(aggregate.version()),
+            Err(e) => self.snapshot_failed(e),
+        }
+    }
+
+    fn snapshot_failed(&self, error: StoreError) {
+        *self.snapshot_error.lock().unwrap() = Some(error);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        store::InMemoryEventStore,
+        tests::{Counter, CounterEvent, CounterId},
+    };
+
+    type Store = SnapshottingStore<
+        Counter,
+        CounterEvent,
+        InMemoryEventStore<CounterEvent>,
+        InMemorySnapshotStore,
+    >;
+
+    fn store(policy: SnapshotPolicy) -> Store {
+        SnapshottingStore::new(
+            InMemoryEventStore::new(),
+            InMemorySnapshotStore::new(),
+            policy,
+        )
+    }
+
+    fn add(store: &Store, id: &CounterId, times: usize) -> HydratedAggregate<Counter> {
+        let mut counter = store.load(id).unwrap();
+        for _ in 0..times {
+            store
+                .append(id, &mut counter, vec![CounterEvent::Added(1)])
+                .unwrap();
+        }
+        counter
+    }
+
+    #[test]
+    fn takes_snapshots_every_n_events() {
+        let store = store(SnapshotPolicy::EveryEvents(NonZeroU64::new(3).unwrap()));
+        let id = CounterId("counter");
+
+        let counter = add(&store, &id, 7);
+
+        assert_eq!(counter.snapshot_version(), Some(Version::new(6)));
+        let snapshot: HydratedAggregate<Counter> = store.snapshots().load(&id).unwrap().unwrap();
+        assert_eq!(snapshot.version(), Version::new(6));
+        assert_eq!(snapshot.state().value, 6);
+    }
+
+    #[test]
+    fn takes_snapshots_on_long_replays() {
+        let store = store(SnapshotPolicy::OnReplayLongerThan(5));
+        let id = CounterId("counter");
+
+        add(&store, &id, 5);
+        assert_eq!(store.load(&id).unwrap().snapshot_version(), None);
+
+        add(&store, &id, 1);
+        let counter = store.load(&id).unwrap();
+        assert_eq!(counter.snapshot_version(), Some(Version::new(6)));
+
+        let counter = store.load(&id).unwrap();
+        assert_eq!(counter.version(), Version::new(6));
+        assert_eq!(counter.state().value, 6);
+    }
+
+    #[test]
+    fn replays_only_events_after_snapshot() {
+        let store = store(SnapshotPolicy::Never);
+        let id = CounterId("counter");
+        add(&store, &id, 2);
+
+        // A snapshot disagreeing with the events proves they were not replayed.
+        let mut snapshot = HydratedAggregate::<Counter>::default();
+        snapshot.apply_events([CounterEvent::Added(50), CounterEvent::Added(50)]);
+        store.snapshots().save(&id, &snapshot).unwrap();
+
+        add(&store, &id, 3);
+
+        let counter = store.load(&id).unwrap();
+        assert_eq!(counter.version(), Version::new(5));
+        assert_eq!(counter.snapshot_version(), Some(Version::new(2)));
+        assert_eq!(counter.state().value, 103);
+    }
+
+    /// A [`SnapshotStore`] failing to save and load any snapshot.
+    struct FailingSnapshotStore;
+
+    impl SnapshotStore<Counter> for FailingSnapshotStore {
+        fn save<I>(&self, _: &I, _: &HydratedAggregate<Counter>) -> Result<(), StoreError> {
+            Err(io::Error::other("disk is full").into())
+        }
+
+        fn load<I>(&self, _: &I) -> Result<Option<HydratedAggregate<Counter>>, StoreError> {
+            Err(io::Error::other("disk is gone").into())
+        }
+    }
+
+    #[test]
+    fn keeps_going_when_snapshots_fail() {
+        let id = CounterId("counter");
+        let store = SnapshottingStore::new(
+            InMemoryEventStore::new(),
+            FailingSnapshotStore,
+            SnapshotPolicy::EveryEvents(NonZeroU64::new(1).unwrap()),
+        );
+
+        let mut counter = store.load(&id).unwrap();
+        assert_eq!(
+            store.take_snapshot_error().unwrap().to_string(),
+            "disk is gone"
+        );
+        let version = store
+            .append(&id, &mut counter, vec![CounterEvent::Added(2)])
+            .unwrap();
+        assert_eq!(version, Version::new(1));
+        assert_eq!(counter.snapshot_version(), None);
+        assert_eq!(
+            store.take_snapshot_error().unwrap().to_string(),
+            "disk is full"
+        );
+        assert!(store.take_snapshot_error().is_none());
+
+        let store = SnapshottingStore::new(
+            store.events,
+            FailingSnapshotStore,
+            SnapshotPolicy::OnReplayLongerThan(0),
+        );
+        let counter = store.load(&id).unwrap();
+        assert_eq!(counter.version(), Version::new(1));
+        assert_eq!(counter.state().value, 2);
+        assert_eq!(
+            store.take_snapshot_error().unwrap().to_string(),
+            "disk is full"
+        );
+    }
+
+    #[test]
+    fn ignores_snapshots_with_outdated_schema() {
+        let snapshots = InMemorySnapshotStore::new();
+        snapshots.snapshots.lock().unwrap().insert(
+            ("counter", String::from("counter")),
+            String::from(r#"{"schema_version":0,"version":1,"state":{"count":1}}"#),
+        );
+
+        let loaded: Option<HydratedAggregate<Counter>> =
+            snapshots.load(&CounterId("counter")).unwrap();
+
+        assert!(loaded.is_none());
+    }
+
+    #[test]
+    fn file_store_saves_and_loads_snapshots() {
+        let dir = tempfile::tempdir().unwrap();
+        let snapshots = FileSnapshotStore::open(dir.path().join("snapshots")).unwrap();
+        let id = CounterId("weird/id");
+
+        let mut counter = HydratedAggregate::<Counter>::default();
+        counter.apply_events([CounterEvent::Added(3), CounterEvent::Subtracted(1)]);
+        snapshots.save(&id, &counter).unwrap();
+
+        let json =
+            fs::read_to_string(dir.path().join("snapshots/counter-weird%2Fid.json")).unwrap();
+        assert_eq!(
+            json,
+            format!(
+                r#"{{"schema_version":{},"version":2,"state":{{"value":2}}}}"#,
+                Counter::SCHEMA_VERSION,
+            ),
+        );
+
+        let loaded: HydratedAggregate<Counter> = snapshots.load(&id).unwrap().unwrap();
+        assert_eq!(loaded.state(), counter.state());
+        assert_eq!(loaded.version(), Version::new(2));
+        assert_eq!(loaded.snapshot_version(), Some(Version::new(2)));
+
+        let missing: Option<HydratedAggregate<Counter>> =
+            snapshots.load(&CounterId("missing")).unwrap();
+        assert!(missing.is_none());
+    }
+}
@@ -0,0 +1,608 @@
+use std::{
+    borrow::Cow,
+    collections::HashMap,
+    fmt,
+    fs::{self, File, OpenOptions},
+    io::{self, BufRead, BufReader, Write},
+    iter,
+    marker::PhantomData,
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::{
+    upcast::{Envelope, UpcastError, Upcasters, VersionedEvent},
+    Aggregate, AggregateEvent, AggregateId, HydratedAggregate, Version,
+};
+
+#[derive(Error, Debug)]
+pub enum StoreError {
+    #[error("expected aggregate to be at version {expected:?}, but it is at {actual:?}")]
+    VersionConflict { expected: Version, actual: Version },
+
+    #[error(transparent)]
+    Io(#[from] io::Error),
+
+    #[error(transparent)]
+    Serde(#[from] serde_json::Error),
+
+    #[error(transparent)]
+    Upcast(#[from] UpcastError),
+}
+
+/// A persistent storage of aggregates' events.
+pub trait EventStore<A, E>
+where
+    A: Aggregate,
+    E: AggregateEvent<A>,
+{
+    /// Appends `events` to the aggregate's stream, returning the new version of the aggregate.
+    ///
+    /// Fails with [`StoreError::VersionConflict`] if the aggregate is not at `expected_version` anymore, meaning
+    /// that someone else has written to it since it was loaded.
+    fn append<I>(
+        &self,
+        id: &I,
+        expected_version: Version,
+        events: Vec<E>,
+    ) -> Result<Version, StoreError>
+    where
+        I: AggregateId<A>;
+
+    /// Reads the aggregate's events which were applied after the `after` version, in order.
+    fn read<I>(&self, id: &I, after: Version) -> Result<Vec<E>, StoreError>
+    where
+        I: AggregateId<A>;
+
+    /// Rebuilds the aggregate from all of its events.
+    fn load<I>(&self, id: &I) -> Result<HydratedAggregate<A>, StoreError>
+    where
+        I: AggregateId<A>,
+    {
+        let mut aggregate = HydratedAggregate::default();
+        aggregate.apply_events(self.read(id, Version::Initial)?);
+        Ok(aggregate)
+    }
+}
+
+fn check_version(expected: Version, actual: Version) -> Result<(), StoreError> {
+    if expected == actual {
+        Ok(())
+    } else {
+        Err(StoreError::VersionConflict { expected, actual })
+    }
+}
+
+/// An event as it was recorded by an [`EventLog`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct Recorded<E> {
+    /// The position of the event in the whole log, starting at 1.
+    pub position: u64,
+    pub aggregate_id: String,
+    /// The version of the aggregate after applying the event.
+    pub version: Version,
+    pub event: E,
+}
+
+/// An ordered log of events of all aggregates.
+pub trait EventLog<E> {
+    /// Reads all the events recorded after the `after` position, in order.
+    ///
+    /// Position `0` means reading from the very beginning.
+    fn read_all(&self, after: u64) -> Result<Vec<Recorded<E>>, StoreError>;
+}
+
+/// An [`EventStore`] keeping events in memory, mostly useful for tests.
+#[derive(Debug)]
+pub struct InMemoryEventStore<E> {
+    log: Mutex<Vec<Recorded<E>>>,
+}
+
+impl<E> Default for InMemoryEventStore<E> {
+    fn default() -> Self {
+        Self {
+            log: Mutex::new(Vec::new()),
+        }
+    }
+}
+
+impl<E> InMemoryEventStore<E> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl<A, E> EventStore<A, E> for InMemoryEventStore<E>
+where
+    A: Aggregate,
+    E: AggregateEvent<A> + Clone,
+{
+    fn append<I>(
+        &self,
+        id: &I,
+        expected_version: Version,
+        events: Vec<E>,
+    ) -> Result<Version, StoreError>
+    where
+        I: AggregateId<A>,
+    {
+        let mut log = self.log.lock().unwrap();
+        let mut version = log
+            .iter()
+            .rev()
+            .find(|r| r.aggregate_id == id.as_str())
+            .map(|r| r.version)
+            .unwrap_or_default();
+
+        check_version(expected_version, version)?;
+        for event in events {
+            version.incr();
+            let position = log.len() as u64 + 1;
+            log.push(Recorded {
+                position,
+                aggregate_id: id.as_str().to_owned(),
+                version,
+                event,
+            });
+        }
+
+        Ok(version)
+    }
+
+    fn read<I>(&self, id: &I, after: Version) -> Result<Vec<E>, StoreError>
+    where
+        I: AggregateId<A>,
+    {
+        let log = self.log.lock().unwrap();
+        let events = log
+            .iter()
+            .filter(|r| r.aggregate_id == id.as_str() && r.version > after)
+            .map(|r| r.event.clone())
+            .collect();
+
+        Ok(events)
+    }
+}
+
+impl<E: Clone> EventLog<E> for InMemoryEventStore<E> {
+    fn read_all(&self, after: u64) -> Result<Vec<Recorded<E>>, StoreError> {
+        let log = self.log.lock().unwrap();
+        Ok(log.iter().skip(after as usize).cloned().collect())
+    }
+}
+
+/// A single line of the [`JsonLinesEventStore`] file.
+#[derive(Serialize, Deserialize)]
+struct Record<'a> {
+    aggregate_id: Cow<'a, str>,
+    version: u64,
+    #[serde(flatten)]
+    event: Envelope,
+}
+
+/// An [`EventStore`] appending events of all aggregates to a single file, one JSON object per line.
+///
+/// Events are stored in [`Envelope`]s and migrated by the [`Upcasters`] on reading.
+///
+/// Note: Versions are checked against the state loaded when the file was opened, so the file should not be
+/// written by several stores at once.
+pub struct JsonLinesEventStore<E> {
+    path: PathBuf,
+    writer: Mutex<Writer>,
+    upcasters: Upcasters,
+    _event: PhantomData<fn() -> E>,
+}
+
+impl<E> fmt::Debug for JsonLinesEventStore<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("JsonLinesEventStore")
+            .field("path", &self.path)
+            .field("writer", &self.writer)
+            .field("upcasters", &self.upcasters)
+            .finish()
+    }
+}
+
+#[derive(Debug)]
+struct Writer {
+    file: File,
+    versions: HashMap<String, u64>,
+}
+
+impl<E> JsonLinesEventStore<E> {
+    /// Opens the file at `path`, creating it if it doesn't exist.
+    ///
+    /// A torn line at the end of the file, left by a crash in the middle of an append, is truncated, as the events of
+    /// an unfinished append were never committed.
+    pub fn open(path: impl Into<PathBuf>) -> Result<Self, StoreError> {
+        let path = path.into();
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+
+        let bytes = fs::read(&path)?;
+        let complete = bytes.iter().rposition(|b| *b == b'\n').map_or(0, |i| i + 1);
+        if complete < bytes.len() {
+            file.set_len(complete as u64)?;
+        }
+
+        let mut versions = HashMap::new();
+        for record in Self::records(&path)? {
+            let record = record?;
+            versions.insert(record.aggregate_id.into_owned(), record.version);
+        }
+
+        Ok(Self {
+            path,
+            writer: Mutex::new(Writer { file, versions }),
+            upcasters: Upcasters::new(),
+            _event: PhantomData,
+        })
+    }
+
+    /// Sets the [`Upcasters`] migrating events written with older schema versions.
+    pub fn with_upcasters(mut self, upcasters: Upcasters) -> Self {
+        self.upcasters = upcasters;
+        self
+    }
+
+    /// Reads the records of the complete lines of the file at `path`.
+    ///
+    /// A line without its line break is still being written by another process, so it's not a record yet.
+    fn records(
+        path: &Path,
+    ) -> Result<impl Iterator<Item = Result<Record<'static>, StoreError>>, StoreError> {
+        let mut reader = BufReader::new(File::open(path)?);
+
+        Ok(iter::from_fn(move || loop {
+            let mut line = Vec::new();
+            match reader.read_until(b'\n', &mut line) {
+                Err(e) => return Some(Err(e.into())),
+                Ok(_) if line.last() != Some(&b'\n') => return None,
+                Ok(_) if line.trim_ascii().is_empty() => continue,
+                Ok(_) => return Some(serde_json::from_slice(&line).map_err(Into::into)),
+            }
+        }))
+    }
+}
+
+impl<A, E> EventStore<A, E> for JsonLinesEventStore<E>
+where
+    A: Aggregate,
+    E: AggregateEvent<A> + VersionedEvent,
+{
+    fn append<I>(
+        &self,
+        id: &I,
+        expected_version: Version,
+        events: Vec<E>,
+    ) -> Result<Version, StoreError>
+    where
+        I: AggregateId<A>,
+    {
+        let mut writer = self.writer.lock().unwrap();
+        let current = writer.versions.get(id.as_str()).copied().unwrap_or(0);
+
+        check_version(expected_version, Version::new(current))?;
+
+        let mut buf = Vec::new();
+        let mut version = current;
+        for event in events {
+            version += 1;
+            serde_json::to_writer(
+                &mut buf,
+                &Record {
+                    aggregate_id: Cow::Borrowed(id.as_str()),
+                    version,
+                    event: Envelope::wrap(&event)?,
+                },
+            )?;
+            buf.push(b'\n');
+        }
+
+        // Writing everything at once, so the events are not interleaved with the ones of other aggregates.
+        let len = writer.file.metadata()?.len();
+        if let Err(e) = writer.file.write_all(&buf) {
+            // Otherwise, the next records would be glued to the torn line, corrupting the file.
+            writer.file.set_len(len)?;
+            return Err(e.into());
+        }
+        writer.versions.insert(id.as_str().to_owned(), version);
+
+        Ok(Version::new(version))
+    }
+
+    fn read<I>(&self, id: &I, after: Version) -> Result<Vec<E>, StoreError>
+    where
+        I: AggregateId<A>,
+    {
+        // Kept locked while reading, so the events of an append are read either all or none.
+        let _writer = self.writer.lock().unwrap();
+        let mut events = Vec::new();
+        for record in Self::records(&self.path)? {
+            let record = record?;
+            if record.aggregate_id == id.as_str() && record.version > after.number() {
+                events.push(self.upcasters.decode(record.event)?);
+            }
+        }
+
+        Ok(events)
+    }
+}
+
+impl<E: VersionedEvent> EventLog<E> for JsonLinesEventStore<E> {
+    fn read_all(&self, after: u64) -> Result<Vec<Recorded<E>>, StoreError> {
+        let _writer = self.writer.lock().unwrap();
+        let mut events = Vec::new();
+        for (position, record) in (1..).zip(Self::records(&self.path)?) {
+            if position <= after {
+                continue;
+            }
+            let record = record?;
+            events.push(Recorded {
+                position,
+                aggregate_id: record.aggregate_id.into_owned(),
+                version: Version::new(record.version),
+                event: self.upcasters.decode(record.event)?,
+            });
+        }
+
+        Ok(events)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::{
+            atomic::{AtomicBool, Ordering},
+            Barrier,
+        },
+        thread,
+    };
+
+    use super::*;
+    use crate::tests::{counter_upcasters, Counter, CounterEvent, CounterId};
+
+    fn assert_appends<S: EventStore<Counter, CounterEvent>>(store: &S) {
+        let id = CounterId("first");
+
+        let version = store
+            .append(
+                &id,
+                Version::Initial,
+                vec![CounterEvent::Added(2), CounterEvent::Added(3)],
+            )
+            .unwrap();
+        assert_eq!(version, Version::new(2));
+
+        let version = store
+            .append(&id, version, vec![CounterEvent::Subtracted(1)])
+            .unwrap();
+        assert_eq!(version, Version::new(3));
+
+        store
+            .append(
+                &CounterId("second"),
+                Version::Initial,
+                vec![CounterEvent::Added(10)],
+            )
+            .unwrap();
+
+        let counter = store.load(&id).unwrap();
+        assert_eq!(counter.version(), Version::new(3));
+        assert_eq!(counter.state().value, 4);
+
+        assert_eq!(
+            store.read(&id, Version::new(2)).unwrap(),
+            vec![CounterEvent::Subtracted(1)],
+        );
+        assert_eq!(
+            store.load(&CounterId("unknown")).unwrap().version(),
+            Version::Initial
+        );
+    }
+
+    fn assert_conflicts<S: EventStore<Counter, CounterEvent>>(store: &S) {
+        let id = CounterId("counter");
+        store
+            .append(&id, Version::Initial, vec![CounterEvent::Added(1)])
+            .unwrap();
+
+        let err = store
+            .append(&id, Version::Initial, vec![CounterEvent::Added(1)])
+            .unwrap_err();
+
+        assert!(matches!(
+            err,
+            StoreError::VersionConflict {
+                expected: Version::Initial,
+                actual,
+            } if actual == Version::new(1),
+        ));
+        assert_eq!(store.load(&id).unwrap().state().value, 1);
+    }
+
+    /// Every writer increments the counter, retrying on conflicts, so no increment may be lost, while a reader never
+    /// sees a partially written append.
+    fn assert_concurrent_writers<S: EventStore<Counter, CounterEvent> + Sync>(store: &S) {
+        const WRITERS: usize = 8;
+        const INCREMENTS: usize = 20;
+
+        let id = CounterId("contended");
+        let barrier = Barrier::new(WRITERS + 1);
+        let done = AtomicBool::new(false);
+
+        thread::scope(|s| {
+            s.spawn(|| {
+                barrier.wait();
+
+                let mut seen = 0;
+                while !done.load(Ordering::SeqCst) {
+                    let events = store.read(&id, Version::Initial).unwrap();
+                    assert!(events.len() >= seen, "events must not disappear");
+                    seen = events.len();
+                }
+            });
+
+            let writers = (0..WRITERS)
+                .map(|_| {
+                    s.spawn(|| {
+                        barrier.wait();
+
+                        for _ in 0..INCREMENTS {
+                            loop {
+                                let version = store.load(&id).unwrap().version();
+                                match store.append(&id, version, vec![CounterEvent::Added(1)]) {
+                                    Ok(_) => break,
+                                    Err(StoreError::VersionConflict { .. }) => continue,
+                                    Err(e) => panic!("unexpected error: {e}"),
+                                }
+                            }
+                        }
+                    })
+                })
+                .collect::<Vec<_>>();
+            for writer in writers {
+                writer.join().unwrap();
+            }
+            done.store(true, Ordering::SeqCst);
+        });
+
+        let counter = store.load(&id).unwrap();
+        assert_eq!(
+            counter.version(),
+            Version::new((WRITERS * INCREMENTS) as u64)
+        );
+        assert_eq!(counter.state().value, (WRITERS * INCREMENTS) as i64);
+    }
+
+    fn json_lines_store() -> (tempfile::TempDir, JsonLinesEventStore<CounterEvent>) {
+        let dir = tempfile::tempdir().unwrap();
+        let store = JsonLinesEventStore::open(dir.path().join("events.jsonl")).unwrap();
+        (dir, store)
+    }
+
+    #[test]
+    fn in_memory_store_appends_and_loads() {
+        assert_appends(&InMemoryEventStore::new());
+    }
+
+    #[test]
+    fn in_memory_store_detects_conflicts() {
+        assert_conflicts(&InMemoryEventStore::new());
+    }
+
+    #[test]
+    fn in_memory_store_handles_concurrent_writers() {
+        assert_concurrent_writers(&InMemoryEventStore::new());
+    }
+
+    #[test]
+    fn json_lines_store_appends_and_loads() {
+        let (_dir, store) = json_lines_store();
+        assert_appends(&store);
+    }
+
+    #[test]
+    fn json_lines_store_detects_conflicts() {
+        let (_dir, store) = json_lines_store();
+        assert_conflicts(&store);
+    }
+
+    #[test]
+    fn json_lines_store_handles_concurrent_writers() {
+        let (_dir, store) = json_lines_store();
+        assert_concurrent_writers(&store);
+    }
+
+    #[test]
+    fn json_lines_store_keeps_versions_after_reopening() {
+        let (dir, store) = json_lines_store();
+        let id = CounterId("counter");
+        store
+            .append(&id, Version::Initial, vec![CounterEvent::Added(5)])
+            .unwrap();
+        drop(store);
+
+        let store =
+            JsonLinesEventStore::<CounterEvent>::open(dir.path().join("events.jsonl")).unwrap();
+        assert!(store
+            .append(&id, Version::Initial, vec![CounterEvent::Added(1)])
+            .is_err());
+        store
+            .append(&id, Version::new(1), vec![CounterEvent::Added(1)])
+            .unwrap();
+
+        assert_eq!(store.load(&id).unwrap().state().value, 6);
+    }
+
+    #[test]
+    fn json_lines_store_truncates_torn_line_on_opening() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("events.jsonl");
+        fs::write(
+            &path,
+            concat!(
+                r#"{"aggregate_id":"counter","version":1,"#,
+                r#""event_type":"counter_added","schema_version":3,"payload":{"Added":5}}"#,
+                "\n",
+                r#"{"aggregate_id":"counter","version":2,"event_ty"#,
+            ),
+        )
+        .unwrap();
+
+        let store = JsonLinesEventStore::<CounterEvent>::open(&path).unwrap();
+        let id = CounterId("counter");
+        assert_eq!(store.load(&id).unwrap().state().value, 5);
+        store
+            .append(&id, Version::new(1), vec![CounterEvent::Added(1)])
+            .unwrap();
+
+        let reopened = JsonLinesEventStore::<CounterEvent>::open(&path).unwrap();
+        assert_eq!(reopened.load(&id).unwrap().state().value, 6);
+        assert_eq!(fs::read_to_string(&path).unwrap().lines().count(), 2);
+    }
+
+    #[test]
+    fn json_lines_store_upcasts_old_events() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("events.jsonl");
+        fs::write(
+            &path,
+            concat!(
+                r#"{"aggregate_id":"counter","version":1,"#,
+                r#""event_type":"counter_incremented","schema_version":1,"payload":"Incremented"}"#,
+                "\n",
+                r#"{"aggregate_id":"counter","version":2,"#,
+                r#""event_type":"counter_added","schema_version":2,"payload":{"Added":{"amount":5}}}"#,
+                "\n",
+            ),
+        )
+        .unwrap();
+
+        let store = JsonLinesEventStore::<CounterEvent>::open(&path)
+            .unwrap()
+            .with_upcasters(counter_upcasters());
+        let id = CounterId("counter");
+        store
+            .append(&id, Version::new(2), vec![CounterEvent::Subtracted(2)])
+            .unwrap();
+
+        assert_eq!(
+            store.read(&id, Version::Initial).unwrap(),
+            vec![
+                CounterEvent::Added(1),
+                CounterEvent::Added(5),
+                CounterEvent::Subtracted(2),
+            ],
+        );
+        assert!(matches!(
+            JsonLinesEventStore::<CounterEvent>::open(&path)
+                .unwrap()
+                .load(&id),
+            Err(StoreError::Upcast(UpcastError::MissingUpcaster { .. })),
+        ));
+    }
+}
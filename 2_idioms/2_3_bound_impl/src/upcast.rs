@@ -0,0 +1,389 @@
+use std::{collections::BTreeMap, fmt};
+
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::Value;
+use thiserror::Error;
+
+use crate::Event;
+
+/// An event which shape is versioned, so it may be migrated once the shape changes.
+pub trait VersionedEvent: Event + Serialize + DeserializeOwned {
+    /// The version of the event's serialized shape.
+    ///
+    /// Should be bumped whenever the shape changes, along with registering an [`Upcasters`] step migrating the
+    /// previous shape to the new one.
+    const SCHEMA_VERSION: u32;
+
+    /// The version of the serialized shape of the events of the given `event_type`.
+    ///
+    /// Defaults to [`VersionedEvent::SCHEMA_VERSION`] for all the event types, while may be overridden to version them
+    /// separately, so bumping one doesn't require registering steps for the unchanged others.
+    fn schema_version(event_type: &str) -> u32 {
+        let _ = event_type;
+        Self::SCHEMA_VERSION
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum UpcastError {
+    #[error("no upcaster for `{event_type}` events of schema version {schema_version}")]
+    MissingUpcaster {
+        event_type: String,
+        schema_version: u32,
+    },
+
+    #[error("`{event_type}` event has schema version {schema_version}, which is newer than current {current}")]
+    UnknownSchemaVersion {
+        event_type: String,
+        schema_version: u32,
+        current: u32,
+    },
+
+    #[error("`{event_type}` event of schema version {schema_version} is malformed: {reason}")]
+    MalformedPayload {
+        event_type: String,
+        schema_version: u32,
+        reason: String,
+    },
+
+    #[error(transparent)]
+    Serde(#[from] serde_json::Error),
+}
+
+/// A persisted form of an event, which is stable across its shape changes.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Envelope {
+    /// The [`Event::event_type`] of the event.
+    pub event_type: String,
+    /// The [`VersionedEvent::schema_version`] the `payload` was serialized with.
+    pub schema_version: u32,
+    /// The serialized event.
+    pub payload: Value,
+}
+
+impl Envelope {
+    pub fn wrap<E: VersionedEvent>(event: &E) -> Result<Self, UpcastError> {
+        Ok(Self {
+            event_type: event.event_type().to_owned(),
+            schema_version: E::schema_version(event.event_type()),
+            payload: serde_json::to_value(event)?,
+        })
+    }
+
+    /// Builds an [`UpcastError::MalformedPayload`] for this envelope, to be returned from an upcaster.
+    pub fn malformed(&self, reason: impl fmt::Display) -> UpcastError {
+        UpcastError::MalformedPayload {
+            event_type: self.event_type.clone(),
+            schema_version: self.schema_version,
+            reason: reason.to_string(),
+        }
+    }
+}
+
+type Upcaster = Box<dyn Fn(&mut Envelope) -> Result<(), UpcastError> + Send + Sync>;
+
+/// Chains of migrations, each one moving an [`Envelope`] of some event type from one schema version to the next.
+#[derive(Default)]
+pub struct Upcasters {
+    steps: BTreeMap<(String, u32), Upcaster>,
+}
+
+impl fmt::Debug for Upcasters {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Upcasters")
+            .field("from", &self.steps.keys())
+            .finish()
+    }
+}
+
+impl Upcasters {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers a step migrating envelopes of the `event_type` and schema `version` to `version + 1`.
+    ///
+    /// The step may change both the `payload` and the `event_type`, while the `schema_version` gets bumped
+    /// automatically. The next step is looked up by the changed `event_type`, so a renamed event continues along the
+    /// chain of its new type.
+    pub fn register<F>(mut self, event_type: &str, version: u32, step: F) -> Self
+    where
+        F: Fn(&mut Envelope) -> Result<(), UpcastError> + Send + Sync + 'static,
+    {
+        self.steps
+            .insert((event_type.to_owned(), version), Box::new(step));
+        self
+    }
+
+    /// Migrates the envelope to the current [`VersionedEvent::schema_version`] of its event type.
+    pub fn upcast<E: VersionedEvent>(
+        &self,
+        mut envelope: Envelope,
+    ) -> Result<Envelope, UpcastError> {
+        loop {
+            let current = E::schema_version(&envelope.event_type);
+            if envelope.schema_version > current {
+                return Err(UpcastError::UnknownSchemaVersion {
+                    event_type: envelope.event_type,
+                    schema_version: envelope.schema_version,
+                    current,
+                });
+            }
+            if envelope.schema_version == current {
+                return Ok(envelope);
+            }
+
+            let key = (envelope.event_type.clone(), envelope.schema_version);
+            let step = self
+                .steps
+                .get(&key)
+                .ok_or_else(|| UpcastError::MissingUpcaster {
+                    event_type: envelope.event_type.clone(),
+                    schema_version: envelope.schema_version,
+                })?;
+            step(&mut envelope)?;
+            envelope.schema_version += 1;
+        }
+    }
+
+    /// Migrates the envelope to the current schema version and deserializes the event out of it.
+    pub fn decode<E: VersionedEvent>(&self, envelope: Envelope) -> Result<E, UpcastError> {
+        let envelope = self.upcast::<E>(envelope)?;
+        Ok(serde_json::from_value(envelope.payload)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, path::Path};
+
+    use super::*;
+    use crate::{
+        tests::{counter_upcasters, Counter, CounterEvent},
+        Event, HydratedAggregate, Version,
+    };
+
+    /// Decodes every golden file in the `dir`, each being a JSON lines file of [`Envelope`]s written with some
+    /// historical schema version.
+    fn replay_golden_files<E: VersionedEvent>(
+        dir: &Path,
+        upcasters: &Upcasters,
+    ) -> Vec<(String, Vec<E>)> {
+        let mut files = fs::read_dir(dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .collect::<Vec<_>>();
+        files.sort();
+
+        files
+            .into_iter()
+            .map(|path| {
+                let name = path.file_name().unwrap().to_string_lossy().into_owned();
+                let events = fs::read_to_string(&path)
+                    .unwrap()
+                    .lines()
+                    .filter(|line| !line.trim().is_empty())
+                    .map(|line| {
+                        let envelope = serde_json::from_str(line).unwrap();
+                        upcasters
+                            .decode(envelope)
+                            .unwrap_or_else(|e| panic!("failed to decode `{name}`: {e}"))
+                    })
+                    .collect();
+                (name, events)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn golden_files_of_all_versions_replay_to_same_state() {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("golden/counter");
+        let replayed = replay_golden_files::<CounterEvent>(&dir, &counter_upcasters());
+
+        let versions = replayed
+            .iter()
+            .map(|(name, _)| name.as_str())
+            .collect::<Vec<_>>();
+        let expected_versions = (1..=CounterEvent::SCHEMA_VERSION)
+            .map(|v| format!("v{v}.jsonl"))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            versions, expected_versions,
+            "every version must have a golden file"
+        );
+
+        for (name, events) in replayed {
+            assert_eq!(
+                events,
+                vec![
+                    CounterEvent::Added(1),
+                    CounterEvent::Added(1),
+                    CounterEvent::Subtracted(1),
+                    CounterEvent::Added(1),
+                ],
+                "unexpected events in `{name}`",
+            );
+
+            let mut counter = HydratedAggregate::<Counter>::default();
+            counter.apply_events(events);
+            assert_eq!(counter.state().value, 2, "unexpected state from `{name}`");
+            assert_eq!(counter.version(), Version::new(4));
+        }
+    }
+
+    #[test]
+    fn wraps_events_with_current_schema_version() {
+        let envelope = Envelope::wrap(&CounterEvent::Subtracted(5)).unwrap();
+
+        assert_eq!(envelope.event_type, "counter_subtracted");
+        assert_eq!(envelope.schema_version, CounterEvent::SCHEMA_VERSION);
+        assert_eq!(
+            counter_upcasters()
+                .decode::<CounterEvent>(envelope)
+                .unwrap(),
+            CounterEvent::Subtracted(5),
+        );
+    }
+
+    #[test]
+    fn fails_on_unknown_versions() {
+        let envelope = Envelope {
+            event_type: String::from("counter_added"),
+            schema_version: CounterEvent::SCHEMA_VERSION + 1,
+            payload: Value::Null,
+        };
+        assert!(matches!(
+            counter_upcasters().decode::<CounterEvent>(envelope),
+            Err(UpcastError::UnknownSchemaVersion { .. }),
+        ));
+
+        let envelope = Envelope {
+            event_type: String::from("counter_added"),
+            schema_version: 0,
+            payload: Value::Null,
+        };
+        assert!(matches!(
+            counter_upcasters().decode::<CounterEvent>(envelope),
+            Err(UpcastError::MissingUpcaster {
+                schema_version: 0,
+                ..
+            }),
+        ));
+    }
+
+    /// Events which types are versioned separately: only `task_created` has changed its shape, from
+    /// `{"Created": {"name": s}}` to `{"Created": {"title": s}}`.
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    enum TaskEvent {
+        Created { title: String },
+        Completed,
+    }
+
+    impl Event for TaskEvent {
+        fn event_type(&self) -> &'static str {
+            match self {
+                TaskEvent::Created { .. } => "task_created",
+                TaskEvent::Completed => "task_completed",
+            }
+        }
+    }
+
+    impl VersionedEvent for TaskEvent {
+        const SCHEMA_VERSION: u32 = 1;
+
+        fn schema_version(event_type: &str) -> u32 {
+            match event_type {
+                "task_created" => 2,
+                _ => Self::SCHEMA_VERSION,
+            }
+        }
+    }
+
+    fn task_upcasters() -> Upcasters {
+        Upcasters::new().register("task_created", 1, |envelope| {
+            let Some(name) = envelope.payload.pointer("/Created/name").cloned() else {
+                return Err(envelope.malformed("missing `name`"));
+            };
+            envelope.payload = serde_json::json!({"Created": {"title": name}});
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn upcasts_each_event_type_along_own_chain() {
+        let upcasters = task_upcasters();
+
+        let created = Envelope::wrap(&TaskEvent::Created {
+            title: String::from("write"),
+        })
+        .unwrap();
+        let completed = Envelope::wrap(&TaskEvent::Completed).unwrap();
+        assert_eq!(created.schema_version, 2);
+        assert_eq!(completed.schema_version, 1);
+
+        let old_created = Envelope {
+            event_type: String::from("task_created"),
+            schema_version: 1,
+            payload: serde_json::json!({"Created": {"name": "write"}}),
+        };
+        for (envelope, expected) in [
+            (
+                old_created,
+                TaskEvent::Created {
+                    title: String::from("write"),
+                },
+            ),
+            (
+                created,
+                TaskEvent::Created {
+                    title: String::from("write"),
+                },
+            ),
+            (completed, TaskEvent::Completed),
+        ] {
+            assert_eq!(upcasters.decode::<TaskEvent>(envelope).unwrap(), expected);
+        }
+
+        let newer_completed = Envelope {
+            event_type: String::from("task_completed"),
+            schema_version: 2,
+            payload: Value::from("Completed"),
+        };
+        assert!(matches!(
+            upcasters.decode::<TaskEvent>(newer_completed),
+            Err(UpcastError::UnknownSchemaVersion { current: 1, .. }),
+        ));
+    }
+
+    #[test]
+    fn fails_on_steps_registered_for_other_event_types() {
+        // `counter_incremented` has the step from version 1 only.
+        let envelope = Envelope {
+            event_type: String::from("counter_incremented"),
+            schema_version: 2,
+            payload: serde_json::json!({"Added": {"amount": 1}}),
+        };
+
+        assert!(matches!(
+            counter_upcasters().decode::<CounterEvent>(envelope),
+            Err(UpcastError::MissingUpcaster {
+                schema_version: 2,
+                ..
+            }),
+        ));
+    }
+
+    #[test]
+    fn fails_on_malformed_payloads() {
+        let envelope = Envelope {
+            event_type: String::from("counter_added"),
+            schema_version: 2,
+            payload: serde_json::json!({"Added": {"count": 1}}),
+        };
+
+        assert!(matches!(
+            counter_upcasters().decode::<CounterEvent>(envelope),
+            Err(UpcastError::MalformedPayload { .. }),
+        ));
+    }
+}
@@ -1,11 +1,22 @@
+pub mod problem;
+pub mod server;
+
 use std::borrow::Cow;
-use std::net::{IpAddr, SocketAddr};
+
+use serde_json::{Map, Value};
 
 fn main() {
     println!("Refactor me!");
 
     let mut err = Error::new("NO_USER");
-    err.status(404).message("User not found");
+    err.status(404)
+        .message("User not found")
+        .instance("/users/42")
+        .extension("user_id", 42);
+    match problem::ProblemDetails::try_from(&err) {
+        Ok(problem) => println!("{}", serde_json::to_string_pretty(&problem).unwrap()),
+        Err(e) => println!("{e}"),
+    }
 }
 
 #[derive(Debug)]
@@ -13,6 +24,8 @@ pub struct Error<'a> {
     code: Cow<'a, str>,
     status: u16,
     message: Cow<'a, str>,
+    instance: Option<Cow<'a, str>>,
+    extensions: Map<String, Value>,
 }
 
 impl<'a> Default for Error<'a> {
@@ -22,6 +35,8 @@ impl<'a> Default for Error<'a> {
             code: Cow::Borrowed("UNKNOWN"),
             status: 500,
             message: Cow::Borrowed("Unknown error has happened."),
+            instance: None,
+            extensions: Map::new(),
         }
     }
 }
@@ -43,35 +58,23 @@ impl<'a> Error<'a> {
         self.message = m.into();
         self
     }
-}
 
-#[derive(Debug, Default)]
-pub struct Server(Option<SocketAddr>);
-
-impl Server {
-    pub fn bind(&mut self, ip: IpAddr, port: u16) {
-        self.0 = Some(SocketAddr::new(ip, port))
+    /// Sets the URI reference identifying this occurrence of the error.
+    pub fn instance(&mut self, i: impl Into<Cow<'a, str>>) -> &mut Self {
+        self.instance = Some(i.into());
+        self
     }
-}
 
-#[cfg(test)]
-mod server_spec {
-    use super::*;
-
-    mod bind {
-        use std::net::Ipv4Addr;
-
-        use super::*;
-
-        #[test]
-        fn sets_provided_address_to_server() {
-            let mut server = Server::default();
-
-            server.bind(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 8080);
-            assert_eq!(format!("{}", server.0.unwrap()), "127.0.0.1:8080");
-
-            server.bind("::1".parse().unwrap(), 9911);
-            assert_eq!(format!("{}", server.0.unwrap()), "[::1]:9911");
+    /// Adds a problem-type-specific member to the [`problem::ProblemDetails`] of this error.
+    ///
+    /// Members defined by [RFC 7807] itself are ignored, as they would be duplicated otherwise.
+    ///
+    /// [RFC 7807]: https://www.rfc-editor.org/rfc/rfc7807
+    pub fn extension(&mut self, key: impl Into<String>, value: impl Into<Value>) -> &mut Self {
+        let key = key.into();
+        if !problem::RESERVED_MEMBERS.contains(&key.as_str()) {
+            self.extensions.insert(key, value.into());
         }
+        self
     }
 }
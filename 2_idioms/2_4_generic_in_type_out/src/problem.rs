@@ -0,0 +1,220 @@
+//! [RFC 7807] problem details for HTTP APIs.
+//!
+//! [RFC 7807]: https://www.rfc-editor.org/rfc/rfc7807
+
+use axum::{
+    http::{header, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use thiserror::Error;
+
+use crate::Error;
+
+pub const CONTENT_TYPE: &str = "application/problem+json";
+
+/// Members of [`ProblemDetails`] defined by the RFC, which extensions can't be named after.
+pub const RESERVED_MEMBERS: &[&str] = &["type", "title", "status", "detail", "instance"];
+
+/// A machine-readable description of an HTTP API error.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ProblemDetails {
+    /// A URI reference identifying the problem type.
+    #[serde(rename = "type")]
+    pub type_uri: String,
+    /// A short summary of the problem type.
+    pub title: String,
+    pub status: u16,
+    /// An explanation specific to this occurrence of the problem.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+    /// A URI reference identifying this occurrence of the problem.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub instance: Option<String>,
+    /// Additional problem-type-specific members.
+    #[serde(flatten)]
+    pub extensions: Map<String, Value>,
+}
+
+#[derive(Clone, Copy, Debug, Error, PartialEq, Eq)]
+#[error("{0} is not a known HTTP error status code")]
+pub struct InvalidStatus(pub u16);
+
+/// Checks that `status` is a registered HTTP status code of a client or a server error.
+pub fn validate_status(status: u16) -> Result<StatusCode, InvalidStatus> {
+    StatusCode::from_u16(status)
+        .ok()
+        .filter(|s| s.canonical_reason().is_some())
+        .filter(|s| s.is_client_error() || s.is_server_error())
+        .ok_or(InvalidStatus(status))
+}
+
+impl<'a> TryFrom<&Error<'a>> for ProblemDetails {
+    type Error = InvalidStatus;
+
+    fn try_from(err: &Error<'a>) -> Result<Self, Self::Error> {
+        let status = validate_status(err.status)?;
+
+        Ok(Self {
+            type_uri: format!("urn:problem-type:{}", err.code),
+            title: status.canonical_reason().unwrap_or_default().to_owned(),
+            status: status.as_u16(),
+            detail: Some(err.message.clone().into_owned()),
+            instance: err.instance.as_ref().map(|i| i.clone().into_owned()),
+            extensions: err.extensions.clone(),
+        })
+    }
+}
+
+impl IntoResponse for ProblemDetails {
+    fn into_response(self) -> Response {
+        let status = StatusCode::from_u16(self.status).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+        let body = match serde_json::to_vec(&self) {
+            Ok(body) => body,
+            Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+        };
+
+        (
+            status,
+            [(header::CONTENT_TYPE, HeaderValue::from_static(CONTENT_TYPE))],
+            body,
+        )
+            .into_response()
+    }
+}
+
+impl<'a> IntoResponse for Error<'a> {
+    /// Responds with the [`ProblemDetails`] of this error.
+    ///
+    /// An error with an invalid status is a bug on its own, so it is reported as an internal server error.
+    fn into_response(self) -> Response {
+        match ProblemDetails::try_from(&self) {
+            Ok(problem) => problem.into_response(),
+            Err(e) => {
+                let mut err = Error::default();
+                err.message(e.to_string())
+                    .extension("code", self.code.as_ref());
+                ProblemDetails::try_from(&err)
+                    .expect("default error has a valid status")
+                    .into_response()
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod spec {
+    use serde_json::json;
+
+    use super::*;
+
+    async fn body(response: Response) -> Value {
+        let bytes = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        serde_json::from_slice(&bytes).unwrap()
+    }
+
+    #[test]
+    fn validates_status_codes() {
+        assert_eq!(validate_status(404), Ok(StatusCode::NOT_FOUND));
+        assert_eq!(validate_status(503), Ok(StatusCode::SERVICE_UNAVAILABLE));
+        assert_eq!(validate_status(200), Err(InvalidStatus(200)));
+        assert_eq!(validate_status(499), Err(InvalidStatus(499)));
+        assert_eq!(validate_status(1000), Err(InvalidStatus(1000)));
+    }
+
+    #[test]
+    fn serializes_all_members() {
+        let mut err = Error::new("NO_USER");
+        err.status(404)
+            .message("User not found")
+            .instance("/users/42")
+            .extension("user_id", 42);
+
+        let problem = ProblemDetails::try_from(&err).unwrap();
+
+        assert_eq!(
+            serde_json::to_value(problem).unwrap(),
+            json!({
+                "type": "urn:problem-type:NO_USER",
+                "title": "Not Found",
+                "status": 404,
+                "detail": "User not found",
+                "instance": "/users/42",
+                "user_id": 42,
+            }),
+        );
+    }
+
+    #[test]
+    fn ignores_extensions_named_after_reserved_members() {
+        let mut err = Error::new("NO_USER");
+        err.status(404).message("User not found");
+        for member in RESERVED_MEMBERS {
+            err.extension(*member, "overridden");
+        }
+        err.extension("user_id", 42);
+
+        let json = serde_json::to_string(&ProblemDetails::try_from(&err).unwrap()).unwrap();
+
+        assert_eq!(
+            serde_json::from_str::<Value>(&json).unwrap(),
+            json!({
+                "type": "urn:problem-type:NO_USER",
+                "title": "Not Found",
+                "status": 404,
+                "detail": "User not found",
+                "user_id": 42,
+            }),
+        );
+        assert_eq!(json.matches("\"status\"").count(), 1);
+    }
+
+    #[test]
+    fn rejects_invalid_status() {
+        let mut err = Error::new("OK");
+        err.status(200);
+
+        assert_eq!(ProblemDetails::try_from(&err), Err(InvalidStatus(200)));
+    }
+
+    #[tokio::test]
+    async fn responds_with_problem_json() {
+        let mut err = Error::new("NO_USER");
+        err.status(404).message("User not found");
+
+        let response = err.into_response();
+
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        assert_eq!(response.headers()[header::CONTENT_TYPE], CONTENT_TYPE);
+        assert_eq!(
+            body(response).await,
+            json!({
+                "type": "urn:problem-type:NO_USER",
+                "title": "Not Found",
+                "status": 404,
+                "detail": "User not found",
+            }),
+        );
+    }
+
+    #[tokio::test]
+    async fn responds_with_internal_error_on_invalid_status() {
+        let mut err = Error::new("WEIRD");
+        err.status(299);
+
+        let response = err.into_response();
+
+        assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+        assert_eq!(
+            body(response).await,
+            json!({
+                "type": "urn:problem-type:UNKNOWN",
+                "title": "Internal Server Error",
+                "status": 500,
+                "detail": "299 is not a known HTTP error status code",
+                "code": "WEIRD",
+            }),
+        );
+    }
+}
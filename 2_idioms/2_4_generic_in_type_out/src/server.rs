@@ -0,0 +1,512 @@
+use std::{
+    fmt,
+    future::Future,
+    io,
+    net::{SocketAddr, ToSocketAddrs},
+    sync::Arc,
+    time::Duration,
+};
+
+use tokio::{
+    net::{TcpListener, TcpStream},
+    sync::{mpsc, watch},
+    task::JoinSet,
+    time,
+};
+
+/// Callback reporting the errors of accepting connections.
+type AcceptErrorHandler = Arc<dyn Fn(&io::Error) + Send + Sync>;
+
+/// A TCP server accepting connections on any number of addresses.
+pub struct Server {
+    addrs: Vec<SocketAddr>,
+    drain_timeout: Duration,
+    accept_backoff: Duration,
+    on_accept_error: Option<AcceptErrorHandler>,
+}
+
+impl Default for Server {
+    #[inline]
+    fn default() -> Self {
+        Self {
+            addrs: Vec::new(),
+            drain_timeout: Duration::from_secs(30),
+            accept_backoff: Duration::from_secs(1),
+            on_accept_error: None,
+        }
+    }
+}
+
+impl fmt::Debug for Server {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Server")
+            .field("addrs", &self.addrs)
+            .field("drain_timeout", &self.drain_timeout)
+            .field("accept_backoff", &self.accept_backoff)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Server {
+    /// Adds all the addresses `addr` resolves to, so the server listens on each of them.
+    pub fn bind(&mut self, addr: impl ToSocketAddrs) -> io::Result<&mut Self> {
+        self.addrs.extend(addr.to_socket_addrs()?);
+        Ok(self)
+    }
+
+    /// Sets how long in-flight connections are waited for once the shutdown is requested.
+    pub fn drain_timeout(&mut self, timeout: Duration) -> &mut Self {
+        self.drain_timeout = timeout;
+        self
+    }
+
+    /// Sets how long a listener pauses after failing to accept a connection, e.g. when running out of file
+    /// descriptors, so it doesn't spin retrying.
+    pub fn accept_backoff(&mut self, backoff: Duration) -> &mut Self {
+        self.accept_backoff = backoff;
+        self
+    }
+
+    /// Sets the callback reporting the errors of accepting connections, which are ignored otherwise, as none of them
+    /// brings the whole server down.
+    pub fn on_accept_error(
+        &mut self,
+        handler: impl Fn(&io::Error) + Send + Sync + 'static,
+    ) -> &mut Self {
+        self.on_accept_error = Some(Arc::new(handler));
+        self
+    }
+
+    /// Addresses the server is going to listen on.
+    pub fn addrs(&self) -> &[SocketAddr] {
+        &self.addrs
+    }
+
+    /// Opens a listener on every bound address.
+    pub async fn listen(&self) -> io::Result<Listening> {
+        if self.addrs.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "no addresses to listen on",
+            ));
+        }
+
+        let mut listeners = Vec::with_capacity(self.addrs.len());
+        for addr in &self.addrs {
+            listeners.push(TcpListener::bind(addr).await?);
+        }
+
+        Ok(Listening {
+            listeners,
+            drain_timeout: self.drain_timeout,
+            accept_backoff: self.accept_backoff,
+            on_accept_error: self.on_accept_error.clone(),
+        })
+    }
+}
+
+/// Outcome of a [`Listening::serve`] run.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Shutdown {
+    /// All the in-flight connections have finished.
+    Graceful,
+    /// The drain timeout has elapsed, so the remaining connections were aborted.
+    TimedOut { aborted: usize },
+}
+
+/// A [`Server`] with its listeners open, ready to [`serve`](Listening::serve).
+pub struct Listening {
+    listeners: Vec<TcpListener>,
+    drain_timeout: Duration,
+    accept_backoff: Duration,
+    on_accept_error: Option<AcceptErrorHandler>,
+}
+
+impl fmt::Debug for Listening {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Listening")
+            .field("listeners", &self.listeners)
+            .field("drain_timeout", &self.drain_timeout)
+            .field("accept_backoff", &self.accept_backoff)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Listening {
+    /// Actual addresses of the open listeners, which is useful when binding to port `0`.
+    pub fn local_addrs(&self) -> io::Result<Vec<SocketAddr>> {
+        self.listeners.iter().map(TcpListener::local_addr).collect()
+    }
+
+    /// Runs the `handler` for every accepted connection until the `shutdown` future completes.
+    ///
+    /// Once it does, no new connections are accepted, while the ones accepted already are still handled, and all of
+    /// them are given the drain timeout to finish. Pass `tokio::signal::ctrl_c()` (requiring the `signal` feature of
+    /// `tokio`) to shut down on a signal, or any other future as a token.
+    pub async fn serve<H, F>(
+        self,
+        handler: H,
+        shutdown: impl Future<Output = ()>,
+    ) -> io::Result<Shutdown>
+    where
+        H: Fn(TcpStream, SocketAddr) -> F,
+        F: Future<Output = ()> + Send + 'static,
+    {
+        let (tx, mut accepted) = mpsc::channel::<(TcpStream, SocketAddr)>(1);
+        // Never sends anything, so the acceptors are stopped only once it's dropped.
+        let (stop, stopped) = watch::channel(());
+        let mut acceptors = JoinSet::new();
+        for listener in self.listeners {
+            let (tx, on_error) = (tx.clone(), self.on_accept_error.clone());
+            let backoff = self.accept_backoff;
+            let mut stopped = stopped.clone();
+            acceptors.spawn(async move {
+                let listener = &listener;
+                let stopped = async move {
+                    let _ = stopped.changed().await;
+                };
+                accept_loop(|| listener.accept(), backoff, on_error, tx, stopped).await;
+            });
+        }
+        drop(tx);
+
+        let mut connections = JoinSet::new();
+        tokio::pin!(shutdown);
+        loop {
+            tokio::select! {
+                () = &mut shutdown => break,
+                Some((stream, peer)) = accepted.recv() => {
+                    connections.spawn(handler(stream, peer));
+                }
+                // Reap finished connections, so they don't pile up.
+                Some(_) = connections.join_next(), if !connections.is_empty() => {}
+            }
+        }
+
+        // Connections accepted already are handed over before the acceptors finish, so none of them is dropped.
+        drop(stop);
+        while let Some((stream, peer)) = accepted.recv().await {
+            connections.spawn(handler(stream, peer));
+        }
+        while acceptors.join_next().await.is_some() {}
+
+        let drained = time::timeout(self.drain_timeout, async {
+            while connections.join_next().await.is_some() {}
+        })
+        .await;
+        match drained {
+            Ok(()) => Ok(Shutdown::Graceful),
+            Err(_) => {
+                let aborted = connections.len();
+                connections.shutdown().await;
+                Ok(Shutdown::TimedOut { aborted })
+            }
+        }
+    }
+}
+
+/// Sends the connections accepted with `accept` over `tx`, until it's closed or the `stop` future completes.
+///
+/// A connection accepted before stopping is still sent, so it's not lost.
+///
+/// Failing to accept a single connection shouldn't bring the whole server down. The peer aborting the connection
+/// concerns that connection only, while any other error (e.g. running out of file descriptors) is likely to persist
+/// for a while, so accepting is paused for the `backoff` instead of retrying in a busy loop.
+async fn accept_loop<T, F>(
+    mut accept: impl FnMut() -> F,
+    backoff: Duration,
+    on_error: Option<AcceptErrorHandler>,
+    tx: mpsc::Sender<T>,
+    stop: impl Future<Output = ()>,
+) where
+    F: Future<Output = io::Result<T>>,
+{
+    tokio::pin!(stop);
+    loop {
+        let accepted = tokio::select! {
+            biased;
+            () = &mut stop => break,
+            accepted = accept() => accepted,
+        };
+        match accepted {
+            Ok(conn) => {
+                if tx.send(conn).await.is_err() {
+                    break;
+                }
+            }
+            Err(e) => {
+                if let Some(on_error) = &on_error {
+                    on_error(&e);
+                }
+                let is_connection_error = matches!(
+                    e.kind(),
+                    io::ErrorKind::ConnectionAborted
+                        | io::ErrorKind::ConnectionRefused
+                        | io::ErrorKind::ConnectionReset
+                );
+                if !is_connection_error {
+                    tokio::select! {
+                        () = &mut stop => break,
+                        () = time::sleep(backoff) => {}
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod server_spec {
+    use std::net::{IpAddr, Ipv4Addr};
+
+    use tokio::{
+        io::{AsyncReadExt as _, AsyncWriteExt as _},
+        sync::oneshot,
+    };
+
+    use super::*;
+
+    /// Echoes everything back until the peer closes the connection.
+    async fn echo(mut stream: TcpStream, _: SocketAddr) {
+        let mut buf = [0; 64];
+        while let Ok(n @ 1..) = stream.read(&mut buf).await {
+            if stream.write_all(&buf[..n]).await.is_err() {
+                break;
+            }
+        }
+    }
+
+    async fn roundtrip(addr: SocketAddr, msg: &[u8]) -> Vec<u8> {
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        stream.write_all(msg).await.unwrap();
+        let mut buf = vec![0; msg.len()];
+        stream.read_exact(&mut buf).await.unwrap();
+        buf
+    }
+
+    mod bind {
+        use super::*;
+
+        #[test]
+        fn sets_provided_address_to_server() {
+            let mut server = Server::default();
+
+            server
+                .bind((IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 8080))
+                .unwrap();
+            assert_eq!(format!("{}", server.addrs()[0]), "127.0.0.1:8080");
+
+            server.bind("[::1]:9911").unwrap();
+            assert_eq!(format!("{}", server.addrs()[1]), "[::1]:9911");
+        }
+
+        #[test]
+        fn accepts_many_addresses_at_once() {
+            let mut server = Server::default();
+
+            server
+                .bind(&["127.0.0.1:1".parse().unwrap(), "[::1]:2".parse().unwrap()][..])
+                .unwrap();
+
+            assert_eq!(server.addrs().len(), 2);
+        }
+
+        #[tokio::test]
+        async fn fails_to_listen_without_addresses() {
+            let err = Server::default().listen().await.unwrap_err();
+
+            assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        }
+    }
+
+    mod accept_loop {
+        use std::{
+            future,
+            sync::{
+                atomic::{AtomicUsize, Ordering},
+                Mutex,
+            },
+        };
+
+        use tokio::time::Instant;
+
+        use super::*;
+
+        /// Accepts after failing with each of the `errors`, reporting them into the returned list.
+        async fn accept_after(
+            errors: Vec<io::ErrorKind>,
+            backoff: Duration,
+        ) -> (Duration, Vec<io::ErrorKind>) {
+            let reported = Arc::new(Mutex::new(Vec::new()));
+            let on_error: AcceptErrorHandler = {
+                let reported = reported.clone();
+                Arc::new(move |e: &io::Error| reported.lock().unwrap().push(e.kind()))
+            };
+            let attempts = AtomicUsize::new(0);
+            let (tx, mut rx) = mpsc::channel(1);
+
+            let start = Instant::now();
+            let accepting = accept_loop(
+                || {
+                    let attempt = attempts.fetch_add(1, Ordering::SeqCst);
+                    let result = errors
+                        .get(attempt)
+                        .map_or(Ok(attempt), |e| Err((*e).into()));
+                    async move { result }
+                },
+                backoff,
+                Some(on_error),
+                tx,
+                future::pending(),
+            );
+            tokio::select! {
+                () = accepting => unreachable!("the receiver is alive"),
+                accepted = rx.recv() => assert_eq!(accepted, Some(errors.len())),
+            }
+
+            let reported = reported.lock().unwrap().clone();
+            (start.elapsed(), reported)
+        }
+
+        #[tokio::test]
+        async fn backs_off_on_listener_errors() {
+            let errors = vec![io::ErrorKind::Other; 3];
+
+            let (elapsed, reported) = accept_after(errors.clone(), Duration::from_millis(50)).await;
+
+            assert!(elapsed >= Duration::from_millis(150), "{elapsed:?}");
+            assert_eq!(reported, errors);
+        }
+
+        #[tokio::test]
+        async fn sends_connection_accepted_before_stopping() {
+            let (tx, mut rx) = mpsc::channel(1);
+            let (stop, stopped) = oneshot::channel::<()>();
+            let mut attempts = 0;
+
+            let accepting = tokio::spawn(accept_loop(
+                move || {
+                    attempts += 1;
+                    let attempt = attempts;
+                    async move { Ok(attempt) }
+                },
+                Duration::ZERO,
+                None,
+                tx,
+                async {
+                    stopped.await.ok();
+                },
+            ));
+            // Lets the second connection wait for the first one to be received.
+            time::sleep(Duration::from_millis(50)).await;
+            stop.send(()).unwrap();
+
+            let mut received = Vec::new();
+            while let Some(attempt) = rx.recv().await {
+                received.push(attempt);
+            }
+            assert_eq!(received, [1, 2]);
+            accepting.await.unwrap();
+        }
+
+        #[tokio::test]
+        async fn retries_at_once_on_connection_errors() {
+            let errors = vec![
+                io::ErrorKind::ConnectionAborted,
+                io::ErrorKind::ConnectionReset,
+            ];
+
+            let (elapsed, reported) = accept_after(errors.clone(), Duration::from_secs(10)).await;
+
+            assert!(elapsed < Duration::from_secs(10), "{elapsed:?}");
+            assert_eq!(reported, errors);
+        }
+    }
+
+    mod serve {
+        use super::*;
+
+        #[tokio::test]
+        async fn handles_connections_on_all_listeners() {
+            let mut server = Server::default();
+            server.bind("127.0.0.1:0").unwrap().bind("[::1]:0").unwrap();
+            let listening = server.listen().await.unwrap();
+            let addrs = listening.local_addrs().unwrap();
+            let (stop, stopped) = oneshot::channel();
+
+            let serving = tokio::spawn(listening.serve(echo, async {
+                stopped.await.ok();
+            }));
+
+            assert!(addrs[0].is_ipv4());
+            assert!(addrs[1].is_ipv6());
+            for addr in addrs {
+                assert_eq!(roundtrip(addr, b"hello").await, b"hello");
+            }
+
+            stop.send(()).unwrap();
+            assert_eq!(serving.await.unwrap().unwrap(), Shutdown::Graceful);
+        }
+
+        #[tokio::test]
+        async fn drains_in_flight_connections_on_shutdown() {
+            let mut server = Server::default();
+            server.bind("127.0.0.1:0").unwrap();
+            let listening = server.listen().await.unwrap();
+            let addr = listening.local_addrs().unwrap()[0];
+            let (stop, stopped) = oneshot::channel();
+
+            let serving = tokio::spawn(listening.serve(echo, async {
+                stopped.await.ok();
+            }));
+
+            let mut client = TcpStream::connect(addr).await.unwrap();
+            client.write_all(b"ping").await.unwrap();
+            let mut buf = [0; 4];
+            client.read_exact(&mut buf).await.unwrap();
+
+            stop.send(()).unwrap();
+            time::sleep(Duration::from_millis(50)).await;
+            assert!(!serving.is_finished(), "must wait for in-flight connection");
+            assert!(
+                TcpStream::connect(addr).await.is_err(),
+                "must stop accepting new connections",
+            );
+
+            client.write_all(b"pong").await.unwrap();
+            client.read_exact(&mut buf).await.unwrap();
+            assert_eq!(&buf, b"pong");
+            drop(client);
+
+            assert_eq!(serving.await.unwrap().unwrap(), Shutdown::Graceful);
+        }
+
+        #[tokio::test]
+        async fn aborts_connections_after_drain_timeout() {
+            let mut server = Server::default();
+            server
+                .bind("127.0.0.1:0")
+                .unwrap()
+                .drain_timeout(Duration::from_millis(50));
+            let listening = server.listen().await.unwrap();
+            let addr = listening.local_addrs().unwrap()[0];
+            let (stop, stopped) = oneshot::channel();
+
+            let serving = tokio::spawn(listening.serve(echo, async {
+                stopped.await.ok();
+            }));
+
+            let mut client = TcpStream::connect(addr).await.unwrap();
+            client.write_all(b"ping").await.unwrap();
+            let mut buf = [0; 4];
+            client.read_exact(&mut buf).await.unwrap();
+
+            stop.send(()).unwrap();
+            assert_eq!(
+                serving.await.unwrap().unwrap(),
+                Shutdown::TimedOut { aborted: 1 },
+            );
+            assert_eq!(client.read(&mut buf).await.unwrap(), 0);
+        }
+    }
+}
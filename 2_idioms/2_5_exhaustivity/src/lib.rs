@@ -2,8 +2,13 @@ pub trait EventSourced<Ev: ?Sized> {
     fn apply(&mut self, event: &Ev);
 }
 
+pub mod presence;
+
 pub mod user {
-    use std::time::SystemTime;
+    use std::{borrow::Borrow, time::SystemTime};
+
+    use serde::{Deserialize, Serialize};
+    use thiserror::Error;
 
     use super::{event, EventSourced};
 
@@ -73,7 +78,95 @@ pub mod user {
         }
     }
 
-    #[derive(Debug)]
+    impl User {
+        /// Restores a [`User`] from its whole event log.
+        ///
+        /// The log must start with [`Event::Created`], must not have anything after [`Event::Deleted`], and must
+        /// contain events of the created user only, ordered by their time.
+        pub fn replay<I>(events: I) -> Result<Self, ReplayError>
+        where
+            I: IntoIterator,
+            I::Item: Borrow<Event>,
+        {
+            let mut events = events.into_iter();
+
+            let mut user = match events.next() {
+                Some(ev) => match ev.borrow() {
+                    Event::Created(ev) => Self {
+                        id: ev.user_id,
+                        name: None,
+                        online_since: None,
+                        created_at: ev.at,
+                        last_activity_at: LastActivityDateTime(ev.at.0),
+                        deleted_at: None,
+                    },
+                    ev => return Err(ReplayError::NotCreated { actual: ev.kind() }),
+                },
+                None => return Err(ReplayError::Empty),
+            };
+            let mut last_at = user.created_at.0;
+
+            for (index, ev) in events.enumerate().map(|(i, ev)| (i + 1, ev)) {
+                let ev = ev.borrow();
+
+                if ev.user_id() != user.id {
+                    return Err(ReplayError::ForeignUser {
+                        index,
+                        expected: user.id,
+                        actual: ev.user_id(),
+                    });
+                }
+                if user.deleted_at.is_some() {
+                    return Err(ReplayError::AfterDeletion {
+                        index,
+                        kind: ev.kind(),
+                    });
+                }
+                if let Event::Created(_) = ev {
+                    return Err(ReplayError::AlreadyCreated { index });
+                }
+                if ev.at() < last_at {
+                    return Err(ReplayError::OutOfOrder { index });
+                }
+
+                last_at = ev.at();
+                user.apply(ev);
+            }
+
+            Ok(user)
+        }
+    }
+
+    /// Reason of a [`User::replay`] failure.
+    ///
+    /// An `index` is the position of the offending event in the log.
+    #[derive(Clone, Copy, Debug, Error, PartialEq, Eq)]
+    pub enum ReplayError {
+        #[error("event log is empty")]
+        Empty,
+
+        #[error("event log starts with `{actual}` instead of `created`")]
+        NotCreated { actual: &'static str },
+
+        #[error("event #{index} creates already created user")]
+        AlreadyCreated { index: usize },
+
+        #[error("event #{index} `{kind}` happens after the user is deleted")]
+        AfterDeletion { index: usize, kind: &'static str },
+
+        #[error("event #{index} belongs to user {} instead of {}", actual.0, expected.0)]
+        ForeignUser {
+            index: usize,
+            expected: Id,
+            actual: Id,
+        },
+
+        #[error("event #{index} happens earlier than the previous one")]
+        OutOfOrder { index: usize },
+    }
+
+    #[derive(Debug, Serialize, Deserialize)]
+    #[serde(tag = "type", rename_all = "snake_case")]
     pub enum Event {
         Created(event::UserCreated),
         NameUpdated(event::UserNameUpdated),
@@ -104,55 +197,258 @@ pub mod user {
         }
     }
 
-    #[derive(Clone, Copy, Debug)]
+    impl Event {
+        /// The [`User`] this event belongs to.
+        pub fn user_id(&self) -> Id {
+            match self {
+                Event::Created(ev) => ev.user_id,
+                Event::NameUpdated(ev) => ev.user_id,
+                Event::Online(ev) => ev.user_id,
+                Event::Offline(ev) => ev.user_id,
+                Event::Deleted(ev) => ev.user_id,
+            }
+        }
+
+        /// The moment this event has happened at.
+        pub fn at(&self) -> SystemTime {
+            match self {
+                Event::Created(ev) => ev.at.0,
+                Event::NameUpdated(ev) => ev.at,
+                Event::Online(ev) => ev.at,
+                Event::Offline(ev) => ev.at,
+                Event::Deleted(ev) => ev.at.0,
+            }
+        }
+
+        /// The name of this event's variant, as it's tagged in serialized form.
+        pub fn kind(&self) -> &'static str {
+            match self {
+                Event::Created(_) => "created",
+                Event::NameUpdated(_) => "name_updated",
+                Event::Online(_) => "online",
+                Event::Offline(_) => "offline",
+                Event::Deleted(_) => "deleted",
+            }
+        }
+    }
+
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
     pub struct Id(pub u64);
 
-    #[derive(Clone, Debug)]
+    #[derive(Clone, Debug, Serialize, Deserialize)]
     pub struct Name(pub Box<str>);
 
-    #[derive(Clone, Copy, Debug)]
+    #[derive(Clone, Copy, Debug, Serialize, Deserialize)]
     pub struct CreationDateTime(pub SystemTime);
 
     #[derive(Clone, Copy, Debug)]
     pub struct LastActivityDateTime(pub SystemTime);
 
-    #[derive(Clone, Copy, Debug)]
+    #[derive(Clone, Copy, Debug, Serialize, Deserialize)]
     pub struct DeletionDateTime(pub SystemTime);
 }
 
 pub mod event {
     use std::time::SystemTime;
 
+    use serde::{Deserialize, Serialize};
+
     use super::user;
 
-    #[derive(Debug)]
+    #[derive(Debug, Serialize, Deserialize)]
     pub struct UserCreated {
         pub user_id: user::Id,
         pub at: user::CreationDateTime,
     }
 
-    #[derive(Debug)]
+    #[derive(Debug, Serialize, Deserialize)]
     pub struct UserNameUpdated {
         pub user_id: user::Id,
         pub name: Option<user::Name>,
         pub at: SystemTime,
     }
 
-    #[derive(Debug)]
+    #[derive(Debug, Serialize, Deserialize)]
     pub struct UserBecameOnline {
         pub user_id: user::Id,
         pub at: SystemTime,
     }
 
-    #[derive(Debug)]
+    #[derive(Debug, Serialize, Deserialize)]
     pub struct UserBecameOffline {
         pub user_id: user::Id,
         pub at: SystemTime,
     }
 
-    #[derive(Debug)]
+    #[derive(Debug, Serialize, Deserialize)]
     pub struct UserDeleted {
         pub user_id: user::Id,
         pub at: user::DeletionDateTime,
     }
 }
+
+#[cfg(test)]
+mod spec {
+    use std::time::{Duration, SystemTime};
+
+    use super::{
+        event,
+        user::{self, Event, ReplayError, User},
+    };
+
+    fn at(secs: u64) -> SystemTime {
+        SystemTime::UNIX_EPOCH + Duration::from_secs(secs)
+    }
+
+    fn created(id: u64, secs: u64) -> Event {
+        Event::Created(event::UserCreated {
+            user_id: user::Id(id),
+            at: user::CreationDateTime(at(secs)),
+        })
+    }
+
+    fn renamed(id: u64, name: &str, secs: u64) -> Event {
+        Event::NameUpdated(event::UserNameUpdated {
+            user_id: user::Id(id),
+            name: Some(user::Name(name.into())),
+            at: at(secs),
+        })
+    }
+
+    fn online(id: u64, secs: u64) -> Event {
+        Event::Online(event::UserBecameOnline {
+            user_id: user::Id(id),
+            at: at(secs),
+        })
+    }
+
+    fn deleted(id: u64, secs: u64) -> Event {
+        Event::Deleted(event::UserDeleted {
+            user_id: user::Id(id),
+            at: user::DeletionDateTime(at(secs)),
+        })
+    }
+
+    mod replay {
+        use super::*;
+
+        #[test]
+        fn restores_user_from_valid_log() {
+            let user = User::replay([
+                created(1, 10),
+                renamed(1, "Alice", 20),
+                online(1, 30),
+                deleted(1, 40),
+            ])
+            .unwrap();
+
+            assert_eq!(user.id, user::Id(1));
+            assert_eq!(user.name.unwrap().0.as_ref(), "Alice");
+            assert_eq!(user.online_since, Some(at(30)));
+            assert_eq!(user.created_at.0, at(10));
+            assert_eq!(user.last_activity_at.0, at(40));
+            assert_eq!(user.deleted_at.unwrap().0, at(40));
+        }
+
+        #[test]
+        fn accepts_borrowed_events() {
+            let log = vec![created(1, 10), renamed(1, "Bob", 10)];
+
+            let user = User::replay(&log).unwrap();
+
+            assert_eq!(user.name.unwrap().0.as_ref(), "Bob");
+        }
+
+        #[test]
+        fn requires_log_to_start_with_creation() {
+            assert_eq!(
+                User::replay(Vec::<Event>::new()).unwrap_err(),
+                ReplayError::Empty
+            );
+            assert_eq!(
+                User::replay([online(1, 10), created(1, 20)]).unwrap_err(),
+                ReplayError::NotCreated { actual: "online" },
+            );
+            assert_eq!(
+                User::replay([created(1, 10), created(1, 20)]).unwrap_err(),
+                ReplayError::AlreadyCreated { index: 1 },
+            );
+        }
+
+        #[test]
+        fn rejects_events_after_deletion() {
+            assert_eq!(
+                User::replay([created(1, 10), deleted(1, 20), renamed(1, "Zombie", 30)])
+                    .unwrap_err(),
+                ReplayError::AfterDeletion {
+                    index: 2,
+                    kind: "name_updated",
+                },
+            );
+        }
+
+        #[test]
+        fn rejects_events_of_other_users() {
+            assert_eq!(
+                User::replay([created(1, 10), online(2, 20)]).unwrap_err(),
+                ReplayError::ForeignUser {
+                    index: 1,
+                    expected: user::Id(1),
+                    actual: user::Id(2),
+                },
+            );
+        }
+
+        #[test]
+        fn rejects_events_going_back_in_time() {
+            assert_eq!(
+                User::replay([created(1, 10), online(1, 30), renamed(1, "Late", 20)]).unwrap_err(),
+                ReplayError::OutOfOrder { index: 2 },
+            );
+        }
+    }
+
+    mod serde {
+        use super::*;
+
+        #[test]
+        fn serializes_events_as_tagged_json() {
+            let json = serde_json::to_value(online(7, 5)).unwrap();
+
+            assert_eq!(
+                json,
+                serde_json::json!({
+                    "type": "online",
+                    "user_id": 7,
+                    "at": {"secs_since_epoch": 5, "nanos_since_epoch": 0},
+                }),
+            );
+        }
+
+        #[test]
+        fn replays_log_loaded_from_json() {
+            let log = r#"[
+                {"type": "created", "user_id": 3, "at": {"secs_since_epoch": 1, "nanos_since_epoch": 0}},
+                {"type": "name_updated", "user_id": 3, "name": "Carol", "at": {"secs_since_epoch": 2, "nanos_since_epoch": 0}},
+                {"type": "online", "user_id": 3, "at": {"secs_since_epoch": 3, "nanos_since_epoch": 0}},
+                {"type": "offline", "user_id": 3, "at": {"secs_since_epoch": 4, "nanos_since_epoch": 0}}
+            ]"#;
+
+            let events = serde_json::from_str::<Vec<Event>>(log).unwrap();
+            let kinds = events.iter().map(Event::kind).collect::<Vec<_>>();
+            let user = User::replay(events).unwrap();
+
+            assert_eq!(kinds, ["created", "name_updated", "online", "offline"]);
+            assert_eq!(user.name.unwrap().0.as_ref(), "Carol");
+            assert_eq!(user.online_since, None);
+            assert_eq!(user.last_activity_at.0, at(4));
+        }
+
+        #[test]
+        fn fails_on_unknown_event_type() {
+            let log = r#"{"type": "promoted", "user_id": 3}"#;
+
+            assert!(serde_json::from_str::<Event>(log).is_err());
+        }
+    }
+}
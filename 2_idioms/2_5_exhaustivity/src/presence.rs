@@ -0,0 +1,478 @@
+//! Online-presence analytics derived from [`event::UserBecameOnline`] and [`event::UserBecameOffline`] events.
+
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    ops::Range,
+    time::{Duration, SystemTime},
+};
+
+use super::{event, user, EventSourced};
+
+/// Whether a user went online or offline.
+///
+/// Ordered so that, at the same moment, going online is considered before going offline.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+enum Transition {
+    Online,
+    Offline,
+}
+
+/// A continuous period of a user being online.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Session {
+    pub start: SystemTime,
+    pub end: SessionEnd,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SessionEnd {
+    /// The user went offline explicitly.
+    Offline(SystemTime),
+    /// No offline event came in time, so the session is considered ended once its timeout elapsed.
+    TimedOut(SystemTime),
+    /// The user is still online.
+    Ongoing,
+}
+
+impl Session {
+    /// The moment the session ended at, if it did.
+    pub fn end_at(&self) -> Option<SystemTime> {
+        match self.end {
+            SessionEnd::Offline(at) | SessionEnd::TimedOut(at) => Some(at),
+            SessionEnd::Ongoing => None,
+        }
+    }
+
+    /// The part of the session lying within the `window`, if any.
+    fn clip(&self, window: &Range<SystemTime>) -> Option<Range<SystemTime>> {
+        let start = self.start.max(window.start);
+        let end = self.end_at().map_or(window.end, |end| end.min(window.end));
+        (start < end).then_some(start..end)
+    }
+}
+
+/// A projection of users' online presence.
+///
+/// Events may arrive in any order and may be delivered more than once: sessions are always derived from the events
+/// sorted by their time. A session missing its offline event is considered timed out once no online event for the
+/// user comes within the configured timeout.
+///
+/// Every event is kept, until the ones no longer needed are forgotten with [`Presence::forget_before`].
+#[derive(Debug)]
+pub struct Presence {
+    timeout: Duration,
+    transitions: HashMap<u64, BTreeSet<(SystemTime, Transition)>>,
+}
+
+impl Presence {
+    pub fn new(timeout: Duration) -> Self {
+        Self {
+            timeout,
+            transitions: HashMap::new(),
+        }
+    }
+
+    fn record(&mut self, user_id: user::Id, at: SystemTime, transition: Transition) {
+        self.transitions
+            .entry(user_id.0)
+            .or_default()
+            .insert((at, transition));
+    }
+
+    /// Session history of the user, as it's seen at the moment `now`.
+    ///
+    /// Events happening after `now` are not taken into account.
+    pub fn sessions(&self, user_id: user::Id, now: SystemTime) -> Vec<Session> {
+        self.transitions
+            .get(&user_id.0)
+            .map_or_else(Vec::new, |t| sessions(t, self.timeout, now))
+    }
+
+    /// Forgets the events of the sessions ended before the moment `before`, so the memory taken doesn't grow forever.
+    ///
+    /// Sessions and online time are complete only for the windows starting at `before` or later afterwards, while the
+    /// events older than `before` delivered afterwards are taken into account with the forgotten ones missing.
+    pub fn forget_before(&mut self, before: SystemTime) {
+        let timeout = self.timeout;
+        self.transitions.retain(|_, transitions| {
+            // A session not ended yet may still be ended or extended by the later events, so it's kept whole.
+            let keep_from = sessions(transitions, timeout, before)
+                .into_iter()
+                .find(|s| s.end_at().is_none_or(|end| end >= before))
+                .map_or(before, |s| s.start);
+            *transitions = transitions.split_off(&(keep_from, Transition::Online));
+            !transitions.is_empty()
+        });
+    }
+
+    /// Total time the user was online within the `window`.
+    pub fn online_time(&self, user_id: user::Id, window: Range<SystemTime>) -> Duration {
+        self.sessions(user_id, window.end)
+            .iter()
+            .filter_map(|s| s.clip(&window))
+            .map(|r| r.end.duration_since(r.start).unwrap_or_default())
+            .sum()
+    }
+
+    /// Number of users being online concurrently within the `window`.
+    ///
+    /// Returns the moments the number changes at, starting with the number at the beginning of the `window`.
+    pub fn concurrency(&self, window: Range<SystemTime>) -> Vec<(SystemTime, usize)> {
+        let mut deltas = BTreeMap::<SystemTime, isize>::new();
+        for &user_id in self.transitions.keys() {
+            for session in self.sessions(user::Id(user_id), window.end) {
+                if let Some(r) = session.clip(&window) {
+                    *deltas.entry(r.start).or_default() += 1;
+                    *deltas.entry(r.end).or_default() -= 1;
+                }
+            }
+        }
+
+        let mut online = 0;
+        let mut changes = vec![(window.start, 0)];
+        for (at, delta) in deltas {
+            if delta == 0 || at == window.end {
+                continue;
+            }
+            online += delta;
+            let count = usize::try_from(online).expect("sessions never end before start");
+            match changes.last_mut() {
+                Some(last) if last.0 == at => last.1 = count,
+                _ => changes.push((at, count)),
+            }
+        }
+        changes
+    }
+}
+
+/// Derives the sessions from the `transitions` of a single user, as they're seen at the moment `now`.
+fn sessions(
+    transitions: &BTreeSet<(SystemTime, Transition)>,
+    timeout: Duration,
+    now: SystemTime,
+) -> Vec<Session> {
+    // The session never expires, when its expiry lies beyond the representable time.
+    let expiry = |seen: SystemTime| seen.checked_add(timeout);
+
+    let mut sessions = Vec::new();
+    // Start of the current session along with the moment the user was last seen online in it.
+    let mut current: Option<(SystemTime, SystemTime)> = None;
+
+    for &(at, transition) in transitions.iter().take_while(|(at, _)| *at <= now) {
+        if let Some((start, seen)) = current {
+            if let Some(expires) = expiry(seen).filter(|expires| *expires < at) {
+                sessions.push(Session {
+                    start,
+                    end: SessionEnd::TimedOut(expires),
+                });
+                current = None;
+            }
+        }
+
+        current = match (transition, current) {
+            (Transition::Online, None) => Some((at, at)),
+            (Transition::Online, Some((start, _))) => Some((start, at)),
+            (Transition::Offline, Some((start, _))) => {
+                sessions.push(Session {
+                    start,
+                    end: SessionEnd::Offline(at),
+                });
+                None
+            }
+            // Nothing to end, as the session has already timed out, or its start is missing.
+            (Transition::Offline, None) => None,
+        };
+    }
+
+    if let Some((start, seen)) = current {
+        sessions.push(Session {
+            start,
+            end: match expiry(seen) {
+                Some(expires) if expires < now => SessionEnd::TimedOut(expires),
+                _ => SessionEnd::Ongoing,
+            },
+        });
+    }
+
+    sessions
+}
+
+impl EventSourced<event::UserBecameOnline> for Presence {
+    fn apply(&mut self, ev: &event::UserBecameOnline) {
+        let event::UserBecameOnline { user_id, at } = ev;
+
+        self.record(*user_id, *at, Transition::Online);
+    }
+}
+
+impl EventSourced<event::UserBecameOffline> for Presence {
+    fn apply(&mut self, ev: &event::UserBecameOffline) {
+        let event::UserBecameOffline { user_id, at } = ev;
+
+        self.record(*user_id, *at, Transition::Offline);
+    }
+}
+
+impl EventSourced<user::Event> for Presence {
+    fn apply(&mut self, ev: &user::Event) {
+        match ev {
+            user::Event::Online(ev) => {
+                self.apply(ev);
+            }
+            user::Event::Offline(ev) => {
+                self.apply(ev);
+            }
+            user::Event::Created(_) | user::Event::NameUpdated(_) | user::Event::Deleted(_) => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod spec {
+    use super::*;
+
+    const TIMEOUT: Duration = Duration::from_secs(100);
+
+    fn at(secs: u64) -> SystemTime {
+        SystemTime::UNIX_EPOCH + Duration::from_secs(secs)
+    }
+
+    fn online(id: u64, secs: u64) -> user::Event {
+        user::Event::Online(event::UserBecameOnline {
+            user_id: user::Id(id),
+            at: at(secs),
+        })
+    }
+
+    fn offline(id: u64, secs: u64) -> user::Event {
+        user::Event::Offline(event::UserBecameOffline {
+            user_id: user::Id(id),
+            at: at(secs),
+        })
+    }
+
+    fn presence(events: impl IntoIterator<Item = user::Event>) -> Presence {
+        let mut presence = Presence::new(TIMEOUT);
+        for ev in events {
+            presence.apply(&ev);
+        }
+        presence
+    }
+
+    fn session(start: u64, end: SessionEnd) -> Session {
+        Session {
+            start: at(start),
+            end,
+        }
+    }
+
+    mod sessions {
+        use super::*;
+
+        #[test]
+        fn are_built_from_online_and_offline_pairs() {
+            let p = presence([online(1, 10), offline(1, 20), online(1, 30), offline(1, 50)]);
+
+            assert_eq!(
+                p.sessions(user::Id(1), at(60)),
+                [
+                    session(10, SessionEnd::Offline(at(20))),
+                    session(30, SessionEnd::Offline(at(50))),
+                ],
+            );
+            assert_eq!(p.sessions(user::Id(2), at(60)), []);
+        }
+
+        #[test]
+        fn time_out_without_offline_event() {
+            let p = presence([online(1, 10), online(1, 300), offline(1, 350)]);
+
+            assert_eq!(
+                p.sessions(user::Id(1), at(400)),
+                [
+                    session(10, SessionEnd::TimedOut(at(110))),
+                    session(300, SessionEnd::Offline(at(350))),
+                ],
+            );
+        }
+
+        #[test]
+        fn are_extended_by_repeated_online_events() {
+            let p = presence([online(1, 10), online(1, 90), offline(1, 150)]);
+
+            assert_eq!(
+                p.sessions(user::Id(1), at(200)),
+                [session(10, SessionEnd::Offline(at(150)))],
+            );
+        }
+
+        #[test]
+        fn are_ongoing_until_timeout_elapses() {
+            let p = presence([online(1, 10)]);
+
+            assert_eq!(
+                p.sessions(user::Id(1), at(50)),
+                [session(10, SessionEnd::Ongoing)],
+            );
+            assert_eq!(
+                p.sessions(user::Id(1), at(500)),
+                [session(10, SessionEnd::TimedOut(at(110)))],
+            );
+        }
+
+        #[test]
+        fn ignore_events_after_now() {
+            let p = presence([online(1, 10), offline(1, 20)]);
+
+            assert_eq!(
+                p.sessions(user::Id(1), at(15)),
+                [session(10, SessionEnd::Ongoing)],
+            );
+        }
+
+        #[test]
+        fn tolerate_out_of_order_and_duplicate_events() {
+            let p = presence([
+                offline(1, 50),
+                online(1, 30),
+                offline(1, 20),
+                online(1, 10),
+                offline(1, 20),
+                offline(1, 5),
+            ]);
+
+            assert_eq!(
+                p.sessions(user::Id(1), at(60)),
+                [
+                    session(10, SessionEnd::Offline(at(20))),
+                    session(30, SessionEnd::Offline(at(50))),
+                ],
+            );
+        }
+
+        #[test]
+        fn never_time_out_beyond_representable_time() {
+            let mut p = Presence::new(Duration::MAX);
+            p.apply(&online(1, 10));
+
+            assert_eq!(
+                p.sessions(user::Id(1), at(500)),
+                [session(10, SessionEnd::Ongoing)],
+            );
+        }
+    }
+
+    mod forget_before {
+        use super::*;
+
+        #[test]
+        fn drops_sessions_ended_before() {
+            let mut p = presence([
+                online(1, 10),
+                offline(1, 20),
+                online(1, 30),
+                online(2, 10),
+                offline(2, 20),
+            ]);
+            p.forget_before(at(25));
+
+            assert_eq!(
+                p.sessions(user::Id(1), at(60)),
+                [session(30, SessionEnd::Ongoing)],
+            );
+            assert_eq!(p.sessions(user::Id(2), at(60)), []);
+            assert!(!p.transitions.contains_key(&2));
+        }
+
+        #[test]
+        fn keeps_sessions_not_ended_before() {
+            let mut p = presence([online(1, 10), online(1, 90), offline(1, 150), online(2, 20)]);
+            p.forget_before(at(100));
+
+            assert_eq!(
+                p.sessions(user::Id(1), at(200)),
+                [session(10, SessionEnd::Offline(at(150)))],
+            );
+            // Timed out at 120, which is after the moment the events are forgotten before.
+            assert_eq!(
+                p.sessions(user::Id(2), at(200)),
+                [session(20, SessionEnd::TimedOut(at(120)))],
+            );
+
+            p.forget_before(at(130));
+            assert_eq!(
+                p.sessions(user::Id(1), at(200)),
+                [session(10, SessionEnd::Offline(at(150)))],
+            );
+            assert_eq!(p.sessions(user::Id(2), at(200)), []);
+        }
+    }
+
+    mod online_time {
+        use super::*;
+
+        #[test]
+        fn sums_sessions_clipped_to_window() {
+            let p = presence([online(1, 10), offline(1, 20), online(1, 30), offline(1, 50)]);
+
+            assert_eq!(
+                p.online_time(user::Id(1), at(0)..at(100)),
+                Duration::from_secs(30),
+            );
+            assert_eq!(
+                p.online_time(user::Id(1), at(15)..at(40)),
+                Duration::from_secs(15),
+            );
+            assert_eq!(p.online_time(user::Id(1), at(60)..at(100)), Duration::ZERO,);
+        }
+
+        #[test]
+        fn counts_ongoing_session_up_to_window_end() {
+            let p = presence([online(1, 10)]);
+
+            assert_eq!(
+                p.online_time(user::Id(1), at(0)..at(60)),
+                Duration::from_secs(50),
+            );
+            assert_eq!(p.online_time(user::Id(1), at(0)..at(1000)), TIMEOUT,);
+        }
+    }
+
+    mod concurrency {
+        use super::*;
+
+        #[test]
+        fn tracks_number_of_online_users() {
+            let p = presence([
+                online(1, 10),
+                online(2, 20),
+                offline(1, 30),
+                online(3, 30),
+                offline(2, 40),
+                offline(3, 50),
+                user::Event::Deleted(event::UserDeleted {
+                    user_id: user::Id(3),
+                    at: user::DeletionDateTime(at(60)),
+                }),
+            ]);
+
+            assert_eq!(
+                p.concurrency(at(0)..at(100)),
+                [
+                    (at(0), 0),
+                    (at(10), 1),
+                    (at(20), 2),
+                    (at(40), 1),
+                    (at(50), 0),
+                ],
+            );
+        }
+
+        #[test]
+        fn starts_with_users_online_at_window_start() {
+            let p = presence([online(1, 10), online(2, 20), offline(1, 30)]);
+
+            assert_eq!(p.concurrency(at(25)..at(50)), [(at(25), 2), (at(30), 1)],);
+        }
+    }
+}
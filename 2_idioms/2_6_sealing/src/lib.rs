@@ -1,7 +1,10 @@
 pub mod my_error;
 pub mod my_iterator_ext;
 
-pub use self::{my_error::MyError, my_iterator_ext::MyIteratorExt};
+pub use self::{
+    my_error::{MyError, Report},
+    my_iterator_ext::MyIteratorExt,
+};
 use std::any::TypeId;
 use std::fmt::{Debug, Display, Formatter};
 
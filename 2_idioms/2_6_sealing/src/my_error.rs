@@ -3,7 +3,9 @@
 /// Simplified version of [`std::error::Error`].
 use std::{
     any::TypeId,
-    fmt::{Debug, Display},
+    backtrace::{Backtrace, BacktraceStatus},
+    error::Error as StdError,
+    fmt::{self, Debug, Display},
 };
 
 mod private {
@@ -105,8 +107,484 @@ pub trait MyError: Debug + Display {
     }
 }
 
-impl<'a, T: MyError + ?Sized> MyError for &'a T {
+impl<T: MyError + ?Sized> MyError for &T {
     fn source(&self) -> Option<&(dyn MyError + 'static)> {
         MyError::source(&**self)
     }
 }
+
+impl<T: MyError + ?Sized> MyError for Box<T> {
+    fn source(&self) -> Option<&(dyn MyError + 'static)> {
+        MyError::source(&**self)
+    }
+}
+
+macro_rules! impl_downcast {
+    ($($bounds:tt)*) => {
+        impl dyn MyError $($bounds)* {
+            /// Returns `true` if the inner type is the same as `T`.
+            #[inline]
+            pub fn is<T: MyError + 'static>(&self) -> bool {
+                self.type_id(private::Token) == TypeId::of::<T>()
+            }
+
+            /// Returns some reference to the inner value if it is of type `T`, or `None` if it isn't.
+            #[inline]
+            pub fn downcast_ref<T: MyError + 'static>(&self) -> Option<&T> {
+                if self.is::<T>() {
+                    // SAFETY: `is` ensures this type cast is correct, and `type_id` cannot be overridden outside
+                    //         of this module, as it requires the private `Token`.
+                    Some(unsafe { &*(self as *const Self as *const T) })
+                } else {
+                    None
+                }
+            }
+
+            /// Returns some mutable reference to the inner value if it is of type `T`, or `None` if it isn't.
+            #[inline]
+            pub fn downcast_mut<T: MyError + 'static>(&mut self) -> Option<&mut T> {
+                if self.is::<T>() {
+                    // SAFETY: Same as in `downcast_ref`.
+                    Some(unsafe { &mut *(self as *mut Self as *mut T) })
+                } else {
+                    None
+                }
+            }
+
+            /// Attempts to downcast the box to a concrete type, giving it back if it isn't of type `T`.
+            #[inline]
+            pub fn downcast<T: MyError + 'static>(self: Box<Self>) -> Result<Box<T>, Box<Self>> {
+                if self.is::<T>() {
+                    // SAFETY: Same as in `downcast_ref`.
+                    Ok(unsafe { Box::from_raw(Box::into_raw(self) as *mut T) })
+                } else {
+                    Err(self)
+                }
+            }
+
+            /// Iterates over this error and all its [`source`](MyError::source)s, starting with this one.
+            #[inline]
+            pub fn chain(&self) -> Chain<'_> {
+                Chain {
+                    next: Some(self),
+                }
+            }
+        }
+    };
+}
+
+impl_downcast!(+ 'static);
+impl_downcast!(+ Send + 'static);
+impl_downcast!(+ Send + Sync + 'static);
+
+/// An iterator over an error and its [`source`](MyError::source)s.
+///
+/// See [`chain()`](trait.MyError.html#method.chain) for more information.
+#[derive(Clone, Debug)]
+pub struct Chain<'a> {
+    next: Option<&'a (dyn MyError + 'static)>,
+}
+
+impl<'a> Iterator for Chain<'a> {
+    type Item = &'a (dyn MyError + 'static);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let current = self.next?;
+        self.next = current.source();
+        Some(current)
+    }
+}
+
+/// A wrapper printing an error along with all its [`source`](MyError::source)s and an optional [`Backtrace`].
+///
+/// Its [`Display`] prints everything on a single line, while its [`Debug`] (and alternate [`Display`]) prints
+/// every source on a separate line, which suits returning it from `main()`.
+pub struct Report<E = Box<dyn MyError + Send + Sync>> {
+    error: E,
+    backtrace: Option<Backtrace>,
+}
+
+impl<E: MyError + 'static> Report<E> {
+    /// Wraps the `error`, capturing a [`Backtrace`] if it's enabled via environment variables.
+    ///
+    /// See [`Backtrace::capture()`] for more information.
+    pub fn new(error: E) -> Self {
+        Self {
+            error,
+            backtrace: Some(Backtrace::capture()),
+        }
+    }
+
+    /// Sets the [`Backtrace`] to be printed, or disables printing it with `None`.
+    pub fn with_backtrace(mut self, backtrace: Option<Backtrace>) -> Self {
+        self.backtrace = backtrace;
+        self
+    }
+
+    /// The captured [`Backtrace`], if any.
+    pub fn backtrace(&self) -> Option<&Backtrace> {
+        self.backtrace
+            .as_ref()
+            .filter(|bt| bt.status() == BacktraceStatus::Captured)
+    }
+
+    pub fn error(&self) -> &E {
+        &self.error
+    }
+
+    pub fn into_error(self) -> E {
+        self.error
+    }
+
+    fn chain(&self) -> Chain<'_> {
+        Chain {
+            next: Some(&self.error),
+        }
+    }
+
+    fn fmt_pretty(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.error)?;
+
+        let sources = self.chain().skip(1).collect::<Vec<_>>();
+        if !sources.is_empty() {
+            write!(f, "\n\nCaused by:")?;
+            for (i, source) in sources.iter().enumerate() {
+                if sources.len() > 1 {
+                    write!(f, "\n{i:>5}: {source}")?;
+                } else {
+                    write!(f, "\n      {source}")?;
+                }
+            }
+        }
+
+        if let Some(backtrace) = self.backtrace() {
+            write!(f, "\n\nStack backtrace:\n{backtrace}")?;
+        }
+        Ok(())
+    }
+}
+
+impl<E: MyError + 'static> From<E> for Report<E> {
+    fn from(error: E) -> Self {
+        Self::new(error)
+    }
+}
+
+impl<E: MyError + 'static> Display for Report<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if f.alternate() {
+            return self.fmt_pretty(f);
+        }
+
+        write!(f, "{}", self.error)?;
+        for source in self.chain().skip(1) {
+            write!(f, ": {source}")?;
+        }
+        Ok(())
+    }
+}
+
+impl<E: MyError + 'static> Debug for Report<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.fmt_pretty(f)
+    }
+}
+
+/// A snapshot of an error [`source`](MyError::source), which is both a [`MyError`] and a [`std::error::Error`].
+///
+/// Sources are borrowed from their errors, so they cannot be re-wrapped into the other error trait. Instead, their
+/// messages are captured once an error is wrapped into [`IntoStd`] or [`FromStd`].
+#[derive(Clone, Debug)]
+pub struct Source {
+    message: String,
+    source: Option<Box<Source>>,
+}
+
+impl Source {
+    fn capture(messages: Vec<String>) -> Option<Box<Self>> {
+        messages.into_iter().rev().fold(None, |source, message| {
+            Some(Box::new(Self { message, source }))
+        })
+    }
+}
+
+impl Display for Source {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl MyError for Source {
+    fn source(&self) -> Option<&(dyn MyError + 'static)> {
+        self.source.as_deref().map(|s| s as _)
+    }
+}
+
+impl StdError for Source {
+    fn source(&self) -> Option<&(dyn StdError + 'static)> {
+        self.source.as_deref().map(|s| s as _)
+    }
+}
+
+/// A [`MyError`] usable as a [`std::error::Error`].
+#[derive(Debug)]
+pub struct IntoStd<E> {
+    error: E,
+    source: Option<Box<Source>>,
+}
+
+impl<E: MyError + 'static> IntoStd<E> {
+    pub fn new(error: E) -> Self {
+        let source = Source::capture(
+            Chain { next: Some(&error) }
+                .skip(1)
+                .map(|e| e.to_string())
+                .collect(),
+        );
+        Self { error, source }
+    }
+
+    pub fn into_inner(self) -> E {
+        self.error
+    }
+}
+
+impl<E: MyError + 'static> From<E> for IntoStd<E> {
+    fn from(error: E) -> Self {
+        Self::new(error)
+    }
+}
+
+impl<E: Display> Display for IntoStd<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        Display::fmt(&self.error, f)
+    }
+}
+
+impl<E: Debug + Display> StdError for IntoStd<E> {
+    fn source(&self) -> Option<&(dyn StdError + 'static)> {
+        self.source.as_deref().map(|s| s as _)
+    }
+}
+
+/// A [`std::error::Error`] usable as a [`MyError`].
+#[derive(Debug)]
+pub struct FromStd<E> {
+    error: E,
+    source: Option<Box<Source>>,
+}
+
+impl<E: StdError> FromStd<E> {
+    pub fn new(error: E) -> Self {
+        let mut sources = Vec::new();
+        let mut next = error.source();
+        while let Some(e) = next {
+            sources.push(e.to_string());
+            next = e.source();
+        }
+
+        Self {
+            source: Source::capture(sources),
+            error,
+        }
+    }
+
+    pub fn into_inner(self) -> E {
+        self.error
+    }
+}
+
+impl<E: StdError> From<E> for FromStd<E> {
+    fn from(error: E) -> Self {
+        Self::new(error)
+    }
+}
+
+impl<E: Display> Display for FromStd<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        Display::fmt(&self.error, f)
+    }
+}
+
+impl<E: Debug + Display> MyError for FromStd<E> {
+    fn source(&self) -> Option<&(dyn MyError + 'static)> {
+        self.source.as_deref().map(|s| s as _)
+    }
+}
+
+#[cfg(test)]
+mod spec {
+    use super::*;
+
+    #[derive(Debug, PartialEq)]
+    struct Leaf(u8);
+
+    impl Display for Leaf {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(f, "leaf {}", self.0)
+        }
+    }
+
+    impl MyError for Leaf {}
+
+    #[derive(Debug)]
+    struct Wrapper<E>(&'static str, E);
+
+    impl<E> Display for Wrapper<E> {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            f.write_str(self.0)
+        }
+    }
+
+    impl<E: MyError + 'static> MyError for Wrapper<E> {
+        fn source(&self) -> Option<&(dyn MyError + 'static)> {
+            Some(&self.1)
+        }
+    }
+
+    fn nested() -> Wrapper<Wrapper<Leaf>> {
+        Wrapper("outer", Wrapper("middle", Leaf(1)))
+    }
+
+    mod downcast {
+        use super::*;
+
+        #[test]
+        fn checks_concrete_type() {
+            let err: &dyn MyError = &Leaf(1);
+
+            assert!(err.is::<Leaf>());
+            assert!(!err.is::<Wrapper<Leaf>>());
+            assert!(!err.is::<&Leaf>());
+        }
+
+        #[test]
+        fn borrows_concrete_type() {
+            let mut leaf = Leaf(1);
+            let err: &mut (dyn MyError + Send + Sync) = &mut leaf;
+
+            assert_eq!(err.downcast_ref::<Leaf>(), Some(&Leaf(1)));
+            assert!(err.downcast_ref::<Wrapper<Leaf>>().is_none());
+
+            err.downcast_mut::<Leaf>().unwrap().0 = 2;
+            assert_eq!(leaf, Leaf(2));
+        }
+
+        #[test]
+        fn unboxes_concrete_type() {
+            let err: Box<dyn MyError + Send> = Box::new(Leaf(3));
+            let err = err.downcast::<Wrapper<Leaf>>().unwrap_err();
+
+            assert_eq!(*err.downcast::<Leaf>().unwrap(), Leaf(3));
+        }
+
+        #[test]
+        fn finds_source_of_concrete_type() {
+            let err = nested();
+            let err: &dyn MyError = &err;
+
+            let leaf = err.chain().find_map(|e| e.downcast_ref::<Leaf>());
+
+            assert_eq!(leaf, Some(&Leaf(1)));
+        }
+    }
+
+    mod chain {
+        use super::*;
+
+        #[test]
+        fn starts_with_error_itself() {
+            let err = nested();
+            let err: &dyn MyError = &err;
+
+            let messages = err.chain().map(|e| e.to_string()).collect::<Vec<_>>();
+
+            assert_eq!(messages, ["outer", "middle", "leaf 1"]);
+        }
+    }
+
+    mod report {
+        use super::*;
+
+        #[test]
+        fn prints_chain_on_single_line() {
+            let report = Report::new(nested()).with_backtrace(None);
+
+            assert_eq!(report.to_string(), "outer: middle: leaf 1");
+        }
+
+        #[test]
+        fn prints_numbered_sources_in_pretty_mode() {
+            let report = Report::new(nested()).with_backtrace(None);
+
+            assert_eq!(
+                format!("{report:?}"),
+                "outer\n\nCaused by:\n    0: middle\n    1: leaf 1",
+            );
+            assert_eq!(format!("{report:#}"), format!("{report:?}"));
+        }
+
+        #[test]
+        fn prints_single_source_without_number() {
+            let report = Report::new(Wrapper("outer", Leaf(1))).with_backtrace(None);
+
+            assert_eq!(format!("{report:?}"), "outer\n\nCaused by:\n      leaf 1");
+        }
+
+        #[test]
+        fn prints_captured_backtrace() {
+            let report = Report::new(Leaf(1)).with_backtrace(Some(Backtrace::force_capture()));
+
+            assert!(report.backtrace().is_some());
+            assert!(format!("{report:?}").starts_with("leaf 1\n\nStack backtrace:\n"));
+            assert_eq!(report.to_string(), "leaf 1");
+        }
+
+        #[test]
+        fn wraps_boxed_errors() {
+            let err: Box<dyn MyError + Send + Sync> = Box::new(Wrapper("outer", Leaf(1)));
+            let report: Report = Report::new(err).with_backtrace(None);
+
+            assert_eq!(report.to_string(), "outer: leaf 1");
+            assert!(report.error().is::<Wrapper<Leaf>>());
+        }
+    }
+
+    mod interop {
+        use std::io;
+
+        use super::*;
+
+        #[test]
+        fn exposes_my_error_as_std_error() {
+            let err = IntoStd::new(nested());
+            let err: &(dyn StdError + 'static) = &err;
+
+            let mut messages = vec![err.to_string()];
+            let mut next = err.source();
+            while let Some(e) = next {
+                messages.push(e.to_string());
+                next = e.source();
+            }
+
+            assert_eq!(messages, ["outer", "middle", "leaf 1"]);
+            assert!(err.is::<IntoStd<Wrapper<Wrapper<Leaf>>>>());
+        }
+
+        #[test]
+        fn exposes_std_error_as_my_error() {
+            let outer = io::Error::other(IntoStd::new(Wrapper("write failed", Leaf(1))));
+
+            let err = FromStd::from(outer);
+            let err: &dyn MyError = &err;
+
+            let messages = err.chain().map(|e| e.to_string()).collect::<Vec<_>>();
+            assert_eq!(messages, ["write failed", "leaf 1"]);
+            assert_eq!(
+                err.downcast_ref::<FromStd<io::Error>>()
+                    .map(|e| e.to_string()),
+                Some(String::from("write failed")),
+            );
+        }
+    }
+}
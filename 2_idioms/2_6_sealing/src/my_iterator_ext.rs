@@ -7,7 +7,12 @@
 use crate::my_iterator_ext::private::Sealed;
 use std::fmt;
 
-use self::format::{Format, FormatWith};
+pub use self::{
+    adapters::{
+        ChunkBy, DedupByKey, Interleave, MinMaxResult, Positions, TupleWindow, TupleWindows,
+    },
+    format::{Format, FormatWith},
+};
 
 mod private {
     pub trait Sealed {}
@@ -46,7 +51,7 @@ pub trait MyIteratorExt: Iterator + private::Sealed {
     ///     format!("{:.2}", data.iter().format(", ")),
     ///            "1.10, 2.72, -3.00");
     /// ```
-    fn format(self, sep: &str) -> Format<Self>
+    fn format(self, sep: &str) -> Format<'_, Self>
     where
         Self: Sized,
     {
@@ -82,16 +87,186 @@ pub trait MyIteratorExt: Iterator + private::Sealed {
     /// });
     /// assert_eq!(matrix_formatter.to_string(), "1, 2, 3\n4, 5, 6");
     /// ```
-    fn format_with<F>(self, sep: &str, format: F) -> FormatWith<Self, F>
+    fn format_with<F>(self, sep: &str, format: F) -> FormatWith<'_, Self, F>
     where
         Self: Sized,
         F: FnMut(Self::Item, &mut dyn FnMut(&dyn fmt::Display) -> fmt::Result) -> fmt::Result,
     {
         format::new_format(self, sep, format)
     }
+
+    /// Alternate elements from two iterators.
+    ///
+    /// If one iterator is longer than the other, the remaining elements of the longer one are yielded at the end.
+    ///
+    /// ```rust
+    /// use step_2_6::MyIteratorExt as _;
+    ///
+    /// let it = (1..4).interleave(-3..-1);
+    /// assert_eq!(it.collect::<Vec<_>>(), [1, -3, 2, -2, 3]);
+    /// ```
+    fn interleave<J>(self, other: J) -> Interleave<Self, J::IntoIter>
+    where
+        Self: Sized,
+        J: IntoIterator<Item = Self::Item>,
+    {
+        adapters::Interleave::new(self, other.into_iter())
+    }
+
+    /// Group consecutive elements into [`Vec`]s, as long as `same_chunk` returns `true` for every two adjacent
+    /// elements.
+    ///
+    /// This is a lazy version of [`slice::chunk_by()`].
+    ///
+    /// ```rust
+    /// use step_2_6::MyIteratorExt as _;
+    ///
+    /// let it = [1, 1, 2, 3, 3, 3].into_iter().chunk_by(|a, b| a == b);
+    /// assert_eq!(it.collect::<Vec<_>>(), [vec![1, 1], vec![2], vec![3, 3, 3]]);
+    ///
+    /// let ascending = [1, 2, 0, 5, 7, 4].into_iter().chunk_by(|a, b| a < b);
+    /// assert_eq!(ascending.collect::<Vec<_>>(), [vec![1, 2], vec![0, 5, 7], vec![4]]);
+    /// ```
+    fn chunk_by<F>(self, same_chunk: F) -> ChunkBy<Self, F>
+    where
+        Self: Sized,
+        F: FnMut(&Self::Item, &Self::Item) -> bool,
+    {
+        adapters::ChunkBy::new(self, same_chunk)
+    }
+
+    /// Remove consecutive elements resolving to the same key, keeping the first one of them.
+    ///
+    /// This is a lazy version of [`Vec::dedup_by_key()`].
+    ///
+    /// ```rust
+    /// use step_2_6::MyIteratorExt as _;
+    ///
+    /// let it = [10, 11, 20, 21, 12].into_iter().dedup_by_key(|n| n / 10);
+    /// assert_eq!(it.collect::<Vec<_>>(), [10, 20, 12]);
+    /// ```
+    fn dedup_by_key<K, F>(self, key: F) -> DedupByKey<Self, K, F>
+    where
+        Self: Sized,
+        K: PartialEq,
+        F: FnMut(&Self::Item) -> K,
+    {
+        adapters::DedupByKey::new(self, key)
+    }
+
+    /// Iterate over all overlapping windows of consecutive elements, represented as tuples.
+    ///
+    /// This is a lazy version of [`slice::windows()`], supporting tuples of 2, 3 and 4 elements.
+    ///
+    /// ```rust
+    /// use step_2_6::MyIteratorExt as _;
+    ///
+    /// let pairs = (1..5).tuple_windows::<(_, _)>();
+    /// assert_eq!(pairs.collect::<Vec<_>>(), [(1, 2), (2, 3), (3, 4)]);
+    ///
+    /// let triples = "abcd".chars().tuple_windows();
+    /// assert_eq!(triples.collect::<Vec<(_, _, _)>>(), [('a', 'b', 'c'), ('b', 'c', 'd')]);
+    ///
+    /// assert_eq!((1..3).tuple_windows::<(_, _, _)>().next(), None);
+    /// ```
+    fn tuple_windows<T>(self) -> TupleWindows<Self, T>
+    where
+        Self: Sized,
+        Self::Item: Clone,
+        T: TupleWindow<Self::Item>,
+    {
+        adapters::TupleWindows::new(self)
+    }
+
+    /// Sort all elements by the `key`, calling it only once per element.
+    ///
+    /// The sort is stable. See [`slice::sort_by_cached_key()`] for more information.
+    ///
+    /// ```rust
+    /// use step_2_6::MyIteratorExt as _;
+    ///
+    /// let words = ["Rust", "is", "awesome"];
+    /// let it = words.into_iter().sorted_by_cached_key(|w| w.to_lowercase());
+    /// assert_eq!(it.collect::<Vec<_>>(), ["awesome", "is", "Rust"]);
+    /// ```
+    fn sorted_by_cached_key<K, F>(self, key: F) -> std::vec::IntoIter<Self::Item>
+    where
+        Self: Sized,
+        K: Ord,
+        F: FnMut(&Self::Item) -> K,
+    {
+        let mut v = self.collect::<Vec<_>>();
+        v.sort_by_cached_key(key);
+        v.into_iter()
+    }
+
+    /// Combine all elements into a single [`String`], separated by `sep`.
+    ///
+    /// This is an eager version of [`.format()`](MyIteratorExt::format) using [`Display`](fmt::Display).
+    ///
+    /// ```rust
+    /// use step_2_6::MyIteratorExt as _;
+    ///
+    /// assert_eq!([1, 2, 3].iter().join(", "), "1, 2, 3");
+    /// assert_eq!(std::iter::empty::<u8>().join(", "), "");
+    /// ```
+    fn join(&mut self, sep: &str) -> String
+    where
+        Self::Item: fmt::Display,
+    {
+        use fmt::Write as _;
+
+        let Some(first) = self.next() else {
+            return String::new();
+        };
+        let mut out = first.to_string();
+        for elt in self {
+            out.push_str(sep);
+            write!(out, "{elt}").expect("writing to `String` never fails");
+        }
+        out
+    }
+
+    /// Find the minimum and the maximum elements in a single pass.
+    ///
+    /// Consistently with [`Iterator::min()`] and [`Iterator::max()`], the first minimum and the last maximum are
+    /// returned if several elements are equally minimum or maximum.
+    ///
+    /// ```rust
+    /// use step_2_6::{my_iterator_ext::MinMaxResult, MyIteratorExt as _};
+    ///
+    /// assert_eq!([3, 1, 4, 1, 5].iter().minmax(), MinMaxResult::MinMax(&1, &5));
+    /// assert_eq!([7].iter().minmax(), MinMaxResult::OneElement(&7));
+    /// assert_eq!(std::iter::empty::<u8>().minmax(), MinMaxResult::NoElements);
+    /// ```
+    fn minmax(self) -> MinMaxResult<Self::Item>
+    where
+        Self: Sized,
+        Self::Item: Ord,
+    {
+        adapters::minmax(self)
+    }
+
+    /// Return the indices of all elements satisfying the `predicate`.
+    ///
+    /// ```rust
+    /// use step_2_6::MyIteratorExt as _;
+    ///
+    /// let it = [1, 2, 3, 4, 6].into_iter().positions(|n| n % 2 == 0);
+    /// assert_eq!(it.collect::<Vec<_>>(), [1, 3, 4]);
+    /// ```
+    fn positions<P>(self, predicate: P) -> Positions<Self, P>
+    where
+        Self: Sized,
+        P: FnMut(Self::Item) -> bool,
+    {
+        adapters::Positions::new(self, predicate)
+    }
 }
 
-// impl<T> MyIteratorExt for T where T: Iterator {}
+impl<T> Sealed for T where T: Iterator {}
+
+impl<T> MyIteratorExt for T where T: Iterator {}
 
 mod format {
     use std::{cell::RefCell, fmt};
@@ -214,3 +389,530 @@ mod format {
         Display Debug UpperExp LowerExp UpperHex LowerHex Octal Binary Pointer
     }
 }
+
+mod adapters {
+    use std::{collections::VecDeque, fmt, iter::Fuse, marker::PhantomData};
+
+    /// An iterator alternating elements of two iterators.
+    ///
+    /// See [`.interleave()`](crate::MyIteratorExt::interleave) for more information.
+    #[derive(Clone, Debug)]
+    #[must_use = "iterator adaptors are lazy and do nothing unless consumed"]
+    pub struct Interleave<I, J> {
+        a: Fuse<I>,
+        b: Fuse<J>,
+        flag: bool,
+    }
+
+    impl<I: Iterator, J: Iterator> Interleave<I, J> {
+        pub(super) fn new(a: I, b: J) -> Self {
+            Self {
+                a: a.fuse(),
+                b: b.fuse(),
+                flag: false,
+            }
+        }
+    }
+
+    impl<I, J> Iterator for Interleave<I, J>
+    where
+        I: Iterator,
+        J: Iterator<Item = I::Item>,
+    {
+        type Item = I::Item;
+
+        fn next(&mut self) -> Option<Self::Item> {
+            self.flag = !self.flag;
+            if self.flag {
+                self.a.next().or_else(|| self.b.next())
+            } else {
+                self.b.next().or_else(|| self.a.next())
+            }
+        }
+
+        fn size_hint(&self) -> (usize, Option<usize>) {
+            let (a_lo, a_hi) = self.a.size_hint();
+            let (b_lo, b_hi) = self.b.size_hint();
+            let hi = a_hi.zip(b_hi).and_then(|(a, b)| a.checked_add(b));
+            (a_lo.saturating_add(b_lo), hi)
+        }
+    }
+
+    /// An iterator over chunks of consecutive elements.
+    ///
+    /// See [`.chunk_by()`](crate::MyIteratorExt::chunk_by) for more information.
+    #[derive(Clone)]
+    #[must_use = "iterator adaptors are lazy and do nothing unless consumed"]
+    pub struct ChunkBy<I: Iterator, F> {
+        iter: I,
+        same_chunk: F,
+        /// The first element of the next chunk, which has already been taken from the `iter`.
+        pending: Option<I::Item>,
+    }
+
+    impl<I: Iterator, F> ChunkBy<I, F> {
+        pub(super) fn new(iter: I, same_chunk: F) -> Self {
+            Self {
+                iter,
+                same_chunk,
+                pending: None,
+            }
+        }
+    }
+
+    impl<I: Iterator, F> fmt::Debug for ChunkBy<I, F>
+    where
+        I: fmt::Debug,
+        I::Item: fmt::Debug,
+    {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            f.debug_struct("ChunkBy")
+                .field("iter", &self.iter)
+                .field("pending", &self.pending)
+                .finish_non_exhaustive()
+        }
+    }
+
+    impl<I, F> Iterator for ChunkBy<I, F>
+    where
+        I: Iterator,
+        F: FnMut(&I::Item, &I::Item) -> bool,
+    {
+        type Item = Vec<I::Item>;
+
+        fn next(&mut self) -> Option<Self::Item> {
+            let first = self.pending.take().or_else(|| self.iter.next())?;
+            let mut chunk = vec![first];
+            for elt in self.iter.by_ref() {
+                let last = chunk.last().expect("chunk is never empty");
+                if (self.same_chunk)(last, &elt) {
+                    chunk.push(elt);
+                } else {
+                    self.pending = Some(elt);
+                    break;
+                }
+            }
+            Some(chunk)
+        }
+
+        fn size_hint(&self) -> (usize, Option<usize>) {
+            let pending = usize::from(self.pending.is_some());
+            let (lo, hi) = self.iter.size_hint();
+            (
+                usize::from(lo > 0 || pending > 0),
+                hi.and_then(|hi| hi.checked_add(pending)),
+            )
+        }
+    }
+
+    /// An iterator skipping consecutive elements with the same key.
+    ///
+    /// See [`.dedup_by_key()`](crate::MyIteratorExt::dedup_by_key) for more information.
+    #[derive(Clone)]
+    #[must_use = "iterator adaptors are lazy and do nothing unless consumed"]
+    pub struct DedupByKey<I, K, F> {
+        iter: I,
+        key: F,
+        last: Option<K>,
+    }
+
+    impl<I, K, F> DedupByKey<I, K, F> {
+        pub(super) fn new(iter: I, key: F) -> Self {
+            Self {
+                iter,
+                key,
+                last: None,
+            }
+        }
+    }
+
+    impl<I: fmt::Debug, K: fmt::Debug, F> fmt::Debug for DedupByKey<I, K, F> {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            f.debug_struct("DedupByKey")
+                .field("iter", &self.iter)
+                .field("last", &self.last)
+                .finish_non_exhaustive()
+        }
+    }
+
+    impl<I, K, F> Iterator for DedupByKey<I, K, F>
+    where
+        I: Iterator,
+        K: PartialEq,
+        F: FnMut(&I::Item) -> K,
+    {
+        type Item = I::Item;
+
+        fn next(&mut self) -> Option<Self::Item> {
+            for elt in self.iter.by_ref() {
+                let key = (self.key)(&elt);
+                if self.last.as_ref() != Some(&key) {
+                    self.last = Some(key);
+                    return Some(elt);
+                }
+            }
+            None
+        }
+
+        fn size_hint(&self) -> (usize, Option<usize>) {
+            let (lo, hi) = self.iter.size_hint();
+            (usize::from(lo > 0 && self.last.is_none()), hi)
+        }
+    }
+
+    /// A tuple of the same element type, which [`TupleWindows`] are able to produce.
+    pub trait TupleWindow<T: Clone>: Sized {
+        /// The number of elements in the tuple.
+        const SIZE: usize;
+
+        /// Builds the tuple out of a `window` of exactly [`Self::SIZE`] elements.
+        fn from_window(window: &[T]) -> Self;
+    }
+
+    macro_rules! impl_tuple_window {
+        ($size:literal => $($idx:literal)+) => {
+            impl<T: Clone> TupleWindow<T> for ($(impl_tuple_window!(@ty $idx T),)+) {
+                const SIZE: usize = $size;
+
+                fn from_window(window: &[T]) -> Self {
+                    ($(window[$idx].clone(),)+)
+                }
+            }
+        };
+        (@ty $idx:literal $ty:ident) => { $ty };
+    }
+
+    impl_tuple_window!(2 => 0 1);
+    impl_tuple_window!(3 => 0 1 2);
+    impl_tuple_window!(4 => 0 1 2 3);
+
+    /// An iterator over overlapping windows of consecutive elements.
+    ///
+    /// See [`.tuple_windows()`](crate::MyIteratorExt::tuple_windows) for more information.
+    #[must_use = "iterator adaptors are lazy and do nothing unless consumed"]
+    pub struct TupleWindows<I: Iterator, T> {
+        iter: I,
+        window: VecDeque<I::Item>,
+        _tuple: PhantomData<fn() -> T>,
+    }
+
+    impl<I: Iterator, T> TupleWindows<I, T> {
+        pub(super) fn new(iter: I) -> Self {
+            Self {
+                iter,
+                window: VecDeque::new(),
+                _tuple: PhantomData,
+            }
+        }
+    }
+
+    impl<I, T> Clone for TupleWindows<I, T>
+    where
+        I: Iterator + Clone,
+        I::Item: Clone,
+    {
+        fn clone(&self) -> Self {
+            Self {
+                iter: self.iter.clone(),
+                window: self.window.clone(),
+                _tuple: PhantomData,
+            }
+        }
+    }
+
+    impl<I, T> fmt::Debug for TupleWindows<I, T>
+    where
+        I: Iterator + fmt::Debug,
+        I::Item: fmt::Debug,
+    {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            f.debug_struct("TupleWindows")
+                .field("iter", &self.iter)
+                .field("window", &self.window)
+                .finish()
+        }
+    }
+
+    impl<I, T> Iterator for TupleWindows<I, T>
+    where
+        I: Iterator,
+        I::Item: Clone,
+        T: TupleWindow<I::Item>,
+    {
+        type Item = T;
+
+        fn next(&mut self) -> Option<Self::Item> {
+            if self.window.len() == T::SIZE {
+                self.window.pop_front();
+            }
+            while self.window.len() < T::SIZE {
+                self.window.push_back(self.iter.next()?);
+            }
+            Some(T::from_window(self.window.make_contiguous()))
+        }
+
+        fn size_hint(&self) -> (usize, Option<usize>) {
+            // Once the window is full, every next element produces exactly one tuple, so the first tuple takes one
+            // element more than the window lacks, and the bounds never overflow even for unbounded sources.
+            let missing = T::SIZE - self.window.len().max(1);
+            let (lo, hi) = self.iter.size_hint();
+            (
+                lo.saturating_sub(missing),
+                hi.map(|hi| hi.saturating_sub(missing)),
+            )
+        }
+    }
+
+    /// The result of [`.minmax()`](crate::MyIteratorExt::minmax).
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    pub enum MinMaxResult<T> {
+        /// The iterator was empty.
+        NoElements,
+        /// The iterator had exactly one element, so it's both the minimum and the maximum.
+        OneElement(T),
+        /// The minimum and the maximum elements respectively.
+        MinMax(T, T),
+    }
+
+    impl<T: Clone> MinMaxResult<T> {
+        /// Converts into an [`Option`] of the minimum and the maximum.
+        pub fn into_option(self) -> Option<(T, T)> {
+            match self {
+                Self::NoElements => None,
+                Self::OneElement(x) => Some((x.clone(), x)),
+                Self::MinMax(min, max) => Some((min, max)),
+            }
+        }
+    }
+
+    pub(super) fn minmax<I>(mut iter: I) -> MinMaxResult<I::Item>
+    where
+        I: Iterator,
+        I::Item: Ord,
+    {
+        let Some(first) = iter.next() else {
+            return MinMaxResult::NoElements;
+        };
+        let Some(second) = iter.next() else {
+            return MinMaxResult::OneElement(first);
+        };
+        let (mut min, mut max) = if second < first {
+            (second, first)
+        } else {
+            (first, second)
+        };
+
+        // Elements are compared in pairs, which takes 3 comparisons per 2 elements instead of 4.
+        loop {
+            let (lo, hi) = match (iter.next(), iter.next()) {
+                (None, _) => break,
+                (Some(x), None) => {
+                    if x < min {
+                        min = x;
+                    } else if x >= max {
+                        max = x;
+                    }
+                    break;
+                }
+                (Some(x), Some(y)) if y < x => (y, x),
+                (Some(x), Some(y)) => (x, y),
+            };
+            if lo < min {
+                min = lo;
+            }
+            if hi >= max {
+                max = hi;
+            }
+        }
+
+        MinMaxResult::MinMax(min, max)
+    }
+
+    /// An iterator over indices of elements satisfying a predicate.
+    ///
+    /// See [`.positions()`](crate::MyIteratorExt::positions) for more information.
+    #[derive(Clone)]
+    #[must_use = "iterator adaptors are lazy and do nothing unless consumed"]
+    pub struct Positions<I, P> {
+        iter: I,
+        predicate: P,
+        count: usize,
+    }
+
+    impl<I, P> Positions<I, P> {
+        pub(super) fn new(iter: I, predicate: P) -> Self {
+            Self {
+                iter,
+                predicate,
+                count: 0,
+            }
+        }
+    }
+
+    impl<I: fmt::Debug, P> fmt::Debug for Positions<I, P> {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            f.debug_struct("Positions")
+                .field("iter", &self.iter)
+                .field("count", &self.count)
+                .finish_non_exhaustive()
+        }
+    }
+
+    impl<I, P> Iterator for Positions<I, P>
+    where
+        I: Iterator,
+        P: FnMut(I::Item) -> bool,
+    {
+        type Item = usize;
+
+        fn next(&mut self) -> Option<Self::Item> {
+            for elt in self.iter.by_ref() {
+                let i = self.count;
+                self.count += 1;
+                if (self.predicate)(elt) {
+                    return Some(i);
+                }
+            }
+            None
+        }
+
+        fn size_hint(&self) -> (usize, Option<usize>) {
+            (0, self.iter.size_hint().1)
+        }
+    }
+}
+
+#[cfg(test)]
+mod spec {
+    use proptest::prelude::*;
+
+    use super::*;
+
+    proptest! {
+        #[test]
+        fn interleave_alternates_then_appends_rest(a: Vec<u8>, b: Vec<u8>) {
+            let n = a.len().min(b.len());
+            let expected = a[..n]
+                .iter()
+                .zip(&b[..n])
+                .flat_map(|(x, y)| [x, y])
+                .chain(&a[n..])
+                .chain(&b[n..])
+                .collect::<Vec<_>>();
+
+            let it = a.iter().interleave(&b);
+            prop_assert_eq!(it.size_hint(), (a.len() + b.len(), Some(a.len() + b.len())));
+            prop_assert_eq!(it.collect::<Vec<_>>(), expected);
+        }
+
+        #[test]
+        fn chunk_by_matches_slice_chunk_by(v in prop::collection::vec(0..4u8, 0..50)) {
+            let expected = v.chunk_by(|a, b| a <= b).map(<[_]>::to_vec).collect::<Vec<_>>();
+
+            prop_assert_eq!(v.into_iter().chunk_by(|a, b| a <= b).collect::<Vec<_>>(), expected);
+        }
+
+        #[test]
+        fn dedup_by_key_matches_vec_dedup_by_key(v in prop::collection::vec(0..20u8, 0..50)) {
+            let mut expected = v.clone();
+            expected.dedup_by_key(|n| *n / 3);
+
+            prop_assert_eq!(v.into_iter().dedup_by_key(|n| *n / 3).collect::<Vec<_>>(), expected);
+        }
+
+        #[test]
+        fn tuple_windows_match_slice_windows(v: Vec<u8>) {
+            let pairs = v.windows(2).map(|w| (w[0], w[1])).collect::<Vec<_>>();
+            let quads = v.windows(4).map(|w| (w[0], w[1], w[2], w[3])).collect::<Vec<_>>();
+
+            let it = v.iter().copied().tuple_windows::<(_, _)>();
+            prop_assert_eq!(it.size_hint(), (pairs.len(), Some(pairs.len())));
+            prop_assert_eq!(it.collect::<Vec<_>>(), pairs);
+
+            let mut it = v.iter().copied().tuple_windows::<(_, _, _, _)>();
+            for len in (0..=quads.len()).rev() {
+                prop_assert_eq!(it.size_hint(), (len, Some(len)));
+                it.next();
+            }
+            prop_assert_eq!(v.iter().copied().tuple_windows::<(_, _, _, _)>().collect::<Vec<_>>(), quads);
+        }
+
+        #[test]
+        fn tuple_windows_of_unbounded_source(start: u64, skip in 0..4usize) {
+            let mut it = (start..).tuple_windows::<(_, _, _)>();
+            prop_assert_eq!(it.size_hint(), (usize::MAX - 2, None));
+            it.nth(skip);
+            prop_assert_eq!(it.size_hint(), (usize::MAX, None));
+
+            let start = start as usize;
+            let it = (start..usize::MAX).tuple_windows::<(_, _)>().skip(skip);
+            let len = (usize::MAX - start).saturating_sub(1 + skip);
+            prop_assert_eq!(it.size_hint(), (len, Some(len)));
+
+            let it = (start..).tuple_windows::<(_, _)>().take(3);
+            let expected = (0..3).map(|i| (start + i, start + i + 1));
+            prop_assert_eq!(it.collect::<Vec<_>>(), expected.collect::<Vec<_>>());
+        }
+
+        #[test]
+        fn sorted_by_cached_key_matches_stable_sort(v: Vec<(u8, u8)>) {
+            let mut expected = v.clone();
+            expected.sort_by_key(|(k, _)| *k % 7);
+
+            prop_assert_eq!(
+                v.into_iter().sorted_by_cached_key(|(k, _)| *k % 7).collect::<Vec<_>>(),
+                expected
+            );
+        }
+
+        #[test]
+        fn join_matches_slice_join(v: Vec<i32>, sep in ".{0,3}") {
+            let expected = v.iter().map(i32::to_string).collect::<Vec<_>>().join(&sep);
+
+            prop_assert_eq!(v.iter().join(&sep), expected);
+        }
+
+        #[test]
+        fn minmax_matches_min_and_max(v in prop::collection::vec((0..5u8, any::<u16>()), 0..50)) {
+            // Only the first tuple element is compared, so the second one tells equal elements apart.
+            #[derive(Clone, Debug)]
+            struct ByKey((u8, u16));
+            impl PartialEq for ByKey {
+                fn eq(&self, other: &Self) -> bool {
+                    self.0 .0 == other.0 .0
+                }
+            }
+            impl Eq for ByKey {}
+            impl PartialOrd for ByKey {
+                fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+                    Some(self.cmp(other))
+                }
+            }
+            impl Ord for ByKey {
+                fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+                    self.0 .0.cmp(&other.0 .0)
+                }
+            }
+
+            let expected = v.iter().min_by_key(|e| e.0).zip(v.iter().max_by_key(|e| e.0));
+            let actual = v.iter().map(|e| ByKey(*e)).minmax().into_option();
+
+            prop_assert_eq!(
+                actual.map(|(min, max)| (min.0, max.0)),
+                expected.map(|(min, max)| (*min, *max))
+            );
+        }
+
+        #[test]
+        fn positions_match_enumerate_filter(v: Vec<u8>) {
+            let expected = v
+                .iter()
+                .enumerate()
+                .filter(|(_, n)| *n % 3 == 0)
+                .map(|(i, _)| i)
+                .collect::<Vec<_>>();
+
+            prop_assert_eq!(v.into_iter().positions(|n| n % 3 == 0).collect::<Vec<_>>(), expected);
+        }
+    }
+}
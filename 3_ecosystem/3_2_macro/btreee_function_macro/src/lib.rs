@@ -1,8 +1,21 @@
 extern crate proc_macro;
 
+mod map;
+
+use std::collections::HashMap;
+
 use proc_macro::TokenStream;
+use proc_macro2::{Span, TokenStream as TokenStream2};
 use quote::quote;
-use syn::{parse::Parse, parse_macro_input, punctuated::Punctuated, Expr, Token};
+use syn::{
+    parse::{discouraged::Speculative, Parse, ParseStream},
+    parse_macro_input,
+    punctuated::Punctuated,
+    Attribute, DeriveInput, Expr, ExprLit, ExprPath, ExprUnary, Ident, Lit, Token, Type, UnOp,
+};
+
+/// Attribute letting the later duplicate keys overwrite the earlier ones, instead of failing the compilation.
+const ALLOW_DUPLICATES: &str = "allow_duplicates";
 
 struct KeyValue {
     key: Expr,
@@ -10,7 +23,7 @@ struct KeyValue {
 }
 
 impl Parse for KeyValue {
-    fn parse(input: syn::parse::ParseStream) -> syn::Result<Self> {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
         let key = input.parse()?;
         input.parse::<Token![=>]>()?;
         let value = input.parse()?;
@@ -18,34 +31,334 @@ impl Parse for KeyValue {
     }
 }
 
-struct KeyValuePairs {
-    pairs: Punctuated<KeyValue, Token![,]>,
+/// Contents of a collection macro: `#[allow_duplicates] <Types>; with_capacity(n); entries,*`, with all the prefixes
+/// being optional.
+struct Collection<T> {
+    allow_duplicates: bool,
+    types: Option<(Span, Vec<Type>)>,
+    capacity: Option<(Span, Expr)>,
+    entries: Punctuated<T, Token![,]>,
 }
 
-impl Parse for KeyValuePairs {
-    fn parse(input: syn::parse::ParseStream) -> syn::Result<Self> {
-        let pairs = Punctuated::<KeyValue, Token![,]>::parse_terminated(input)?;
-        Ok(KeyValuePairs { pairs })
+impl<T: Parse> Parse for Collection<T> {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let mut allow_duplicates = false;
+        for attr in input.call(Attribute::parse_outer)? {
+            if !attr.path().is_ident(ALLOW_DUPLICATES) {
+                return Err(syn::Error::new_spanned(
+                    attr,
+                    format!("unsupported attribute, only `#[{ALLOW_DUPLICATES}]` is allowed"),
+                ));
+            }
+            if attr.meta.require_path_only().is_err() {
+                return Err(syn::Error::new_spanned(
+                    attr,
+                    format!("`#[{ALLOW_DUPLICATES}]` takes no arguments"),
+                ));
+            }
+            allow_duplicates = true;
+        }
+
+        let types = if input.peek(Token![<]) {
+            let span = input.parse::<Token![<]>()?.span;
+            let mut types = vec![input.parse()?];
+            while input.parse::<Option<Token![,]>>()?.is_some() {
+                types.push(input.parse()?);
+            }
+            input.parse::<Token![>]>()?;
+            input.parse::<Token![;]>()?;
+            Some((span, types))
+        } else {
+            None
+        };
+
+        // An entry may start the same way, e.g. with a call to a `with_capacity` function.
+        let fork = input.fork();
+        let capacity = match parse_capacity(&fork) {
+            Ok(capacity) => {
+                input.advance_to(&fork);
+                Some(capacity)
+            }
+            Err(_) => None,
+        };
+
+        let entries = Punctuated::parse_terminated(input)?;
+        Ok(Collection {
+            allow_duplicates,
+            types,
+            capacity,
+            entries,
+        })
     }
 }
 
-#[proc_macro]
-pub fn btreemap(input: TokenStream) -> TokenStream {
-    let input = parse_macro_input!(input as KeyValuePairs);
+fn parse_capacity(input: ParseStream) -> syn::Result<(Span, Expr)> {
+    let ident = input.parse::<Ident>()?;
+    if ident != "with_capacity" {
+        return Err(syn::Error::new(ident.span(), "expected `with_capacity`"));
+    }
+    let content;
+    syn::parenthesized!(content in input);
+    let capacity = content.parse()?;
+    input.parse::<Token![;]>()?;
+    Ok((ident.span(), capacity))
+}
 
-    let insertions = input.pairs.iter().map(|KeyValue { key, value }| {
-        quote! {
-            map.insert(#key, #value);
-        }
-    });
+/// How a collection is created and filled.
+struct Kind {
+    path: TokenStream2,
+    /// Number of the explicit types the collection is parameterized with.
+    arity: usize,
+    /// Whether the collection may be preallocated, as only the hash ones can.
+    hashed: bool,
+}
 
-    let expanded = quote! {
-        {
-            let mut map = ::std::collections::BTreeMap::new();
-            #(#insertions)*
-            map
+impl<T> Collection<T> {
+    /// Fails on every key equal to an earlier one, unless the duplicates are allowed.
+    fn check_duplicates<'a>(&self, keys: impl IntoIterator<Item = &'a Expr>) -> syn::Result<()> {
+        if self.allow_duplicates {
+            return Ok(());
         }
+
+        let mut seen = HashMap::new();
+        let mut errors = keys
+            .into_iter()
+            .filter_map(|key| Some((LiteralKey::from_expr(key)?, key)))
+            .filter_map(|(literal, key)| {
+                let first = *seen.entry(literal).or_insert(key);
+                (!std::ptr::eq(first, key)).then(|| {
+                    syn::Error::new_spanned(
+                        key,
+                        format!(
+                            "duplicate key `{}`, which would overwrite the earlier one; \
+                             add `#[{ALLOW_DUPLICATES}]` to the macro to allow it",
+                            pretty(key),
+                        ),
+                    )
+                })
+            });
+
+        let Some(mut error) = errors.next() else {
+            return Ok(());
+        };
+        error.extend(errors);
+        Err(error)
+    }
+
+    /// Expands into a block filling the collection of the given `kind` with the `insertions`.
+    fn expand(self, kind: Kind, insertions: Vec<TokenStream2>) -> syn::Result<TokenStream2> {
+        let Kind {
+            path,
+            arity,
+            hashed,
+        } = kind;
+
+        let ty = match self.types {
+            Some((span, types)) if types.len() != arity => {
+                return Err(syn::Error::new(
+                    span,
+                    format!("expected {arity} type(s), found {}", types.len()),
+                ))
+            }
+            Some((_, types)) => quote! { #path<#(#types),*> },
+            None => {
+                let params = (0..arity).map(|_| quote! { _ });
+                quote! { #path<#(#params),*> }
+            }
+        };
+
+        let new = match (self.capacity, hashed) {
+            (Some((span, _)), false) => {
+                return Err(syn::Error::new(
+                    span,
+                    "`with_capacity` is supported by hash collections only",
+                ))
+            }
+            (Some((_, capacity)), true) => quote! { with_capacity(#capacity) },
+            (None, true) => {
+                let len = insertions.len();
+                quote! { with_capacity(#len) }
+            }
+            (None, false) => quote! { new() },
+        };
+
+        Ok(quote! {
+            {
+                #[allow(unused_mut)]
+                let mut collection: #ty = #path::#new;
+                #(#insertions)*
+                collection
+            }
+        })
+    }
+}
+
+fn expand_map(input: TokenStream, path: TokenStream2, hashed: bool) -> TokenStream {
+    let input = parse_macro_input!(input as Collection<KeyValue>);
+    if let Err(e) = input.check_duplicates(input.entries.iter().map(|e| &e.key)) {
+        return compile_error(e);
+    }
+    let insertions = input
+        .entries
+        .iter()
+        .map(|KeyValue { key, value }| quote! { collection.insert(#key, #value); })
+        .collect();
+    let kind = Kind {
+        path,
+        arity: 2,
+        hashed,
     };
+    input
+        .expand(kind, insertions)
+        .map_or_else(compile_error, Into::into)
+}
+
+fn expand_set(input: TokenStream, path: TokenStream2, hashed: bool) -> TokenStream {
+    let input = parse_macro_input!(input as Collection<Expr>);
+    if let Err(e) = input.check_duplicates(&input.entries) {
+        return compile_error(e);
+    }
+    let insertions = input
+        .entries
+        .iter()
+        .map(|value| quote! { collection.insert(#value); })
+        .collect();
+    let kind = Kind {
+        path,
+        arity: 1,
+        hashed,
+    };
+    input
+        .expand(kind, insertions)
+        .map_or_else(compile_error, Into::into)
+}
+
+/// Prints the tokens the way they're usually written, unlike their spaced out default representation.
+fn pretty(tokens: impl quote::ToTokens) -> String {
+    let mut printed = tokens.to_token_stream().to_string();
+    for (spaced, tight) in [
+        (" <", "<"),
+        ("< ", "<"),
+        (" >", ">"),
+        (" ::", "::"),
+        (":: ", "::"),
+        ("- ", "-"),
+    ] {
+        printed = printed.replace(spaced, tight);
+    }
+    printed
+}
+
+/// Expands into all the errors, which are statements, so they're wrapped into a block to remain an expression.
+fn compile_error(error: syn::Error) -> TokenStream {
+    let errors = error.into_compile_error();
+    quote! { { #errors } }.into()
+}
+
+/// Key, whose value is known at compile time, so its duplicates may be detected.
+#[derive(PartialEq, Eq, Hash)]
+enum LiteralKey {
+    Str(String),
+    ByteStr(Vec<u8>),
+    Byte(u8),
+    Char(char),
+    /// Integer by its sign and magnitude, whatever radix or suffix it's written with.
+    Int(bool, u128),
+    Bool(bool),
+    /// Path to a constant, which is told apart from a variable by its `SCREAMING_CASE` name.
+    Const(String),
+}
+
+impl LiteralKey {
+    fn from_expr(expr: &Expr) -> Option<Self> {
+        match expr {
+            Expr::Group(group) => Self::from_expr(&group.expr),
+            Expr::Paren(paren) => Self::from_expr(&paren.expr),
+            Expr::Lit(ExprLit { lit, .. }) => Self::from_lit(lit, false),
+            Expr::Unary(ExprUnary {
+                op: UnOp::Neg(_),
+                expr,
+                ..
+            }) => match &**expr {
+                Expr::Lit(ExprLit {
+                    lit: lit @ Lit::Int(_),
+                    ..
+                }) => Self::from_lit(lit, true),
+                _ => None,
+            },
+            Expr::Path(ExprPath {
+                qself: None, path, ..
+            }) => {
+                let name = path.segments.last()?.ident.to_string();
+                let is_const = name
+                    .chars()
+                    .all(|c| c.is_ascii_uppercase() || c.is_ascii_digit() || c == '_');
+                is_const.then(|| Self::Const(quote! { #path }.to_string()))
+            }
+            _ => None,
+        }
+    }
+
+    fn from_lit(lit: &Lit, negative: bool) -> Option<Self> {
+        Some(match lit {
+            Lit::Str(s) => Self::Str(s.value()),
+            Lit::ByteStr(s) => Self::ByteStr(s.value()),
+            Lit::Byte(b) => Self::Byte(b.value()),
+            Lit::Char(c) => Self::Char(c.value()),
+            Lit::Int(i) => {
+                let magnitude = i.base10_parse::<u128>().ok()?;
+                Self::Int(negative && magnitude != 0, magnitude)
+            }
+            Lit::Bool(b) => Self::Bool(b.value),
+            _ => return None,
+        })
+    }
+}
+
+#[proc_macro]
+pub fn btreemap(input: TokenStream) -> TokenStream {
+    expand_map(input, quote! { ::std::collections::BTreeMap }, false)
+}
+
+#[proc_macro]
+pub fn btreeset(input: TokenStream) -> TokenStream {
+    expand_set(input, quote! { ::std::collections::BTreeSet }, false)
+}
+
+#[proc_macro]
+pub fn hashmap(input: TokenStream) -> TokenStream {
+    expand_map(input, quote! { ::std::collections::HashMap }, true)
+}
+
+#[proc_macro]
+pub fn hashset(input: TokenStream) -> TokenStream {
+    expand_set(input, quote! { ::std::collections::HashSet }, true)
+}
+
+/// Converts a struct with named fields into a `step_3_2::map::Map` of its fields.
+///
+/// A field is kept under its name, unless it's renamed with `#[map(rename = "name")]` or skipped with `#[map(skip)]`,
+/// while an `Option` one is left out, when it's `None`. The path to `step_3_2` may be given with
+/// `#[map(crate = "path")]` on the struct, when the crate is renamed or re-exported.
+#[proc_macro_derive(IntoBTreeMap, attributes(map))]
+pub fn derive_into_btreemap(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    map::derive_into(&input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
 
-    expanded.into()
+/// Converts a `step_3_2::map::Map` back into a struct with named fields, failing on missing or invalid ones.
+///
+/// Fields marked with `#[map(default)]` take their default value, when they're missing, as the skipped ones always do.
+///
+/// Along with the conversion, a `<Name>Builder` is generated, returned by `Name::builder()`, which has a setter for
+/// every field not skipped, and builds the struct the way it's converted from a map of the set fields. The crate path
+/// is given the same way as for [`macro@IntoBTreeMap`].
+#[proc_macro_derive(FromBTreeMap, attributes(map))]
+pub fn derive_from_btreemap(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    map::derive_from(&input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
 }
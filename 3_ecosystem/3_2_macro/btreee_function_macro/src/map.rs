@@ -0,0 +1,301 @@
+//! Derives converting structs to and from the `step_3_2::map::Map` of their fields, and building them field by field.
+
+use std::collections::HashMap;
+
+use proc_macro2::TokenStream as TokenStream2;
+use quote::{format_ident, quote};
+use syn::{
+    Data, DataStruct, DeriveInput, Fields, GenericArgument, Ident, LitStr, Path, PathArguments,
+    Type,
+};
+
+/// Types the fields may be of, either as they are or wrapped into an [`Option`].
+const SUPPORTED_TYPES: &[&str] = &[
+    "bool", "i8", "i16", "i32", "i64", "isize", "u8", "u16", "u32", "u64", "usize", "f32", "f64",
+    "char", "String",
+];
+
+struct Field<'a> {
+    ident: &'a Ident,
+    key: String,
+    /// Type of the value kept in the map, which is the inner one of an [`Option`].
+    ty: &'a Type,
+    /// Whether the field is left out of the map, while taking its default value when converted back.
+    skip: bool,
+    /// Whether the field takes its default value, when it's missing in the map.
+    default: bool,
+    /// Whether the field is an [`Option`], which is missing in the map when it's [`None`].
+    optional: bool,
+}
+
+impl<'a> Field<'a> {
+    fn parse(field: &'a syn::Field) -> syn::Result<Self> {
+        let ident = field.ident.as_ref().expect("fields are named");
+        let (mut rename, mut skip, mut default) = (None, false, false);
+        for attr in field.attrs.iter().filter(|a| a.path().is_ident("map")) {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("rename") {
+                    rename = Some(meta.value()?.parse::<LitStr>()?);
+                } else if meta.path.is_ident("skip") {
+                    skip = true;
+                } else if meta.path.is_ident("default") {
+                    default = true;
+                } else {
+                    return Err(meta.error(
+                        "unsupported map attribute, expected `rename`, `skip` or `default`",
+                    ));
+                }
+                Ok(())
+            })?;
+        }
+
+        // Skipped fields are never converted, so they may be of any type with a default value.
+        let (ty, optional) = match field_type(&field.ty) {
+            _ if skip => (&field.ty, false),
+            Some(FieldType::Optional(inner)) => (inner, true),
+            Some(FieldType::Required) => (&field.ty, false),
+            None => {
+                let ty = &field.ty;
+                return Err(syn::Error::new_spanned(
+                    ty,
+                    format!(
+                        "unsupported field type `{}`, expected one of {} or an `Option` of them; \
+                         skip the field with `#[map(skip)]`",
+                        crate::pretty(ty),
+                        SUPPORTED_TYPES.join(", "),
+                    ),
+                ));
+            }
+        };
+
+        Ok(Field {
+            ident,
+            key: rename.map_or_else(|| ident.to_string(), |r| r.value()),
+            ty,
+            skip,
+            default,
+            optional,
+        })
+    }
+}
+
+enum FieldType<'a> {
+    Required,
+    /// An [`Option`] of the given type.
+    Optional(&'a Type),
+}
+
+/// Tells the type of a field apart by its name, as no other information about it is available to a macro.
+fn field_type(ty: &Type) -> Option<FieldType<'_>> {
+    let segment = match ty {
+        Type::Group(group) => return field_type(&group.elem),
+        Type::Path(path) if path.qself.is_none() => path.path.segments.last()?,
+        _ => return None,
+    };
+
+    match &segment.arguments {
+        PathArguments::None => SUPPORTED_TYPES
+            .iter()
+            .any(|t| segment.ident == t)
+            .then_some(FieldType::Required),
+        PathArguments::AngleBracketed(args)
+            if segment.ident == "Option" && args.args.len() == 1 =>
+        {
+            match &args.args[0] {
+                GenericArgument::Type(inner) => match field_type(inner)? {
+                    FieldType::Required => Some(FieldType::Optional(inner)),
+                    FieldType::Optional(_) => None,
+                },
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+/// Parses the path to the crate the `map` module is in, given with `#[map(crate = "path")]` on the struct, e.g. when
+/// the crate is renamed or re-exported, and being `::step_3_2` otherwise.
+fn crate_path(input: &DeriveInput) -> syn::Result<TokenStream2> {
+    let mut path = None;
+    for attr in input.attrs.iter().filter(|a| a.path().is_ident("map")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("crate") {
+                path = Some(meta.value()?.parse::<LitStr>()?.parse::<Path>()?);
+                Ok(())
+            } else {
+                Err(meta.error("unsupported map attribute, expected `crate`"))
+            }
+        })?;
+    }
+    Ok(path.map_or_else(|| quote! { ::step_3_2 }, |path| quote! { #path }))
+}
+
+/// Parses the named fields of the struct, failing on all the invalid ones at once.
+fn fields(input: &DeriveInput) -> syn::Result<Vec<Field<'_>>> {
+    let named = match &input.data {
+        Data::Struct(DataStruct {
+            fields: Fields::Named(named),
+            ..
+        }) => named,
+        _ => {
+            return Err(syn::Error::new_spanned(
+                &input.ident,
+                "only structs with named fields may be converted to and from maps",
+            ))
+        }
+    };
+
+    let mut fields = Vec::new();
+    let mut errors = Vec::<syn::Error>::new();
+    let mut keys = HashMap::new();
+    for field in &named.named {
+        match Field::parse(field) {
+            Ok(parsed) => {
+                let first = (!parsed.skip)
+                    .then(|| keys.insert(parsed.key.clone(), parsed.ident))
+                    .flatten();
+                if let Some(first) = first {
+                    errors.push(syn::Error::new_spanned(
+                        field,
+                        format!("duplicate key `{}`, already used by `{first}`", parsed.key),
+                    ));
+                }
+                fields.push(parsed);
+            }
+            Err(e) => errors.push(e),
+        }
+    }
+
+    match errors.into_iter().reduce(|mut all, e| {
+        all.combine(e);
+        all
+    }) {
+        Some(errors) => Err(errors),
+        None => Ok(fields),
+    }
+}
+
+pub fn derive_into(input: &DeriveInput) -> syn::Result<TokenStream2> {
+    let krate = crate_path(input)?;
+    let insertions = fields(input)?.into_iter().filter(|f| !f.skip).map(
+        |Field {
+             ident,
+             key,
+             optional,
+             ..
+         }| {
+            if optional {
+                quote! {
+                    if let ::core::option::Option::Some(v) = value.#ident {
+                        map.insert(#key, #krate::map::Value::from(v));
+                    }
+                }
+            } else {
+                quote! { map.insert(#key, #krate::map::Value::from(value.#ident)); }
+            }
+        },
+    );
+
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics ::core::convert::From<#name #ty_generics> for #krate::map::Map
+            #where_clause
+        {
+            #[allow(unused_mut, unused_variables)]
+            fn from(value: #name #ty_generics) -> Self {
+                let mut map = #krate::map::Map::new();
+                #(#insertions)*
+                map
+            }
+        }
+    })
+}
+
+pub fn derive_from(input: &DeriveInput) -> syn::Result<TokenStream2> {
+    let krate = crate_path(input)?;
+    let fields = fields(input)?;
+    let initializers = fields.iter().map(|field| {
+        let Field { ident, key, .. } = &field;
+        let value = match *field {
+            Field { skip: true, .. } => quote! { ::core::default::Default::default() },
+            Field { optional: true, .. } => {
+                quote! { #krate::map::take_optional(&mut map, #key)? }
+            }
+            Field { default: true, .. } => quote! {
+                #krate::map::take_optional(&mut map, #key)?.unwrap_or_default()
+            },
+            _ => quote! { #krate::map::take(&mut map, #key)? },
+        };
+        quote! { #ident: #value }
+    });
+
+    let vis = &input.vis;
+    let setters = fields
+        .iter()
+        .filter(|f| !f.skip)
+        .map(|Field { ident, key, ty, .. }| {
+            let doc = format!("Sets the `{key}` field.");
+            quote! {
+                #[doc = #doc]
+                #vis fn #ident(&mut self, value: #ty) -> &mut Self {
+                    self.map.insert(#key, #krate::map::Value::from(value));
+                    self
+                }
+            }
+        });
+
+    let (name, generics) = (&input.ident, &input.generics);
+    let builder = format_ident!("{name}Builder");
+    let builder_doc = format!(
+        "Builder of [`{name}`], setting its fields one by one, while failing to build on the missing required ones."
+    );
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics ::core::convert::TryFrom<#krate::map::Map> for #name #ty_generics
+            #where_clause
+        {
+            type Error = #krate::map::MapError;
+
+            #[allow(unused_mut)]
+            fn try_from(mut map: #krate::map::Map) -> ::core::result::Result<Self, Self::Error> {
+                ::core::result::Result::Ok(Self {
+                    #(#initializers),*
+                })
+            }
+        }
+
+        #[doc = #builder_doc]
+        #vis struct #builder #generics #where_clause {
+            map: #krate::map::Map,
+            marker: ::core::marker::PhantomData<fn() -> #name #ty_generics>,
+        }
+
+        impl #impl_generics ::core::default::Default for #builder #ty_generics #where_clause {
+            fn default() -> Self {
+                Self {
+                    map: #krate::map::Map::new(),
+                    marker: ::core::marker::PhantomData,
+                }
+            }
+        }
+
+        impl #impl_generics #builder #ty_generics #where_clause {
+            #(#setters)*
+
+            /// Builds the value out of the fields set so far, the way it's converted from a map of them.
+            #vis fn build(&self) -> ::core::result::Result<#name #ty_generics, #krate::map::MapError> {
+                <#name #ty_generics as ::core::convert::TryFrom<_>>::try_from(
+                    ::core::clone::Clone::clone(&self.map),
+                )
+            }
+        }
+
+        impl #impl_generics #name #ty_generics #where_clause {
+            /// Creates an empty builder of this value.
+            #vis fn builder() -> #builder #ty_generics {
+                ::core::default::Default::default()
+            }
+        }
+    })
+}
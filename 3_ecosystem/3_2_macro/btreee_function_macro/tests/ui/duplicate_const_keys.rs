@@ -0,0 +1,11 @@
+use btree_function_macro::hashmap;
+
+const KEY: &str = "key";
+
+mod keys {
+    pub const KEY: &str = "other";
+}
+
+fn main() {
+    let _ = hashmap!(KEY => 1, keys::KEY => 2, KEY => 3);
+}
@@ -0,0 +1,11 @@
+use btree_function_macro::{btreemap, hashset};
+
+fn main() {
+    let _ = btreemap! {
+        "a" => 1,
+        "b" => 2,
+        "a" => 3,
+    };
+    let _ = btreemap!(1 => 'a', 0x1 => 'b', 1u8 => 'c', -1 => 'd');
+    let _ = hashset!['x', 'y', ('x')];
+}
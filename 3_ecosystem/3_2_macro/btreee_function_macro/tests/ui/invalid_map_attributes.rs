@@ -0,0 +1,26 @@
+use btree_function_macro::{FromBTreeMap, IntoBTreeMap};
+
+#[derive(IntoBTreeMap)]
+struct User {
+    #[map(flatten)]
+    name: String,
+    #[map(rename = "id")]
+    login: String,
+    id: u64,
+}
+
+#[derive(FromBTreeMap)]
+enum Role {
+    Admin,
+}
+
+#[derive(FromBTreeMap)]
+struct Point(i32, i32);
+
+#[derive(IntoBTreeMap)]
+#[map(krate = "step_3_2")]
+struct Group {
+    name: String,
+}
+
+fn main() {}
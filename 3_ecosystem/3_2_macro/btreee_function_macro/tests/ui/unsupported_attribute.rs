@@ -0,0 +1,6 @@
+use btree_function_macro::btreemap;
+
+fn main() {
+    let _ = btreemap!(#[allow(duplicates)] "a" => 1, "a" => 2);
+    let _ = btreemap!(#[allow_duplicates(true)] "a" => 1, "a" => 2);
+}
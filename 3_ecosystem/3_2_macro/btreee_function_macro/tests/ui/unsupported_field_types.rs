@@ -0,0 +1,14 @@
+use btree_function_macro::IntoBTreeMap;
+
+struct Config;
+
+#[derive(IntoBTreeMap)]
+struct User {
+    name: String,
+    tags: Vec<String>,
+    nested: Option<Option<u8>>,
+    #[map(skip)]
+    config: Config,
+}
+
+fn main() {}
@@ -0,0 +1,4 @@
+// Lets the derives refer to `::step_3_2` from within the crate itself.
+extern crate self as step_3_2;
+
+pub mod map;
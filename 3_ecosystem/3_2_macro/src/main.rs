@@ -1,8 +1,18 @@
+//! Every collection macro takes an optional explicit type prefix, e.g. `btreemap!{<String, u32>; "a".into() => 1}`,
+//! while the hash ones take an optional capacity hint as well, e.g. `hashset!{with_capacity(16); 1, 2}`.
+
 #[macro_export]
 macro_rules! btreemap {
-    ( $($key:expr => $value:expr),* ) => {
+    (< $k:ty, $v:ty >; $($rest:tt)*) => {
         {
-            let mut map = std::collections::BTreeMap::new();
+            let map: ::std::collections::BTreeMap<$k, $v> = $crate::btreemap!($($rest)*);
+            map
+        }
+    };
+    ( $($key:expr => $value:expr),* $(,)? ) => {
+        {
+            #[allow(unused_mut)]
+            let mut map = ::std::collections::BTreeMap::new();
             $(
                 map.insert($key, $value);
             )*
@@ -11,15 +21,91 @@ macro_rules! btreemap {
     };
 }
 
+#[macro_export]
+macro_rules! btreeset {
+    (< $t:ty >; $($rest:tt)*) => {
+        {
+            let set: ::std::collections::BTreeSet<$t> = $crate::btreeset!($($rest)*);
+            set
+        }
+    };
+    ( $($value:expr),* $(,)? ) => {
+        {
+            #[allow(unused_mut)]
+            let mut set = ::std::collections::BTreeSet::new();
+            $(
+                set.insert($value);
+            )*
+            set
+        }
+    };
+}
+
+#[macro_export]
+macro_rules! hashmap {
+    (< $k:ty, $v:ty >; $($rest:tt)*) => {
+        {
+            let map: ::std::collections::HashMap<$k, $v> = $crate::hashmap!($($rest)*);
+            map
+        }
+    };
+    (with_capacity($capacity:expr); $($key:expr => $value:expr),* $(,)? ) => {
+        {
+            #[allow(unused_mut)]
+            let mut map = ::std::collections::HashMap::with_capacity($capacity);
+            $(
+                map.insert($key, $value);
+            )*
+            map
+        }
+    };
+    ( $($key:expr => $value:expr),* $(,)? ) => {
+        $crate::hashmap!(with_capacity($crate::count!($($key),*)); $($key => $value),*)
+    };
+}
+
+#[macro_export]
+macro_rules! hashset {
+    (< $t:ty >; $($rest:tt)*) => {
+        {
+            let set: ::std::collections::HashSet<$t> = $crate::hashset!($($rest)*);
+            set
+        }
+    };
+    (with_capacity($capacity:expr); $($value:expr),* $(,)? ) => {
+        {
+            #[allow(unused_mut)]
+            let mut set = ::std::collections::HashSet::with_capacity($capacity);
+            $(
+                set.insert($value);
+            )*
+            set
+        }
+    };
+    ( $($value:expr),* $(,)? ) => {
+        $crate::hashset!(with_capacity($crate::count!($($value),*)); $($value),*)
+    };
+}
+
+/// Counts the given expressions at compile time, without evaluating them.
+#[doc(hidden)]
+#[macro_export]
+macro_rules! count {
+    (@unit $_:expr) => { () };
+    ( $($value:expr),* ) => { <[()]>::len(&[$($crate::count!(@unit $value)),*]) };
+}
+
 fn main() {
     let map = btreemap!("hello" => 1, "world" => 2);
     println!("{:?}", map);
 }
 
 #[cfg(test)]
-mod tests {
-    use super::*;
+#[path = "../tests/spec/collections.rs"]
+mod collections;
 
+#[cfg(test)]
+mod tests {
     #[test]
     fn should_create_btreemap() {
         let map = btreemap!("hello" => 1, "world" => 2);
@@ -0,0 +1,247 @@
+//! Values of the maps the structs deriving `IntoBTreeMap` and `FromBTreeMap` are converted to and from.
+
+use std::collections::BTreeMap;
+use std::fmt;
+
+pub use btree_function_macro::{FromBTreeMap, IntoBTreeMap};
+use thiserror::Error;
+
+/// Map a struct is converted to and from, keyed by the names of its fields.
+pub type Map = BTreeMap<&'static str, Value>;
+
+/// Value of a single field of a struct.
+///
+/// Signed and unsigned integers are kept apart, so neither loses its range, while either converts to the other, if it
+/// fits into it.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    Bool(bool),
+    Int(i64),
+    UInt(u64),
+    Float(f64),
+    Char(char),
+    String(String),
+}
+
+impl Value {
+    fn kind(&self) -> &'static str {
+        match self {
+            Value::Bool(_) => "bool",
+            Value::Int(_) => "signed integer",
+            Value::UInt(_) => "unsigned integer",
+            Value::Float(_) => "float",
+            Value::Char(_) => "char",
+            Value::String(_) => "string",
+        }
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Bool(v) => write!(f, "{v}"),
+            Value::Int(v) => write!(f, "{v}"),
+            Value::UInt(v) => write!(f, "{v}"),
+            Value::Float(v) => write!(f, "{v}"),
+            Value::Char(v) => write!(f, "{v:?}"),
+            Value::String(v) => write!(f, "{v:?}"),
+        }
+    }
+}
+
+/// Error of converting a [`Value`] into a field of the type it doesn't fit.
+#[derive(Clone, Debug, Error, PartialEq)]
+#[error("expected {expected}, found {} {found}", found.kind())]
+pub struct ValueError {
+    pub expected: &'static str,
+    pub found: Value,
+}
+
+/// Error of converting a [`Map`] into a struct.
+#[derive(Clone, Debug, Error, PartialEq)]
+pub enum MapError {
+    #[error("missing field `{0}`")]
+    Missing(&'static str),
+    #[error("invalid field `{key}`: {source}")]
+    Invalid {
+        key: &'static str,
+        source: ValueError,
+    },
+}
+
+/// Converts the integers of the given types to and from the variant of the same signedness, while any integer variant
+/// converts back into any of the types, as long as it fits.
+macro_rules! impl_integer_value {
+    ($variant:ident($inner:ty): $($ty:ty),* $(,)?) => {
+        $(
+            impl From<$ty> for Value {
+                fn from(v: $ty) -> Self {
+                    // Never truncated, as no supported integer is wider than 64 bits.
+                    Value::$variant(v as $inner)
+                }
+            }
+
+            impl TryFrom<Value> for $ty {
+                type Error = ValueError;
+
+                fn try_from(value: Value) -> Result<Self, Self::Error> {
+                    let converted = match &value {
+                        Value::Int(v) => <$ty>::try_from(*v).ok(),
+                        Value::UInt(v) => <$ty>::try_from(*v).ok(),
+                        _ => None,
+                    };
+                    converted.ok_or(ValueError {
+                        expected: stringify!($ty),
+                        found: value,
+                    })
+                }
+            }
+        )*
+    };
+}
+
+impl_integer_value!(Int(i64): i8, i16, i32, i64, isize);
+impl_integer_value!(UInt(u64): u8, u16, u32, u64, usize);
+
+/// Converts the given types to and from the variant holding exactly them.
+macro_rules! impl_exact_value {
+    ($($variant:ident($ty:ty) => $expected:literal),* $(,)?) => {
+        $(
+            impl From<$ty> for Value {
+                fn from(v: $ty) -> Self {
+                    Value::$variant(v)
+                }
+            }
+
+            impl TryFrom<Value> for $ty {
+                type Error = ValueError;
+
+                fn try_from(value: Value) -> Result<Self, Self::Error> {
+                    match value {
+                        Value::$variant(v) => Ok(v),
+                        found => Err(ValueError {
+                            expected: $expected,
+                            found,
+                        }),
+                    }
+                }
+            }
+        )*
+    };
+}
+
+impl_exact_value!(
+    Bool(bool) => "bool",
+    Float(f64) => "f64",
+    Char(char) => "char",
+    String(String) => "string",
+);
+
+impl From<f32> for Value {
+    fn from(v: f32) -> Self {
+        Value::Float(v.into())
+    }
+}
+
+impl TryFrom<Value> for f32 {
+    type Error = ValueError;
+
+    /// Narrows the float, so it may lose its precision, as floats usually do.
+    fn try_from(value: Value) -> Result<Self, Self::Error> {
+        match value {
+            Value::Float(v) => Ok(v as f32),
+            found => Err(ValueError {
+                expected: "f32",
+                found,
+            }),
+        }
+    }
+}
+
+/// Takes the value of the required field `key` out of the `map`.
+#[doc(hidden)]
+pub fn take<T: TryFrom<Value, Error = ValueError>>(
+    map: &mut Map,
+    key: &'static str,
+) -> Result<T, MapError> {
+    take_optional(map, key)?.ok_or(MapError::Missing(key))
+}
+
+/// Takes the value of the optional field `key` out of the `map`.
+#[doc(hidden)]
+pub fn take_optional<T: TryFrom<Value, Error = ValueError>>(
+    map: &mut Map,
+    key: &'static str,
+) -> Result<Option<T>, MapError> {
+    map.remove(key)
+        .map(T::try_from)
+        .transpose()
+        .map_err(|source| MapError::Invalid { key, source })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_convert_integers_of_any_signedness_if_they_fit() {
+        assert_eq!(Value::from(-3i8), Value::Int(-3));
+        assert_eq!(Value::from(3usize), Value::UInt(3));
+        assert_eq!(u8::try_from(Value::Int(200)), Ok(200));
+        assert_eq!(i64::try_from(Value::UInt(7)), Ok(7));
+        assert_eq!(
+            u8::try_from(Value::Int(-1)),
+            Err(ValueError {
+                expected: "u8",
+                found: Value::Int(-1)
+            }),
+        );
+        assert!(i64::try_from(Value::UInt(u64::MAX)).is_err());
+    }
+
+    #[test]
+    fn should_convert_exact_values_only() {
+        assert_eq!(
+            String::try_from(Value::from("a".to_string())),
+            Ok("a".into())
+        );
+        assert_eq!(f32::try_from(Value::from(0.5f32)), Ok(0.5));
+        assert!(bool::try_from(Value::Int(1)).is_err());
+        assert!(f64::try_from(Value::Int(1)).is_err());
+    }
+
+    #[derive(Debug, PartialEq, IntoBTreeMap, FromBTreeMap)]
+    struct Pair {
+        key: String,
+        value: Option<u8>,
+    }
+
+    #[test]
+    fn should_derive_within_crate() {
+        let pair = Pair {
+            key: "a".into(),
+            value: Some(1),
+        };
+
+        assert_eq!(Pair::builder().key("a".into()).value(1).build(), Ok(pair));
+        assert_eq!(
+            Map::from(Pair::builder().key("a".into()).build().unwrap()),
+            Map::from([("key", Value::String("a".into()))]),
+        );
+    }
+
+    #[test]
+    fn should_report_field_errors() {
+        let mut map = Map::from([("age", Value::String("ten".into()))]);
+
+        assert_eq!(
+            take::<u8>(&mut map, "age").unwrap_err().to_string(),
+            "invalid field `age`: expected u8, found string \"ten\"",
+        );
+        assert_eq!(
+            take::<u8>(&mut map, "name").unwrap_err(),
+            MapError::Missing("name")
+        );
+        assert_eq!(take_optional::<u8>(&mut map, "name"), Ok(None));
+    }
+}
@@ -1,4 +1,7 @@
-use btree_function_macro::btreemap;
+use btree_function_macro::{btreemap, btreeset, hashmap, hashset};
+
+#[path = "spec/collections.rs"]
+mod collections;
 
 #[test]
 fn should_create_btreemap() {
@@ -8,3 +11,24 @@ fn should_create_btreemap() {
     assert_eq!(map["hello"], 1);
     assert_eq!(map["world"], 2);
 }
+
+const FIRST: &str = "first";
+const SECOND: &str = "second";
+
+#[test]
+fn should_allow_distinct_literal_keys() {
+    let map = btreemap!(1 => 'a', -1 => 'b', 0x2 => 'c', FIRST.len() as i64 => 'd');
+    let set = hashset![FIRST, SECOND, "first"];
+
+    assert_eq!(map.len(), 4);
+    assert_eq!(set.len(), 2);
+}
+
+#[test]
+fn should_overwrite_allowed_duplicates() {
+    let map = btreemap!(#[allow_duplicates] "a" => 1, "b" => 2, "a" => 3);
+    let set = btreeset!(#[allow_duplicates] <u8>; 1, 1);
+
+    assert_eq!(map["a"], 3);
+    assert_eq!(set.len(), 1);
+}
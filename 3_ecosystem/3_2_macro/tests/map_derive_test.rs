@@ -0,0 +1,154 @@
+// Renamed, as it is by the crates depending on it under another name.
+extern crate step_3_2 as macros;
+
+use std::collections::BTreeMap;
+
+use step_3_2::map::{FromBTreeMap, IntoBTreeMap, Map, MapError, Value, ValueError};
+
+#[derive(Clone, Debug, Default, PartialEq)]
+struct Session {
+    token: String,
+}
+
+#[derive(Debug, PartialEq, IntoBTreeMap, FromBTreeMap)]
+struct User {
+    id: u64,
+    #[map(rename = "login")]
+    name: String,
+    balance: i32,
+    rating: f64,
+    active: bool,
+    nickname: Option<String>,
+    #[map(default)]
+    visits: usize,
+    #[map(skip)]
+    session: Session,
+}
+
+fn user() -> User {
+    User {
+        id: 1,
+        name: "alice".into(),
+        balance: -5,
+        rating: 4.5,
+        active: true,
+        nickname: None,
+        visits: 3,
+        session: Session {
+            token: "secret".into(),
+        },
+    }
+}
+
+#[test]
+fn should_convert_struct_into_map() {
+    let map = Map::from(user());
+
+    assert_eq!(
+        map,
+        BTreeMap::from([
+            ("id", Value::UInt(1)),
+            ("login", Value::String("alice".into())),
+            ("balance", Value::Int(-5)),
+            ("rating", Value::Float(4.5)),
+            ("active", Value::Bool(true)),
+            ("visits", Value::UInt(3)),
+        ]),
+    );
+}
+
+#[test]
+fn should_convert_map_back_into_struct() {
+    let mut map = Map::from(user());
+    map.insert("nickname", Value::String("al".into()));
+    map.insert("unknown", Value::Bool(false));
+
+    assert_eq!(
+        User::try_from(map),
+        Ok(User {
+            nickname: Some("al".into()),
+            session: Session::default(),
+            ..user()
+        }),
+    );
+}
+
+#[test]
+fn should_take_defaults_of_missing_fields() {
+    let mut map = Map::from(user());
+    map.remove("visits");
+
+    assert_eq!(User::try_from(map).map(|u| u.visits), Ok(0));
+}
+
+#[test]
+fn should_fail_on_missing_and_invalid_fields() {
+    let mut map = Map::from(user());
+    map.remove("login");
+    assert_eq!(User::try_from(map), Err(MapError::Missing("login")));
+
+    let mut map = Map::from(user());
+    map.insert("id", Value::Int(-1));
+    assert_eq!(
+        User::try_from(map),
+        Err(MapError::Invalid {
+            key: "id",
+            source: ValueError {
+                expected: "u64",
+                found: Value::Int(-1),
+            },
+        }),
+    );
+}
+
+#[test]
+fn should_build_struct_field_by_field() {
+    let mut builder = User::builder();
+    builder
+        .id(1)
+        .name("alice".into())
+        .balance(-5)
+        .rating(4.5)
+        .active(true)
+        .visits(3);
+    assert_eq!(
+        builder.build(),
+        Ok(User {
+            session: Session::default(),
+            ..user()
+        }),
+    );
+
+    builder.nickname("al".into());
+    assert_eq!(builder.build().map(|u| u.nickname), Ok(Some("al".into())));
+}
+
+#[test]
+fn should_fail_to_build_without_required_fields() {
+    let mut builder = User::builder();
+    builder.id(1).balance(-5).rating(4.5).active(true);
+
+    assert_eq!(builder.build(), Err(MapError::Missing("login")));
+    assert_eq!(
+        builder.name("alice".into()).build().map(|u| u.visits),
+        Ok(0)
+    );
+}
+
+#[derive(Debug, PartialEq, IntoBTreeMap, FromBTreeMap)]
+#[map(crate = "macros")]
+struct Point {
+    x: i32,
+    y: i32,
+}
+
+#[test]
+fn should_refer_to_renamed_crate() {
+    let map = Map::from(Point { x: 1, y: -1 });
+
+    assert_eq!(Point::try_from(map), Ok(Point { x: 1, y: -1 }));
+    assert_eq!(
+        Point::builder().x(1).y(-1).build(),
+        Ok(Point { x: 1, y: -1 })
+    );
+}
@@ -0,0 +1,91 @@
+//! Behaviour shared by the declarative and the procedural collection macros, so both are checked against it.
+
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+
+// Imports the procedural macros, while the declarative ones are in scope already.
+#[allow(unused_imports)]
+use super::*;
+
+#[test]
+fn should_create_empty_collections() {
+    let map: BTreeMap<&str, i32> = btreemap!();
+    let set: BTreeSet<i32> = btreeset!();
+    let hash_map: HashMap<&str, i32> = hashmap!();
+    let hash_set: HashSet<i32> = hashset!();
+
+    assert!(map.is_empty());
+    assert!(set.is_empty());
+    assert!(hash_map.is_empty());
+    assert!(hash_set.is_empty());
+}
+
+#[test]
+fn should_accept_trailing_comma() {
+    let map = btreemap! {
+        "hello" => 1,
+        "world" => 2,
+    };
+    let set = btreeset![1, 2, 3,];
+    let hash_map = hashmap! {
+        "hello" => 1,
+        "world" => 2,
+    };
+    let hash_set = hashset![1, 2, 3,];
+
+    assert_eq!(map, BTreeMap::from([("hello", 1), ("world", 2)]));
+    assert_eq!(set, BTreeSet::from([1, 2, 3]));
+    assert_eq!(hash_map, HashMap::from([("hello", 1), ("world", 2)]));
+    assert_eq!(hash_set, HashSet::from([1, 2, 3]));
+}
+
+#[test]
+fn should_keep_last_value_of_repeated_key() {
+    // Keys known only at runtime can't be checked for duplicates at compile time.
+    let (a, b) = ("a", "b");
+    let map = btreemap!(a => 1, b => 2, a => 3);
+    let hash_map = hashmap!(a => 1, b => 2, a => 3);
+
+    assert_eq!(map, BTreeMap::from([("a", 3), ("b", 2)]));
+    assert_eq!(hash_map, HashMap::from([("a", 3), ("b", 2)]));
+}
+
+#[test]
+fn should_use_explicit_types() {
+    let map = btreemap! {<String, u64>; "a".into() => 1, "b".into() => 2};
+    let set = btreeset! {<String>; "a".into(), "b".into()};
+    let hash_map = hashmap! {<String, Vec<u8>>; "a".into() => vec![1], "b".into() => Vec::new()};
+    let hash_set = hashset! {<Box<str>>; "a".into()};
+    let empty = btreemap! {<String, u64>;};
+
+    assert_eq!(map["a"], 1u64);
+    assert!(set.contains("b"));
+    assert_eq!(hash_map["a"], [1]);
+    assert!(hash_set.contains("a"));
+    assert!(empty.is_empty());
+}
+
+#[test]
+fn should_preallocate_hash_collections() {
+    let map = hashmap! {with_capacity(64); "a" => 1};
+    let set = hashset! {<u8>; with_capacity(64);};
+
+    assert!(map.capacity() >= 64);
+    assert_eq!(map["a"], 1);
+    assert!(set.capacity() >= 64);
+    assert!(set.is_empty());
+    assert!(hashset![1, 2, 3].capacity() >= 3);
+}
+
+#[test]
+fn should_evaluate_entries_once_in_order() {
+    let mut calls = Vec::new();
+    let mut next = |n| {
+        calls.push(n);
+        n
+    };
+
+    let set = hashset![next(1), next(2), next(3)];
+
+    assert_eq!(set.len(), 3);
+    assert_eq!(calls, [1, 2, 3]);
+}
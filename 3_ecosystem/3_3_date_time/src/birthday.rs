@@ -0,0 +1,387 @@
+//! Scheduling of the birthdays of [`User`]s, each celebrated in their own time zone.
+
+use std::ops::RangeInclusive;
+
+use chrono::{DateTime, Datelike, Duration, NaiveDate, NaiveTime, TimeZone, Utc};
+
+use crate::clock::Clock;
+use crate::{LeapDayPolicy, User};
+
+/// Identifier of the product an iCalendar is created by.
+const PRODUCT_ID: &str = "-//step_3_3//Birthdays//EN";
+/// Longest line of an iCalendar in octets, excluding the line break, as longer ones are folded.
+const MAX_LINE_LEN: usize = 75;
+
+/// A birthday of a [`User`].
+#[derive(Clone, Copy, Debug)]
+pub struct Birthday<'a> {
+    pub user: &'a User,
+    /// Date the birthday is celebrated on in the time zone of the [`User`].
+    pub date: NaiveDate,
+    /// Age the [`User`] turns on the birthday.
+    pub age: u16,
+}
+
+impl<'a> Birthday<'a> {
+    /// Returns all the birthdays of the `user` starting from the given date.
+    fn since(user: &'a User, from: NaiveDate) -> impl Iterator<Item = Birthday<'a>> {
+        // The day of birth itself isn't a birthday.
+        let first_year = from.year().max(user.birthdate.year() + 1);
+        (first_year..)
+            .map_while(move |year| {
+                let age = u16::try_from(year - user.birthdate.year()).ok()?;
+                Self::turning(user, age)
+            })
+            .filter(move |b| b.date >= from)
+    }
+
+    /// Returns the birthday, on which the `user` turns the given `age`, unless it's beyond the supported dates.
+    fn turning(user: &'a User, age: u16) -> Option<Birthday<'a>> {
+        let date = user.birthday_in(user.birthdate.year() + i32::from(age))?;
+        Some(Birthday { user, date, age })
+    }
+
+    /// Returns the moment the birthday starts at, being the midnight in the time zone of the [`User`].
+    pub fn starts_at(&self) -> DateTime<Utc> {
+        let midnight = self.date.and_time(NaiveTime::MIN);
+        let zone = self.user.time_zone;
+        // Some zones skip the midnight when switching to the summer time, so the day starts at the first local time
+        // existing after it.
+        (0..24 * 60)
+            .map(|minutes| midnight + Duration::minutes(minutes))
+            .find_map(|local| zone.from_local_datetime(&local).earliest())
+            .expect("no zone skips a whole day")
+            .with_timezone(&Utc)
+    }
+}
+
+/// Returns the birthdays of the `users` celebrated within the given number of `days` from today, today included, in
+/// the order they come.
+///
+/// Today is the one of every [`User`] in their own time zone, so the birthdays starting today may already be over for
+/// some of them.
+pub fn within<'a>(users: &'a [User], clock: &impl Clock, days: u32) -> Vec<Birthday<'a>> {
+    let mut birthdays = users
+        .iter()
+        .flat_map(|user| {
+            let today = user.today(clock);
+            let last = today
+                .checked_add_days(chrono::Days::new(days.into()))
+                .unwrap_or(NaiveDate::MAX);
+            Birthday::since(user, today).take_while(move |b| b.date <= last)
+        })
+        .collect::<Vec<_>>();
+    birthdays.sort_by_key(|b| (b.date, b.starts_at()));
+    birthdays
+}
+
+/// Returns the nearest birthdays of the `users`, which are several ones, when they're celebrated on the same day.
+pub fn next<'a>(users: &'a [User], clock: &impl Clock) -> Vec<Birthday<'a>> {
+    let mut upcoming = users
+        .iter()
+        .filter_map(|user| {
+            let today = user.today(clock);
+            let birthday = Birthday::since(user, today).next()?;
+            Some(((birthday.date - today).num_days(), birthday))
+        })
+        .collect::<Vec<_>>();
+    upcoming.sort_by_key(|(days_left, b)| (*days_left, b.starts_at()));
+
+    let nearest = upcoming.first().map(|(days_left, _)| *days_left);
+    upcoming
+        .into_iter()
+        .take_while(|(days_left, _)| Some(*days_left) == nearest)
+        .map(|(_, b)| b)
+        .collect()
+}
+
+/// Returns the birthdays of the `users` within the range of `dates`, on which they turn any of the milestone `ages`.
+pub fn milestones<'a>(
+    users: &'a [User],
+    ages: &[u16],
+    dates: RangeInclusive<NaiveDate>,
+) -> Vec<Birthday<'a>> {
+    let mut birthdays = users
+        .iter()
+        .flat_map(|user| {
+            ages.iter()
+                .filter_map(move |age| Birthday::turning(user, *age))
+        })
+        .filter(|b| b.age > 0 && dates.contains(&b.date))
+        .collect::<Vec<_>>();
+    birthdays.sort_by_key(|b| (b.date, b.starts_at()));
+    birthdays
+}
+
+/// Exports the birthdays of the `users` as yearly recurring all-day events of an iCalendar ([RFC 5545]).
+///
+/// Events are identified by the position of their [`User`] in the `users`, so the same list should be exported in the
+/// same order, for calendar applications to update the previously imported events.
+///
+/// [RFC 5545]: https://datatracker.ietf.org/doc/html/rfc5545
+pub fn to_icalendar(users: &[User], clock: &impl Clock) -> String {
+    let stamp = clock.now().format("%Y%m%dT%H%M%SZ");
+
+    let mut lines = vec![
+        "BEGIN:VCALENDAR".to_owned(),
+        "VERSION:2.0".to_owned(),
+        format!("PRODID:{PRODUCT_ID}"),
+        "CALSCALE:GREGORIAN".to_owned(),
+    ];
+    for (i, user) in users.iter().enumerate() {
+        let birthdate = user.birthdate;
+        let summary = match user.name.as_str() {
+            "" => "Birthday".to_owned(),
+            name => format!("Birthday of {}", escape_text(name)),
+        };
+        // Occurrences on nonexistent dates are skipped, so leap day ones are moved explicitly.
+        let rule = match (birthdate.month(), birthdate.day(), user.leap_day) {
+            (2, 29, LeapDayPolicy::Feb28) => "FREQ=YEARLY;BYMONTH=2;BYMONTHDAY=-1",
+            // Day 60 is February 29 in leap years and March 1 in common ones.
+            (2, 29, LeapDayPolicy::Mar1) => "FREQ=YEARLY;BYYEARDAY=60",
+            _ => "FREQ=YEARLY",
+        };
+        lines.extend([
+            "BEGIN:VEVENT".to_owned(),
+            format!("UID:{i}-{}@birthdays.step_3_3", birthdate.format("%Y%m%d")),
+            format!("DTSTAMP:{stamp}"),
+            format!("DTSTART;VALUE=DATE:{}", birthdate.format("%Y%m%d")),
+            format!("RRULE:{rule}"),
+            format!("SUMMARY:{summary}"),
+            "TRANSP:TRANSPARENT".to_owned(),
+            "END:VEVENT".to_owned(),
+        ]);
+    }
+    lines.push("END:VCALENDAR".to_owned());
+
+    lines.iter().fold(String::new(), |mut out, line| {
+        fold_line(&mut out, line);
+        out
+    })
+}
+
+/// Escapes the characters, which have a special meaning in the text values of an iCalendar.
+fn escape_text(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '\\' | ';' | ',' => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            '\n' => escaped.push_str("\\n"),
+            '\r' => {}
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Writes the content `line` into the `out`, splitting it into the lines of the allowed length, never in the middle of a
+/// character.
+fn fold_line(out: &mut String, line: &str) {
+    let mut len = 0;
+    for c in line.chars() {
+        if len + c.len_utf8() > MAX_LINE_LEN {
+            // The continuation starts with a space, which counts towards its length.
+            out.push_str("\r\n ");
+            len = 1;
+        }
+        out.push(c);
+        len += c.len_utf8();
+    }
+    out.push_str("\r\n");
+}
+
+#[cfg(test)]
+mod birthday_spec {
+    use chrono_tz::Tz;
+
+    use super::*;
+    use crate::clock::FixedClock;
+
+    fn date(y: i32, m: u32, d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(y, m, d).unwrap()
+    }
+
+    fn user(name: &str, (y, m, d): (i32, u32, u32)) -> User {
+        User::with_birthdate(y, m, d).unwrap().named(name)
+    }
+
+    fn at(moment: &str) -> FixedClock {
+        FixedClock(moment.parse().unwrap())
+    }
+
+    fn names<'a>(birthdays: &[Birthday<'a>]) -> Vec<(&'a str, NaiveDate, u16)> {
+        birthdays
+            .iter()
+            .map(|b| (b.user.name.as_str(), b.date, b.age))
+            .collect()
+    }
+
+    #[test]
+    fn finds_next_birthdays() {
+        let users = [
+            user("alice", (1990, 7, 4)),
+            user("bob", (1985, 6, 28)),
+            user("carol", (2000, 6, 28)),
+            user("dave", (1970, 6, 25)),
+        ];
+
+        assert_eq!(
+            names(&next(&users, &at("2019-06-26T12:00:00Z"))),
+            [
+                ("bob", date(2019, 6, 28), 34),
+                ("carol", date(2019, 6, 28), 19),
+            ],
+        );
+        assert!(next(&[], &at("2019-06-26T12:00:00Z")).is_empty());
+    }
+
+    #[test]
+    fn counts_todays_birthday_as_next() {
+        let users = [
+            user("alice", (1990, 6, 27)),
+            user("bob", (1990, 6, 26)).in_time_zone(Tz::America__New_York),
+        ];
+
+        // Still June 26 in New York, while it's June 27 in UTC already, so both birthdays are today.
+        assert_eq!(
+            names(&next(&users, &at("2019-06-27T02:00:00Z"))),
+            [
+                ("bob", date(2019, 6, 26), 29),
+                ("alice", date(2019, 6, 27), 29),
+            ],
+        );
+    }
+
+    #[test]
+    fn starts_after_midnight_skipped_by_summer_time() {
+        // Clocks in Santiago jump from 00:00 to 01:00 on 2019-09-08, switching from -04:00 to -03:00.
+        let user = user("alice", (1990, 9, 8)).in_time_zone(Tz::America__Santiago);
+        let birthday = Birthday::since(&user, date(2019, 1, 1)).next().unwrap();
+
+        assert_eq!(birthday.date, date(2019, 9, 8));
+        assert_eq!(
+            birthday.starts_at(),
+            "2019-09-08T04:00:00Z".parse::<DateTime<Utc>>().unwrap(),
+        );
+    }
+
+    #[test]
+    fn finds_birthdays_within_days_across_year_end() {
+        let users = [
+            user("alice", (1990, 1, 2)),
+            user("bob", (1985, 12, 31)),
+            user("carol", (2000, 1, 10)),
+            user("dave", (1970, 12, 20)),
+        ];
+        let clock = at("2019-12-28T12:00:00Z");
+
+        assert_eq!(
+            names(&within(&users, &clock, 7)),
+            [
+                ("bob", date(2019, 12, 31), 34),
+                ("alice", date(2020, 1, 2), 30),
+            ],
+        );
+        assert_eq!(within(&users, &clock, 0).len(), 0);
+        assert_eq!(within(&users, &clock, 365).len(), 4);
+        assert_eq!(within(&users, &clock, 366 + 365).len(), 8);
+
+        // Every birthday up to the oldest age, the days being beyond the latest supported date.
+        let all = within(&users, &clock, u32::MAX);
+        assert_eq!(all.len(), 65_506 + 65_502 + 65_516 + 65_486);
+        assert_eq!(
+            names(&all[all.len() - 1..]),
+            [("carol", date(67535, 1, 10), u16::MAX)],
+        );
+    }
+
+    #[test]
+    fn finds_leap_day_birthdays_by_policy() {
+        let users = [
+            user("mar1", (2000, 2, 29)),
+            user("feb28", (2000, 2, 29)).with_leap_day_policy(LeapDayPolicy::Feb28),
+        ];
+
+        assert_eq!(
+            names(&within(&users, &at("2019-02-27T12:00:00Z"), 2)),
+            [
+                ("feb28", date(2019, 2, 28), 19),
+                ("mar1", date(2019, 3, 1), 19),
+            ],
+        );
+    }
+
+    #[test]
+    fn finds_milestones_in_range() {
+        let users = [
+            user("alice", (1990, 7, 4)),
+            user("bob", (1980, 3, 1)),
+            user("carol", (2001, 6, 26)),
+        ];
+
+        assert_eq!(
+            names(&milestones(
+                &users,
+                &[18, 30],
+                date(2019, 1, 1)..=date(2020, 12, 31),
+            )),
+            [
+                ("carol", date(2019, 6, 26), 18),
+                ("alice", date(2020, 7, 4), 30),
+            ],
+        );
+        assert!(milestones(&users, &[30], date(2019, 1, 1)..=date(2019, 12, 31)).is_empty());
+    }
+
+    #[test]
+    fn starts_birthday_at_midnight_of_user_time_zone() {
+        let users = [user("alice", (1990, 6, 27)).in_time_zone(Tz::Asia__Tokyo)];
+        let birthday = within(&users, &at("2019-06-26T12:00:00Z"), 1)[0];
+
+        assert_eq!(
+            birthday.starts_at(),
+            "2019-06-26T15:00:00Z".parse::<DateTime<Utc>>().unwrap(),
+        );
+    }
+
+    #[test]
+    fn exports_icalendar() {
+        let users = [
+            user("Alice, Jr.", (1990, 7, 4)),
+            user("", (2000, 2, 29)),
+            user("Bob", (2000, 2, 29)).with_leap_day_policy(LeapDayPolicy::Feb28),
+        ];
+
+        let ics = to_icalendar(&users, &at("2019-06-26T12:00:00Z"));
+
+        assert!(ics.starts_with("BEGIN:VCALENDAR\r\nVERSION:2.0\r\n"));
+        assert!(ics.ends_with("END:VEVENT\r\nEND:VCALENDAR\r\n"));
+        assert_eq!(ics.matches("BEGIN:VEVENT\r\n").count(), 3);
+        for line in [
+            "UID:0-19900704@birthdays.step_3_3",
+            "DTSTAMP:20190626T120000Z",
+            "DTSTART;VALUE=DATE:19900704",
+            "RRULE:FREQ=YEARLY\r\n",
+            "SUMMARY:Birthday of Alice\\, Jr.",
+            "SUMMARY:Birthday\r\n",
+            "RRULE:FREQ=YEARLY;BYYEARDAY=60",
+            "RRULE:FREQ=YEARLY;BYMONTH=2;BYMONTHDAY=-1",
+        ] {
+            assert!(ics.contains(line), "{line:?} in {ics}");
+        }
+    }
+
+    #[test]
+    fn folds_long_lines() {
+        let users = [user(&"я".repeat(50), (1990, 7, 4))];
+
+        let ics = to_icalendar(&users, &at("2019-06-26T12:00:00Z"));
+
+        assert!(ics.split("\r\n").all(|line| line.len() <= MAX_LINE_LEN));
+        let unfolded = ics.replace("\r\n ", "");
+        assert!(unfolded.contains(&format!("SUMMARY:Birthday of {}\r\n", "я".repeat(50))));
+    }
+}
@@ -0,0 +1,62 @@
+//! Sources of the current time, so anything depending on it may be checked against any moment.
+
+use chrono::{DateTime, Utc};
+
+/// Source of the current moment.
+pub trait Clock {
+    fn now(&self) -> DateTime<Utc>;
+}
+
+/// [`Clock`] of the operating system.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> DateTime<Utc> {
+        Utc::now()
+    }
+}
+
+/// [`Clock`] stopped at the given moment.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FixedClock(pub DateTime<Utc>);
+
+impl Clock for FixedClock {
+    fn now(&self) -> DateTime<Utc> {
+        self.0
+    }
+}
+
+impl<C: Clock + ?Sized> Clock for &C {
+    fn now(&self) -> DateTime<Utc> {
+        (**self).now()
+    }
+}
+
+#[cfg(test)]
+mod clock_spec {
+    use super::*;
+
+    fn now_of(clock: impl Clock) -> DateTime<Utc> {
+        clock.now()
+    }
+
+    #[test]
+    fn fixed_clock_stands_still() {
+        let moment = "2019-06-26T12:00:00Z".parse().unwrap();
+        let clock = FixedClock(moment);
+
+        let borrowed: &dyn Clock = &clock;
+
+        assert_eq!(clock.now(), moment);
+        assert_eq!(now_of(borrowed), moment);
+    }
+
+    #[test]
+    fn system_clock_goes_on() {
+        let before = Utc::now();
+        let now = SystemClock.now();
+
+        assert!(before <= now && now <= Utc::now());
+    }
+}
@@ -1,59 +1,214 @@
-use chrono::{Datelike, NaiveDate};
+mod birthday;
+mod clock;
+
+use chrono::{Datelike, Months, NaiveDate};
+use chrono_tz::Tz;
 use thiserror::Error;
 
+use self::clock::{Clock, FixedClock, SystemClock};
+
 #[derive(Error, Debug)]
 #[error("invalid date")]
 pub struct InvalidDateError;
 
 fn main() {
-    println!("Implement me!");
+    let user = User::with_birthdate(2000, 2, 29)
+        .unwrap()
+        .in_time_zone(chrono_tz::Europe::Kyiv)
+        .with_leap_day_policy(LeapDayPolicy::Feb28);
+
+    let then = FixedClock("2019-06-26T12:00:00Z".parse().unwrap());
+    for (when, clock) in [("then", &then as &dyn Clock), ("now", &SystemClock)] {
+        let age = user.exact_age(&clock);
+        println!(
+            "{when}: {} years, {} months and {} days old, {}",
+            age.years,
+            age.months,
+            age.days,
+            if user.is_adult(&clock) {
+                "adult"
+            } else {
+                "not adult yet"
+            },
+        );
+    }
+
+    let users = [
+        user.named("Alice"),
+        User::with_birthdate(1990, 7, 4).unwrap().named("Bob"),
+        User::with_birthdate(1970, 1, 1)
+            .unwrap()
+            .named("Carol")
+            .in_time_zone(chrono_tz::Asia::Tokyo),
+    ];
+    for b in birthday::next(&users, &then) {
+        println!("next: {} turns {} at {}", b.user.name, b.age, b.starts_at());
+    }
+    for b in birthday::within(&users, &then, 365) {
+        println!(
+            "within a year: {} turns {} on {}",
+            b.user.name, b.age, b.date
+        );
+    }
+    let (from, to) = (then.now().date_naive(), NaiveDate::MAX);
+    for b in birthday::milestones(&users, &[50, 100], from..=to) {
+        println!("milestone: {} turns {} on {}", b.user.name, b.age, b.date);
+    }
+    print!("{}", birthday::to_icalendar(&users, &then));
+}
+
+/// When the birthday of someone born on February 29 is celebrated in common years.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum LeapDayPolicy {
+    /// On the last day of February.
+    Feb28,
+    /// On the day following the last day of February, as the age is counted in most jurisdictions.
+    #[default]
+    Mar1,
 }
 
-const NOW: &str = "2019-06-26";
+/// Age in whole years, months and days, like the one written in official documents.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ExactAge {
+    pub years: u16,
+    pub months: u8,
+    pub days: u8,
+}
 
+#[derive(Debug)]
 struct User {
+    name: String,
     birthdate: NaiveDate,
+    /// Zone the birthdate is in, so the birthday starts at its midnight, rather than the UTC one.
+    time_zone: Tz,
+    leap_day: LeapDayPolicy,
 }
 
 impl User {
     fn with_birthdate(year: i32, month: u32, day: u32) -> Result<Self, InvalidDateError> {
         Ok(Self {
+            name: String::new(),
             birthdate: NaiveDate::from_ymd_opt(year, month, day).ok_or(InvalidDateError)?,
+            time_zone: Tz::UTC,
+            leap_day: LeapDayPolicy::default(),
+        })
+    }
+
+    fn named(self, name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            ..self
+        }
+    }
+
+    fn in_time_zone(self, time_zone: Tz) -> Self {
+        Self { time_zone, ..self }
+    }
+
+    fn with_leap_day_policy(self, leap_day: LeapDayPolicy) -> Self {
+        Self { leap_day, ..self }
+    }
+
+    /// Returns the current date in the zone of [`User`].
+    fn today(&self, clock: &impl Clock) -> NaiveDate {
+        clock.now().with_timezone(&self.time_zone).date_naive()
+    }
+
+    /// Returns the date [`User`] celebrates the birthday on in the given `year`.
+    fn birthday_in(&self, year: i32) -> Option<NaiveDate> {
+        let (month, day) = (self.birthdate.month(), self.birthdate.day());
+        NaiveDate::from_ymd_opt(year, month, day).or_else(|| match self.leap_day {
+            LeapDayPolicy::Feb28 => NaiveDate::from_ymd_opt(year, 2, 28),
+            LeapDayPolicy::Mar1 => NaiveDate::from_ymd_opt(year, 3, 1),
         })
     }
 
     /// Returns current age of [`User`] in years.
-    fn age(&self) -> u16 {
-        let now = NaiveDate::parse_from_str(NOW, "%Y-%m-%d").unwrap();
+    fn age(&self, clock: &impl Clock) -> u16 {
+        self.age_at(self.today(clock))
+    }
+
+    /// Returns age of [`User`] in years at the given `date`, saturating at [`u16::MAX`].
+    fn age_at(&self, date: NaiveDate) -> u16 {
+        u16::try_from(self.years_at(date)).unwrap_or(u16::MAX)
+    }
 
-        if self.birthdate > now {
+    /// Returns the number of full years passed since the birthdate at the given `date`.
+    ///
+    /// Unlike the age, isn't bounded, as dates may be hundreds of thousands of years apart.
+    fn years_at(&self, date: NaiveDate) -> i32 {
+        if self.birthdate > date {
             return 0;
         }
 
-        let age = (now.year() - self.birthdate.year()) as u16;
-
-        if now.month() < self.birthdate.month()
-            || (now.month() == self.birthdate.month() && now.day() < self.birthdate.day())
-        {
-            age - 1
+        let years = date.year() - self.birthdate.year();
+        if self.birthday_in(date.year()).is_some_and(|b| date < b) {
+            years - 1
         } else {
-            age
+            years
+        }
+    }
+
+    /// Returns current age of [`User`] in years, months and days.
+    fn exact_age(&self, clock: &impl Clock) -> ExactAge {
+        self.exact_age_at(self.today(clock))
+    }
+
+    /// Returns age of [`User`] in years, months and days at the given `date`.
+    ///
+    /// Months are counted from the birthdate, so for the ones born on the 31st, a shorter month completes on its last
+    /// day.
+    fn exact_age_at(&self, date: NaiveDate) -> ExactAge {
+        if self.birthdate > date {
+            return ExactAge::default();
+        }
+
+        let years = self.years_at(date);
+        let last_birthday = self
+            .birthday_in(self.birthdate.year() + years)
+            .unwrap_or(self.birthdate);
+        let monthsary = |months: u32| {
+            self.birthdate
+                .checked_add_months(Months::new(years.unsigned_abs() * 12 + months))
+                .filter(|d| *d > last_birthday && *d <= date)
+        };
+        let (months, since) = (1..12)
+            .rev()
+            .find_map(|months| Some((months, monthsary(months)?)))
+            .unwrap_or((0, last_birthday));
+
+        ExactAge {
+            years: u16::try_from(years).unwrap_or(u16::MAX),
+            months: months as u8,
+            days: (date - since).num_days() as u8,
         }
     }
 
     /// Checks if [`User`] is 18 years old at the moment.
-    fn is_adult(&self) -> bool {
-        self.age() >= 18
+    fn is_adult(&self, clock: &impl Clock) -> bool {
+        self.age(clock) >= 18
     }
 }
 
 #[cfg(test)]
 mod age_spec {
+    use chrono::{DateTime, Utc};
+
     use super::*;
 
+    const NOW: &str = "2019-06-26T12:00:00Z";
+
+    fn now() -> FixedClock {
+        FixedClock(NOW.parse().unwrap())
+    }
+
+    fn date(y: i32, m: u32, d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(y, m, d).unwrap()
+    }
+
     #[test]
     fn counts_age() {
-        for ((y, m, d), expected) in vec![
+        for ((y, m, d), expected) in [
             ((1990, 6, 4), 29),
             ((1990, 7, 4), 28),
             ((0, 1, 1), 2019),
@@ -61,23 +216,21 @@ mod age_spec {
             ((2019, 6, 25), 0),
         ] {
             let user = User::with_birthdate(y, m, d).unwrap();
-            assert_eq!(user.age(), expected);
+            assert_eq!(user.age(&now()), expected);
         }
     }
 
     #[test]
     fn zero_if_birthdate_in_future() {
-        for ((y, m, d), expected) in
-            vec![((2032, 6, 25), 0), ((3000, 6, 27), 0), ((9999, 6, 27), 0)]
-        {
+        for ((y, m, d), expected) in [((2032, 6, 25), 0), ((3000, 6, 27), 0), ((9999, 6, 27), 0)] {
             let user = User::with_birthdate(y, m, d).unwrap();
-            assert_eq!(user.age(), expected);
+            assert_eq!(user.age(&now()), expected);
         }
     }
 
     #[test]
     fn check_is_adult() {
-        for ((y, m, d), expected) in vec![
+        for ((y, m, d), expected) in [
             ((1990, 6, 4), true),
             ((1990, 7, 4), true),
             ((0, 1, 1), true),
@@ -88,7 +241,109 @@ mod age_spec {
             ((9999, 6, 27), false),
         ] {
             let user = User::with_birthdate(y, m, d).unwrap();
-            assert_eq!(user.is_adult(), expected);
+            assert_eq!(user.is_adult(&now()), expected);
+        }
+    }
+
+    #[test]
+    fn saturates_age_of_longest_spans() {
+        let user = User::with_birthdate(2019 - 65536, 12, 31).unwrap();
+        assert_eq!(user.age_at(date(2019, 6, 26)), u16::MAX);
+        assert_eq!(user.exact_age_at(date(2019, 6, 26)).years, u16::MAX);
+        assert_eq!(user.exact_age_at(date(2019, 6, 26)).months, 5);
+
+        let user = User::with_birthdate(2019 - 70000, 1, 1).unwrap();
+        assert_eq!(user.age_at(date(2019, 6, 26)), u16::MAX);
+        assert_eq!(user.exact_age_at(date(2019, 6, 26)).years, u16::MAX);
+    }
+
+    #[test]
+    fn counts_age_at_any_date() {
+        let user = User::with_birthdate(2001, 6, 26).unwrap();
+
+        assert_eq!(user.age_at(date(2019, 6, 25)), 17);
+        assert_eq!(user.age_at(date(2019, 6, 26)), 18);
+        assert_eq!(user.age_at(date(2001, 6, 26)), 0);
+        assert_eq!(user.age_at(date(2000, 1, 1)), 0);
+    }
+
+    #[test]
+    fn counts_birthday_in_own_time_zone() {
+        let user = User::with_birthdate(2001, 6, 27).unwrap();
+        // Already June 27 in Tokyo, while still June 26 in UTC and New York.
+        let clock = FixedClock("2019-06-26T20:00:00Z".parse::<DateTime<Utc>>().unwrap());
+
+        assert_eq!(user.age(&clock), 17);
+        assert_eq!(user.in_time_zone(chrono_tz::Asia::Tokyo).age(&clock), 18);
+
+        let user = User::with_birthdate(2001, 6, 26)
+            .unwrap()
+            .in_time_zone(chrono_tz::America::New_York);
+        let clock = FixedClock("2019-06-26T02:00:00Z".parse().unwrap());
+        assert_eq!(user.age(&clock), 17);
+    }
+
+    #[test]
+    fn applies_leap_day_policy_in_common_years() {
+        let feb28 = User::with_birthdate(2000, 2, 29)
+            .unwrap()
+            .with_leap_day_policy(LeapDayPolicy::Feb28);
+        let mar1 = User::with_birthdate(2000, 2, 29).unwrap();
+
+        for (at, feb28_age, mar1_age) in [
+            (date(2019, 2, 27), 18, 18),
+            (date(2019, 2, 28), 19, 18),
+            (date(2019, 3, 1), 19, 19),
+            (date(2020, 2, 28), 19, 19),
+            (date(2020, 2, 29), 20, 20),
+        ] {
+            assert_eq!(feb28.age_at(at), feb28_age, "{at}");
+            assert_eq!(mar1.age_at(at), mar1_age, "{at}");
         }
     }
+
+    #[test]
+    fn counts_exact_age() {
+        let exact = |birthdate: NaiveDate, at: NaiveDate| {
+            let user = User::with_birthdate(birthdate.year(), birthdate.month(), birthdate.day());
+            let age = user.unwrap().exact_age_at(at);
+            (age.years, age.months, age.days)
+        };
+
+        assert_eq!(exact(date(1990, 6, 4), date(2019, 6, 26)), (29, 0, 22));
+        assert_eq!(exact(date(1990, 7, 4), date(2019, 6, 26)), (28, 11, 22));
+        assert_eq!(exact(date(2019, 6, 26), date(2019, 6, 26)), (0, 0, 0));
+        assert_eq!(exact(date(2019, 1, 31), date(2019, 2, 28)), (0, 1, 0));
+        assert_eq!(exact(date(2019, 1, 31), date(2019, 3, 30)), (0, 1, 30));
+        assert_eq!(exact(date(2019, 1, 31), date(2019, 3, 31)), (0, 2, 0));
+        assert_eq!(exact(date(2032, 6, 25), date(2019, 6, 26)), (0, 0, 0));
+
+        let user = User::with_birthdate(2001, 6, 27).unwrap();
+        assert_eq!(
+            user.exact_age(&now()),
+            ExactAge {
+                years: 17,
+                months: 11,
+                days: 30,
+            },
+        );
+    }
+
+    #[test]
+    fn counts_exact_age_of_leap_day_birthdate() {
+        let mar1 = User::with_birthdate(2000, 2, 29).unwrap();
+        let feb28 = User::with_birthdate(2000, 2, 29)
+            .unwrap()
+            .with_leap_day_policy(LeapDayPolicy::Feb28);
+        let exact = |user: &User, at| {
+            let age = user.exact_age_at(at);
+            (age.years, age.months, age.days)
+        };
+
+        assert_eq!(exact(&mar1, date(2019, 2, 28)), (18, 11, 30));
+        assert_eq!(exact(&mar1, date(2019, 3, 1)), (19, 0, 0));
+        assert_eq!(exact(&mar1, date(2019, 3, 29)), (19, 1, 0));
+        assert_eq!(exact(&feb28, date(2019, 2, 28)), (19, 0, 0));
+        assert_eq!(exact(&feb28, date(2019, 3, 1)), (19, 0, 1));
+    }
 }
@@ -0,0 +1,105 @@
+//! Peak memory of optimizing growing numbers of images, which is expected to stay bounded by the concurrency limits.
+//!
+//! Run with `cargo bench --bench peak_memory`. Linux only, as the peak memory is reported in other units or not at all
+//! elsewhere.
+
+#[cfg(target_os = "linux")]
+fn main() {
+    linux::main();
+}
+
+#[cfg(not(target_os = "linux"))]
+fn main() {
+    eprintln!("peak memory is measured on Linux only, so the benchmark is skipped");
+}
+
+#[cfg(target_os = "linux")]
+mod linux {
+    use std::path::Path;
+    use std::process::{Command, Stdio};
+    use std::time::Instant;
+
+    use image::{ImageFormat, RgbImage};
+
+    /// Side of the generated images, so every decoded one takes about 3 MiB.
+    const SIDE: u32 = 1024;
+    /// Numbers of the optimized images, from the one fitting the concurrency limits to the one far exceeding them.
+    const COUNTS: [usize; 3] = [4, 32, 128];
+    const MAX_THREADS: &str = "2";
+    const MAX_IN_FLIGHT: &str = "4";
+    /// How much more the peak memory may grow along with the number of images.
+    const TOLERANCE: f64 = 2.0;
+
+    /// Writes `count` noisy images, which don't compress well, to the `dir`.
+    fn generate(dir: &Path, count: usize) {
+        for i in 0..count {
+            let mut seed = i as u32 + 1;
+            let img = RgbImage::from_fn(SIDE, SIDE, |_, _| {
+                // Xorshift, so the images differ without any extra dependency.
+                seed ^= seed << 13;
+                seed ^= seed >> 17;
+                seed ^= seed << 5;
+                let [r, g, b, _] = seed.to_le_bytes();
+                image::Rgb([r, g, b])
+            });
+            img.save_with_format(dir.join(format!("{i}.png")), ImageFormat::Png)
+                .unwrap();
+        }
+    }
+
+    /// Optimizes the images in the `dir`, returning the peak resident memory of the optimizer, in KiB.
+    fn peak_memory_kib(dir: &Path) -> i64 {
+        let pid = Command::new(env!("CARGO_BIN_EXE_step_3"))
+            .args(["--images", "in", "--output-dir", "out", "--force"])
+            .args(["--output-format", "jpeg", "--quality", "80"])
+            .args([
+                "--max-threads",
+                MAX_THREADS,
+                "--max-in-flight",
+                MAX_IN_FLIGHT,
+            ])
+            .current_dir(dir)
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+            .unwrap()
+            .id() as libc::pid_t;
+
+        // Unlike `Child::wait`, reports the resources used by the child.
+        let mut status = 0;
+        let mut usage = unsafe { std::mem::zeroed::<libc::rusage>() };
+        let waited = unsafe { libc::wait4(pid, &mut status, 0, &mut usage) };
+        assert_eq!(waited, pid, "failed to wait for the optimizer");
+        assert!(
+            libc::WIFEXITED(status) && libc::WEXITSTATUS(status) == 0,
+            "optimizer has failed"
+        );
+        usage.ru_maxrss
+    }
+
+    pub fn main() {
+        println!("{:>8}  {:>12}  {:>8}", "IMAGES", "PEAK MEMORY", "TIME");
+
+        let peaks = COUNTS.map(|count| {
+            let dir = tempfile::tempdir().unwrap();
+            std::fs::create_dir(dir.path().join("in")).unwrap();
+            generate(&dir.path().join("in"), count);
+
+            let start = Instant::now();
+            let peak = peak_memory_kib(dir.path());
+            println!(
+                "{count:>8}  {:>8.1} MiB  {:>7.2}s",
+                peak as f64 / 1024.0,
+                start.elapsed().as_secs_f64()
+            );
+            peak
+        });
+
+        let (first, last) = (peaks[0] as f64, peaks[peaks.len() - 1] as f64);
+        assert!(
+            last <= first * TOLERANCE,
+            "peak memory grows with the number of images: {first} KiB -> {last} KiB"
+        );
+    }
+}
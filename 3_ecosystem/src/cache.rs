@@ -0,0 +1,282 @@
+//! Manifest of the already optimized images, allowing to skip the unchanged ones on subsequent runs.
+
+use std::collections::{BTreeMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, PoisonError};
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+
+/// Name of the manifest file in the output directory.
+pub const MANIFEST_FILE: &str = ".optimizer-cache.json";
+
+/// Version of the manifest format, invalidating the manifests of any other one.
+const MANIFEST_VERSION: u32 = 1;
+
+/// A cached optimized image.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Entry {
+    /// Hash of the source image along with the settings it was optimized with.
+    pub key: String,
+    /// Path of the optimized image relative to the output directory.
+    pub output: PathBuf,
+    /// Local file the image is optimized from, or [`None`] for a remote one, which never counts as disappeared.
+    pub source: Option<PathBuf>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+struct Manifest {
+    version: u32,
+    /// Entries by the outputs of the inputs they're optimized from.
+    entries: BTreeMap<PathBuf, Entry>,
+}
+
+impl Default for Manifest {
+    fn default() -> Self {
+        Self {
+            version: MANIFEST_VERSION,
+            entries: BTreeMap::new(),
+        }
+    }
+}
+
+/// Cache of the optimized images in an output directory.
+///
+/// An image is considered unchanged, if neither its content nor the settings it's optimized with have changed since
+/// the previous run, and its optimized version is still in place.
+#[derive(Debug)]
+pub struct Cache {
+    output_dir: PathBuf,
+    /// Serialized settings, so changing any of them invalidates the whole cache.
+    settings: Vec<u8>,
+    manifest: Mutex<Manifest>,
+}
+
+impl Cache {
+    /// Loads the manifest from the `output_dir`, starting from the empty one if it's missing or malformed.
+    pub fn load(output_dir: impl Into<PathBuf>, settings: &impl Serialize) -> Result<Self> {
+        let output_dir = output_dir.into();
+        let manifest = match fs::read(output_dir.join(MANIFEST_FILE)) {
+            Ok(bytes) => serde_json::from_slice::<Manifest>(&bytes)
+                .ok()
+                .filter(|m| m.version == MANIFEST_VERSION)
+                .unwrap_or_else(|| {
+                    warn!(
+                        "cache manifest in {:?} is invalid, so it's discarded",
+                        output_dir
+                    );
+                    Manifest::default()
+                }),
+            Err(_) => Manifest::default(),
+        };
+
+        Ok(Self {
+            output_dir,
+            settings: serde_json::to_vec(settings)?,
+            manifest: Mutex::new(manifest),
+        })
+    }
+
+    /// Key of the image with the given content in the cache.
+    pub fn key(&self, bytes: &[u8]) -> String {
+        let mut hasher = blake3::Hasher::new();
+        hasher.update(&(self.settings.len() as u64).to_le_bytes());
+        hasher.update(&self.settings);
+        hasher.update(bytes);
+        hasher.finalize().to_hex().to_string()
+    }
+
+    /// Returns the cached optimized image of the input with the given `output`, if it's up to date with the `key`.
+    pub fn get(&self, output: &Path, key: &str) -> Option<Entry> {
+        self.lock()
+            .entries
+            .get(output)
+            .filter(|e| e.key == key && self.output_dir.join(&e.output).is_file())
+            .cloned()
+    }
+
+    /// Records the optimized image of the input with the given `output`.
+    ///
+    /// The previously optimized image of the same input is removed, if it was written to another path.
+    pub fn insert(&self, output: PathBuf, entry: Entry) -> Result<()> {
+        let previous = self.lock().entries.insert(output, entry.clone());
+        if let Some(previous) = previous.filter(|p| p.output != entry.output) {
+            remove_output(&self.output_dir.join(previous.output))?;
+        }
+        Ok(())
+    }
+
+    /// Returns the outputs of the inputs of the cached images, along with their sources.
+    pub fn sources(&self) -> Vec<(PathBuf, Option<PathBuf>)> {
+        self.lock()
+            .entries
+            .iter()
+            .map(|(output, entry)| (output.clone(), entry.source.clone()))
+            .collect()
+    }
+
+    /// Removes the optimized images, whose source files disappeared, unless they're among the `current` inputs.
+    ///
+    /// Returns the number of the removed images.
+    pub fn prune<'a>(&self, current: impl IntoIterator<Item = &'a Path>) -> Result<usize> {
+        let current = current.into_iter().collect::<HashSet<_>>();
+
+        let mut manifest = self.lock();
+        let stale = manifest
+            .entries
+            .iter()
+            .filter(|(output, entry)| {
+                !current.contains(output.as_path())
+                    && entry.source.as_ref().is_some_and(|s| !s.exists())
+            })
+            .map(|(output, _)| output.clone())
+            .collect::<Vec<_>>();
+        for output in &stale {
+            if let Some(entry) = manifest.entries.remove(output) {
+                let path = self.output_dir.join(entry.output);
+                info!("removing stale {:?}", path);
+                remove_output(&path)?;
+            }
+        }
+        Ok(stale.len())
+    }
+
+    /// Writes the manifest to the output directory.
+    pub fn save(&self) -> Result<()> {
+        // Kept locked until the manifest is written, so concurrent saves don't interfere.
+        let manifest = self.lock();
+        let json = serde_json::to_vec_pretty(&*manifest)?;
+        // Replacing the manifest at once, so it's never left partially written.
+        let tmp = self.output_dir.join(format!("{MANIFEST_FILE}.tmp"));
+        fs::write(&tmp, json)?;
+        fs::rename(tmp, self.output_dir.join(MANIFEST_FILE))?;
+        Ok(())
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Manifest> {
+        self.manifest.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+fn remove_output(path: &Path) -> Result<()> {
+    match fs::remove_file(path) {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod spec {
+    use tempfile::TempDir;
+
+    use super::*;
+
+    fn cache(dir: &TempDir, settings: &str) -> Cache {
+        Cache::load(dir.path(), &settings).unwrap()
+    }
+
+    fn write(dir: &TempDir, file: &str) -> Entry {
+        fs::write(dir.path().join(file), b"optimized").unwrap();
+        Entry {
+            key: String::new(),
+            output: file.into(),
+            source: None,
+        }
+    }
+
+    fn with_source(entry: Entry, source: &Path) -> Entry {
+        Entry {
+            source: Some(source.to_path_buf()),
+            ..entry
+        }
+    }
+
+    #[test]
+    fn finds_unchanged_images_across_runs() {
+        let dir = tempfile::tempdir().unwrap();
+
+        let first = cache(&dir, "q=75");
+        let key = first.key(b"image");
+        first
+            .insert(
+                "a.jpg".into(),
+                Entry {
+                    key: key.clone(),
+                    ..write(&dir, "a.jpg")
+                },
+            )
+            .unwrap();
+        first.save().unwrap();
+
+        let second = cache(&dir, "q=75");
+        assert_eq!(second.key(b"image"), key);
+        assert!(second.get(Path::new("a.jpg"), &key).is_some());
+        assert!(second
+            .get(Path::new("a.jpg"), &second.key(b"changed"))
+            .is_none());
+        assert!(second.get(Path::new("b.jpg"), &key).is_none());
+
+        fs::remove_file(dir.path().join("a.jpg")).unwrap();
+        assert!(second.get(Path::new("a.jpg"), &key).is_none());
+    }
+
+    #[test]
+    fn is_invalidated_by_settings() {
+        let dir = tempfile::tempdir().unwrap();
+
+        assert_ne!(
+            cache(&dir, "q=75").key(b"image"),
+            cache(&dir, "q=80").key(b"image")
+        );
+    }
+
+    #[test]
+    fn discards_malformed_manifest() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join(MANIFEST_FILE), b"{").unwrap();
+
+        let cache = cache(&dir, "");
+
+        assert!(cache.lock().entries.is_empty());
+    }
+
+    #[test]
+    fn replaces_output_written_to_another_path() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = cache(&dir, "");
+
+        cache.insert("a.png".into(), write(&dir, "a.png")).unwrap();
+        cache.insert("a.png".into(), write(&dir, "a.jpg")).unwrap();
+
+        assert!(!dir.path().join("a.png").exists());
+        assert!(dir.path().join("a.jpg").exists());
+    }
+
+    #[test]
+    fn prunes_outputs_of_disappeared_sources() {
+        let dir = tempfile::tempdir().unwrap();
+        let sources = tempfile::tempdir().unwrap();
+        let (kept, removed) = (sources.path().join("kept"), sources.path().join("removed"));
+        fs::write(&kept, b"").unwrap();
+
+        let cache = cache(&dir, "");
+        let current = with_source(write(&dir, "a.png"), &removed);
+        cache.insert("a.png".into(), current).unwrap();
+        let gone = with_source(write(&dir, "b.jpg"), &removed);
+        cache.insert("b.png".into(), gone).unwrap();
+        let elsewhere = with_source(write(&dir, "c.png"), &kept);
+        cache.insert("c.png".into(), elsewhere).unwrap();
+        cache.insert("d.png".into(), write(&dir, "d.png")).unwrap();
+        write(&dir, "unrelated.png");
+
+        assert_eq!(cache.prune([Path::new("a.png")]).unwrap(), 1);
+
+        assert!(!dir.path().join("b.jpg").exists());
+        for file in ["a.png", "c.png", "d.png", "unrelated.png"] {
+            assert!(dir.path().join(file).exists(), "{file}");
+        }
+        assert_eq!(cache.lock().entries.len(), 3);
+    }
+}
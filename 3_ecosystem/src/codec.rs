@@ -0,0 +1,485 @@
+//! Decoding and re-encoding of the supported image formats.
+
+use std::collections::HashMap;
+use std::io::{Cursor, Write};
+
+use anyhow::{bail, Result};
+use clap::ValueEnum;
+use image::codecs::gif::{GifDecoder, GifEncoder, Repeat};
+use image::codecs::jpeg::JpegEncoder;
+use image::codecs::png::{CompressionType, FilterType, PngEncoder};
+use image::codecs::webp::WebPEncoder;
+use image::io::Reader as ImageReader;
+use image::{AnimationDecoder, ColorType, DynamicImage, Frame, ImageEncoder, ImageFormat};
+use serde::{Deserialize, Serialize};
+
+pub type Quality = u8;
+
+pub const DEFAULT_QUALITY: Quality = 75;
+
+/// Format the optimized images are written in.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputFormat {
+    Jpeg,
+    Png,
+    /// Always encoded losslessly, as lossy encoding requires `libwebp`.
+    #[value(name = "webp")]
+    WebP,
+    Gif,
+}
+
+impl OutputFormat {
+    pub fn extension(self) -> &'static str {
+        match self {
+            OutputFormat::Jpeg => "jpg",
+            OutputFormat::Png => "png",
+            OutputFormat::WebP => "webp",
+            OutputFormat::Gif => "gif",
+        }
+    }
+
+    fn from_image_format(format: ImageFormat) -> Option<Self> {
+        Some(match format {
+            ImageFormat::Jpeg => OutputFormat::Jpeg,
+            ImageFormat::Png => OutputFormat::Png,
+            ImageFormat::WebP => OutputFormat::WebP,
+            ImageFormat::Gif => OutputFormat::Gif,
+            _ => return None,
+        })
+    }
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, Serialize)]
+pub struct JpegSettings {
+    pub quality: Quality,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum PngCompression {
+    Fast,
+    Default,
+    Best,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, Serialize)]
+pub struct PngSettings {
+    pub compression: PngCompression,
+    /// Whether to losslessly reduce images to a palette, grayscale or no alpha, whenever possible.
+    pub reduce_palette: bool,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, Serialize)]
+pub struct GifSettings {
+    /// Speed of the color quantization in `1..=30`, trading its quality for speed.
+    pub speed: u8,
+}
+
+/// How the images are encoded.
+#[derive(Clone, Copy, Debug, Serialize)]
+pub struct EncodeSettings {
+    /// Format to transcode the images to, or [`None`] to keep their original format.
+    pub output_format: Option<OutputFormat>,
+    pub jpeg: JpegSettings,
+    pub png: PngSettings,
+    pub gif: GifSettings,
+}
+
+/// A decoded image, along with the format it's going to be encoded in.
+pub enum Image {
+    Jpeg(DynamicImage),
+    Png(DynamicImage),
+    WebP(DynamicImage),
+    /// All the frames of a possibly animated GIF.
+    Gif(Vec<Frame>),
+}
+
+impl Image {
+    /// Decodes the image, so it can be encoded in the `output_format`, or the original format if it's [`None`].
+    pub fn decode(bytes: &[u8], output_format: Option<OutputFormat>) -> Result<Self> {
+        let reader = ImageReader::new(Cursor::new(bytes)).with_guessed_format()?;
+        let Some(format) = reader.format() else {
+            bail!("Unknown image format");
+        };
+        let Some(output_format) = output_format.or_else(|| OutputFormat::from_image_format(format))
+        else {
+            bail!("Unsupported image format: {:?}", format);
+        };
+
+        if format == ImageFormat::Gif && output_format == OutputFormat::Gif {
+            let frames = GifDecoder::new(Cursor::new(bytes))?
+                .into_frames()
+                .collect_frames()?;
+            return Ok(Image::Gif(frames));
+        }
+
+        let img = reader.decode()?;
+        Ok(match output_format {
+            OutputFormat::Jpeg => Image::Jpeg(img),
+            OutputFormat::Png => Image::Png(img),
+            OutputFormat::WebP => Image::WebP(img),
+            OutputFormat::Gif => Image::Gif(vec![Frame::new(img.into_rgba8())]),
+        })
+    }
+
+    /// Transforms the image, or every frame of an animated one.
+    pub fn map(self, f: impl Fn(DynamicImage) -> DynamicImage) -> Self {
+        match self {
+            Image::Jpeg(img) => Image::Jpeg(f(img)),
+            Image::Png(img) => Image::Png(f(img)),
+            Image::WebP(img) => Image::WebP(f(img)),
+            Image::Gif(frames) => Image::Gif(
+                frames
+                    .into_iter()
+                    .map(|frame| {
+                        let (left, top, delay) = (frame.left(), frame.top(), frame.delay());
+                        let buffer = f(DynamicImage::ImageRgba8(frame.into_buffer())).into_rgba8();
+                        Frame::from_parts(buffer, left, top, delay)
+                    })
+                    .collect(),
+            ),
+        }
+    }
+
+    pub fn format(&self) -> OutputFormat {
+        match self {
+            Image::Jpeg(_) => OutputFormat::Jpeg,
+            Image::Png(_) => OutputFormat::Png,
+            Image::WebP(_) => OutputFormat::WebP,
+            Image::Gif(_) => OutputFormat::Gif,
+        }
+    }
+
+    pub fn encode(&self, settings: &EncodeSettings, writer: impl Write) -> Result<()> {
+        match self {
+            Image::Jpeg(img) => {
+                let mut encoder = JpegEncoder::new_with_quality(writer, settings.jpeg.quality);
+                // JPEG has no alpha channel.
+                encoder.encode_image(&DynamicImage::ImageRgb8(img.to_rgb8()))?;
+            }
+            Image::Png(img) => encode_png(img, &settings.png, writer)?,
+            Image::WebP(img) => {
+                let img = if img.color().has_alpha() {
+                    DynamicImage::ImageRgba8(img.to_rgba8())
+                } else {
+                    DynamicImage::ImageRgb8(img.to_rgb8())
+                };
+                WebPEncoder::new_lossless(writer).encode(
+                    img.as_bytes(),
+                    img.width(),
+                    img.height(),
+                    img.color(),
+                )?;
+            }
+            Image::Gif(frames) => {
+                let mut encoder =
+                    GifEncoder::new_with_speed(writer, settings.gif.speed.clamp(1, 30).into());
+                if frames.len() > 1 {
+                    encoder.set_repeat(Repeat::Infinite)?;
+                }
+                encoder.encode_frames(frames.iter().cloned())?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn encode_png(img: &DynamicImage, settings: &PngSettings, writer: impl Write) -> Result<()> {
+    let is_8bit = matches!(
+        img.color(),
+        ColorType::L8 | ColorType::La8 | ColorType::Rgb8 | ColorType::Rgba8
+    );
+    if !settings.reduce_palette || !is_8bit {
+        let compression = match settings.compression {
+            PngCompression::Fast => CompressionType::Fast,
+            PngCompression::Default => CompressionType::Default,
+            PngCompression::Best => CompressionType::Best,
+        };
+        PngEncoder::new_with_quality(writer, compression, FilterType::Adaptive).write_image(
+            img.as_bytes(),
+            img.width(),
+            img.height(),
+            img.color(),
+        )?;
+        return Ok(());
+    }
+
+    let reduced = Reduced::from(img);
+    let mut encoder = png::Encoder::new(writer, img.width(), img.height());
+    encoder.set_compression(match settings.compression {
+        PngCompression::Fast => png::Compression::Fast,
+        PngCompression::Default => png::Compression::Default,
+        PngCompression::Best => png::Compression::Best,
+    });
+    let data = match reduced {
+        Reduced::Indexed {
+            palette,
+            trns,
+            depth,
+            data,
+        } => {
+            encoder.set_color(png::ColorType::Indexed);
+            encoder.set_depth(depth);
+            encoder.set_palette(palette);
+            if !trns.is_empty() {
+                encoder.set_trns(trns);
+            }
+            // Filtering rarely helps indexed images.
+            encoder.set_filter(png::FilterType::NoFilter);
+            data
+        }
+        Reduced::Direct { color, data } => {
+            encoder.set_color(color);
+            encoder.set_depth(png::BitDepth::Eight);
+            encoder.set_adaptive_filter(png::AdaptiveFilterType::Adaptive);
+            data
+        }
+    };
+    encoder.write_header()?.write_image_data(&data)?;
+    Ok(())
+}
+
+/// The most compact lossless representation of an 8-bit image in PNG.
+#[derive(Debug, PartialEq)]
+enum Reduced {
+    Indexed {
+        palette: Vec<u8>,
+        trns: Vec<u8>,
+        depth: png::BitDepth,
+        data: Vec<u8>,
+    },
+    Direct {
+        color: png::ColorType,
+        data: Vec<u8>,
+    },
+}
+
+impl From<&DynamicImage> for Reduced {
+    fn from(img: &DynamicImage) -> Self {
+        let rgba = img.to_rgba8();
+        let pixels = rgba.pixels().map(|p| p.0);
+
+        let opaque = rgba.pixels().all(|p| p.0[3] == u8::MAX);
+        let gray = rgba.pixels().all(|p| p.0[0] == p.0[1] && p.0[1] == p.0[2]);
+
+        let mut colors = HashMap::<[u8; 4], u8>::new();
+        for px in pixels.clone() {
+            if colors.len() > 256 {
+                break;
+            }
+            let next = colors.len() as u8;
+            colors.entry(px).or_insert(next);
+        }
+
+        // A grayscale pixel takes a byte, so a palette pays off only when it allows a lower bit depth.
+        let use_palette = colors.len() <= 16 || (!gray && colors.len() <= 256);
+        if use_palette {
+            // Translucent colors go first, so the `tRNS` chunk is as short as possible.
+            let mut entries = colors.keys().copied().collect::<Vec<_>>();
+            entries.sort_by_key(|c| (c[3] == u8::MAX, *c));
+            let index = entries
+                .iter()
+                .enumerate()
+                .map(|(i, c)| (*c, i as u8))
+                .collect::<HashMap<_, _>>();
+
+            let (depth, bits) = match entries.len() {
+                0..=2 => (png::BitDepth::One, 1),
+                3..=4 => (png::BitDepth::Two, 2),
+                5..=16 => (png::BitDepth::Four, 4),
+                _ => (png::BitDepth::Eight, 8),
+            };
+            let per_byte = 8 / bits;
+            let width = rgba.width() as usize;
+            let row_len = width.div_ceil(per_byte);
+
+            let mut data = vec![0; row_len * rgba.height() as usize];
+            for (y, row) in rgba.rows().enumerate() {
+                for (x, px) in row.enumerate() {
+                    let shift = 8 - bits * (x % per_byte + 1);
+                    data[y * row_len + x / per_byte] |= index[&px.0] << shift;
+                }
+            }
+
+            return Reduced::Indexed {
+                palette: entries.iter().flat_map(|c| [c[0], c[1], c[2]]).collect(),
+                trns: entries
+                    .iter()
+                    .map(|c| c[3])
+                    .take_while(|a| *a < u8::MAX)
+                    .collect(),
+                depth,
+                data,
+            };
+        }
+
+        let (color, data) = match (gray, opaque) {
+            (true, true) => (png::ColorType::Grayscale, pixels.map(|p| p[0]).collect()),
+            (true, false) => (
+                png::ColorType::GrayscaleAlpha,
+                pixels.flat_map(|p| [p[0], p[3]]).collect(),
+            ),
+            (false, true) => (
+                png::ColorType::Rgb,
+                pixels.flat_map(|p| [p[0], p[1], p[2]]).collect(),
+            ),
+            (false, false) => (png::ColorType::Rgba, rgba.into_raw()),
+        };
+        Reduced::Direct { color, data }
+    }
+}
+
+#[cfg(test)]
+mod spec {
+    use image::{Rgba, RgbaImage};
+
+    use super::*;
+
+    const SETTINGS: EncodeSettings = EncodeSettings {
+        output_format: None,
+        jpeg: JpegSettings {
+            quality: DEFAULT_QUALITY,
+        },
+        png: PngSettings {
+            compression: PngCompression::Best,
+            reduce_palette: true,
+        },
+        gif: GifSettings { speed: 10 },
+    };
+
+    fn gradient(width: u32, height: u32) -> DynamicImage {
+        DynamicImage::ImageRgba8(RgbaImage::from_fn(width, height, |x, y| {
+            Rgba([(x * 7) as u8, (y * 5) as u8, (x + y) as u8, 255])
+        }))
+    }
+
+    fn encode(img: &Image, settings: &EncodeSettings) -> Vec<u8> {
+        let mut out = Vec::new();
+        img.encode(settings, &mut out).unwrap();
+        out
+    }
+
+    fn encode_as(img: DynamicImage, format: ImageFormat) -> Vec<u8> {
+        let mut out = Cursor::new(Vec::new());
+        img.write_to(&mut out, format).unwrap();
+        out.into_inner()
+    }
+
+    #[test]
+    fn keeps_supported_formats_by_default() {
+        let img = gradient(8, 8);
+        for (format, expected) in [
+            (ImageFormat::Jpeg, OutputFormat::Jpeg),
+            (ImageFormat::Png, OutputFormat::Png),
+            (ImageFormat::Gif, OutputFormat::Gif),
+        ] {
+            let bytes = encode_as(img.clone(), format);
+
+            let decoded = Image::decode(&bytes, None).unwrap();
+
+            assert_eq!(decoded.format(), expected);
+            let reencoded = encode(&decoded, &SETTINGS);
+            assert_eq!(image::guess_format(&reencoded).unwrap(), format);
+        }
+    }
+
+    #[test]
+    fn rejects_unsupported_formats_unless_transcoding() {
+        let bytes = encode_as(gradient(4, 4), ImageFormat::Bmp);
+
+        assert!(Image::decode(&bytes, None).is_err());
+
+        let decoded = Image::decode(&bytes, Some(OutputFormat::WebP)).unwrap();
+        let reencoded = encode(&decoded, &SETTINGS);
+        assert_eq!(image::guess_format(&reencoded).unwrap(), ImageFormat::WebP);
+    }
+
+    #[test]
+    fn transcodes_png_to_webp_losslessly() {
+        let img = gradient(16, 16);
+        let bytes = encode_as(img.clone(), ImageFormat::Png);
+
+        let decoded = Image::decode(&bytes, Some(OutputFormat::WebP)).unwrap();
+        let webp = encode(&decoded, &SETTINGS);
+
+        let roundtrip = image::load_from_memory(&webp).unwrap();
+        assert_eq!(roundtrip.to_rgba8(), img.to_rgba8());
+    }
+
+    #[test]
+    fn keeps_gif_animation_frames() {
+        let frames = (0..3)
+            .map(|i| Frame::new(RgbaImage::from_pixel(4, 4, Rgba([i * 80, 0, 0, 255]))))
+            .collect::<Vec<_>>();
+        let mut bytes = Vec::new();
+        GifEncoder::new(&mut bytes).encode_frames(frames).unwrap();
+
+        let Image::Gif(decoded) = Image::decode(&bytes, None).unwrap() else {
+            panic!("expected GIF");
+        };
+
+        assert_eq!(decoded.len(), 3);
+    }
+
+    #[test]
+    fn reduces_png_to_palette_losslessly() {
+        let img = DynamicImage::ImageRgba8(RgbaImage::from_fn(32, 32, |x, y| match (x + y) % 3 {
+            0 => Rgba([255, 0, 0, 255]),
+            1 => Rgba([0, 0, 255, 128]),
+            _ => Rgba([0, 0, 0, 0]),
+        }));
+
+        let Reduced::Indexed {
+            palette,
+            trns,
+            depth,
+            ..
+        } = Reduced::from(&img)
+        else {
+            panic!("expected palette");
+        };
+        assert_eq!(palette.len(), 3 * 3);
+        assert_eq!(trns, [0, 128]);
+        assert_eq!(depth, png::BitDepth::Two);
+
+        let reduced = encode(&Image::Png(img.clone()), &SETTINGS);
+        let plain = encode(
+            &Image::Png(img.clone()),
+            &EncodeSettings {
+                png: PngSettings {
+                    reduce_palette: false,
+                    ..SETTINGS.png
+                },
+                ..SETTINGS
+            },
+        );
+        assert!(reduced.len() < plain.len());
+        assert_eq!(
+            image::load_from_memory(&reduced).unwrap().to_rgba8(),
+            img.to_rgba8(),
+        );
+    }
+
+    #[test]
+    fn reduces_png_to_grayscale_losslessly() {
+        let img = DynamicImage::ImageRgba8(RgbaImage::from_fn(32, 32, |x, y| {
+            let v = (x * 8 + y) as u8;
+            Rgba([v, v, v, 255])
+        }));
+
+        assert!(matches!(
+            Reduced::from(&img),
+            Reduced::Direct {
+                color: png::ColorType::Grayscale,
+                ..
+            },
+        ));
+
+        let reduced = encode(&Image::Png(img.clone()), &SETTINGS);
+        assert_eq!(
+            image::load_from_memory(&reduced).unwrap().to_rgba8(),
+            img.to_rgba8(),
+        );
+    }
+}
@@ -0,0 +1,194 @@
+//! Downloading of remote images.
+
+use std::time::Duration;
+
+use anyhow::{anyhow, bail, Result};
+use image::ImageFormat;
+use reqwest::header::CONTENT_TYPE;
+use reqwest::{redirect, Client, StatusCode};
+use serde::Deserialize;
+use tracing::warn;
+use url::Url;
+
+/// Longest delay between retries, however many of them were made.
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct FetchSettings {
+    /// Timeout of a single attempt to download an image, in seconds.
+    pub timeout_secs: u64,
+    /// Largest size of a downloaded image, in bytes.
+    pub max_body_size: u64,
+    /// Number of attempts made after the first one has failed transiently.
+    pub retries: u32,
+    /// Delay before the first retry, in milliseconds, which doubles with every next one.
+    pub retry_backoff_ms: u64,
+    pub max_redirects: usize,
+}
+
+impl FetchSettings {
+    fn backoff(&self, retry: u32) -> Duration {
+        Duration::from_millis(self.retry_backoff_ms)
+            .saturating_mul(2u32.saturating_pow(retry))
+            .min(MAX_BACKOFF)
+    }
+}
+
+/// Downloader of remote images, sharing connections between them.
+#[derive(Debug)]
+pub struct Fetcher {
+    client: Client,
+    settings: FetchSettings,
+}
+
+/// Why an attempt to download an image has failed.
+enum Failure {
+    /// The next attempt may succeed, e.g. a timeout or a server error.
+    Transient(anyhow::Error),
+    Permanent(anyhow::Error),
+}
+
+impl From<reqwest::Error> for Failure {
+    fn from(e: reqwest::Error) -> Self {
+        if e.is_redirect() || e.is_builder() {
+            Failure::Permanent(e.into())
+        } else {
+            Failure::Transient(e.into())
+        }
+    }
+}
+
+impl Fetcher {
+    pub fn new(settings: FetchSettings) -> Result<Self> {
+        let client = Client::builder()
+            .timeout(Duration::from_secs(settings.timeout_secs))
+            // The limited policy counts the original request along with the redirects.
+            .redirect(redirect::Policy::limited(settings.max_redirects + 1))
+            .build()?;
+        Ok(Self { client, settings })
+    }
+
+    /// Downloads the image at the `url`, retrying transient failures with exponential backoff.
+    pub async fn fetch(&self, url: &Url) -> Result<Vec<u8>> {
+        let mut retry = 0;
+        loop {
+            match self.attempt(url).await {
+                Ok(body) => return Ok(body),
+                Err(Failure::Transient(e)) if retry < self.settings.retries => {
+                    let backoff = self.settings.backoff(retry);
+                    warn!("failed to download {url}, retrying in {backoff:?}: {e}");
+                    tokio::time::sleep(backoff).await;
+                    retry += 1;
+                }
+                Err(Failure::Transient(e) | Failure::Permanent(e)) => {
+                    return Err(e.context(format!("failed to download {url}")))
+                }
+            }
+        }
+    }
+
+    async fn attempt(&self, url: &Url) -> Result<Vec<u8>, Failure> {
+        let mut response = self.client.get(url.clone()).send().await?;
+
+        let status = response.status();
+        if status.is_server_error() || status == StatusCode::TOO_MANY_REQUESTS {
+            return Err(Failure::Transient(anyhow!("responded with {status}")));
+        }
+        if !status.is_success() {
+            return Err(Failure::Permanent(anyhow!("responded with {status}")));
+        }
+
+        let max = self.settings.max_body_size;
+        let too_large = || Failure::Permanent(anyhow!("image is larger than {max} bytes"));
+        if response.content_length().is_some_and(|len| len > max) {
+            return Err(too_large());
+        }
+
+        let declared = response
+            .headers()
+            .get(CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .map(str::to_owned);
+
+        // The declared length may be absent or wrong, so the limit is checked while reading as well.
+        let mut body = Vec::new();
+        while let Some(chunk) = response.chunk().await? {
+            if (body.len() + chunk.len()) as u64 > max {
+                return Err(too_large());
+            }
+            body.extend_from_slice(&chunk);
+        }
+
+        check_content_type(declared.as_deref(), &body).map_err(Failure::Permanent)?;
+        Ok(body)
+    }
+}
+
+/// Checks that the `body` is an image, regardless of the `declared` content type.
+///
+/// Servers often declare a wrong image type, so it's only warned about, as long as the content is an image.
+fn check_content_type(declared: Option<&str>, body: &[u8]) -> Result<ImageFormat> {
+    let essence = declared.map(|d| {
+        d.split(';')
+            .next()
+            .unwrap_or_default()
+            .trim()
+            .to_lowercase()
+    });
+
+    let Ok(sniffed) = image::guess_format(body) else {
+        bail!(
+            "content of type {} is not an image",
+            essence.as_deref().unwrap_or("unknown")
+        );
+    };
+
+    match essence.as_deref().map(ImageFormat::from_mime_type) {
+        Some(Some(format)) if format == sniffed => {}
+        Some(_) => warn!(
+            "declared content type {} doesn't match the {sniffed:?} image",
+            essence.unwrap_or_default()
+        ),
+        None => {}
+    }
+    Ok(sniffed)
+}
+
+#[cfg(test)]
+mod spec {
+    use super::*;
+
+    const PNG: &[u8] = b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR";
+
+    #[test]
+    fn sniffs_content_type() {
+        assert_eq!(
+            check_content_type(Some("image/png"), PNG).unwrap(),
+            ImageFormat::Png
+        );
+        assert_eq!(
+            check_content_type(Some("Image/JPEG; charset=binary"), PNG).unwrap(),
+            ImageFormat::Png,
+        );
+        assert_eq!(check_content_type(None, PNG).unwrap(), ImageFormat::Png);
+
+        let err = check_content_type(Some("text/html; charset=utf-8"), b"<html>").unwrap_err();
+        assert_eq!(err.to_string(), "content of type text/html is not an image");
+        assert!(check_content_type(None, b"").is_err());
+    }
+
+    #[test]
+    fn doubles_backoff_up_to_limit() {
+        let settings = FetchSettings {
+            timeout_secs: 1,
+            max_body_size: 1,
+            retries: 100,
+            retry_backoff_ms: 100,
+            max_redirects: 0,
+        };
+
+        assert_eq!(settings.backoff(0), Duration::from_millis(100));
+        assert_eq!(settings.backoff(3), Duration::from_millis(800));
+        assert_eq!(settings.backoff(50), MAX_BACKOFF);
+    }
+}
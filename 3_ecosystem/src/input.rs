@@ -0,0 +1,589 @@
+//! Resolving of the given image paths into the images to optimize, along with the paths to write them to.
+
+use std::collections::{HashMap, HashSet};
+use std::ffi::OsString;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Context, Result};
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use image::ImageFormat;
+use serde::Deserialize;
+use tracing::warn;
+use url::Url;
+use walkdir::WalkDir;
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct InputSettings {
+    /// Whether to look for images in the subdirectories of the given directories.
+    pub recursive: bool,
+    /// Glob patterns the images found in directories or by glob patterns must match, if any.
+    pub include: Vec<String>,
+    /// Glob patterns excluding the images found in directories or by glob patterns.
+    pub exclude: Vec<String>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ImageSource {
+    LocalFile(PathBuf),
+    RemoteUrl(Url),
+}
+
+impl fmt::Display for ImageSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ImageSource::LocalFile(path) => write!(f, "{}", path.display()),
+            ImageSource::RemoteUrl(url) => write!(f, "{url}"),
+        }
+    }
+}
+
+/// An image to optimize.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Input {
+    pub source: ImageSource,
+    /// Path of the optimized image relative to the output directory, whose extension is replaced with the one of the
+    /// output format.
+    pub output: PathBuf,
+}
+
+/// Images the given paths are resolved into, along with the paths failed to be resolved.
+#[derive(Debug, Default)]
+pub struct Resolved {
+    pub inputs: Vec<Input>,
+    /// Paths, which are neither existing files or directories, nor glob patterns or URLs, along with their errors.
+    pub failed: Vec<(String, anyhow::Error)>,
+}
+
+/// Resolves the `paths` into the images to optimize.
+///
+/// A path may be a file, a directory, a glob pattern or a URL. Images found in a directory or by a glob pattern keep
+/// their directory structure relative to it, and only the ones with a known image extension, passing the include and
+/// exclude filters, are taken. Images resolved to the same output path are renamed with a numeric suffix, while the
+/// same file given more than once is optimized only once.
+///
+/// A path failed to be resolved doesn't stop the others, so it's reported along with the images instead.
+pub fn resolve(paths: &[String], settings: &InputSettings) -> Result<Resolved> {
+    let filter = Filter::new(settings)?;
+
+    let mut inputs = Vec::new();
+    let mut failed = Vec::new();
+    for path in paths {
+        if let Err(e) = resolve_path(path, settings.recursive, &filter, &mut inputs) {
+            failed.push((path.clone(), e));
+        }
+    }
+
+    let mut seen = HashSet::new();
+    inputs.retain(|input| canonical_source(&input.source).is_none_or(|s| seen.insert(s)));
+    deduplicate_outputs(&mut inputs);
+
+    Ok(Resolved { inputs, failed })
+}
+
+/// Resolves the images at the `path` found in the watched directory `root`, unless they're filtered out.
+///
+/// Removed files are skipped, while the `path` may be a directory appeared along with its content, which is taken only
+/// when watching recursively.
+pub fn resolve_found(root: &Path, path: &Path, settings: &InputSettings) -> Result<Vec<Input>> {
+    let relative = path.strip_prefix(root)?;
+    if !settings.recursive && relative.components().count() > 1 {
+        return Ok(Vec::new());
+    }
+
+    let filter = Filter::new(settings)?;
+    let mut inputs = Vec::new();
+    if path.is_dir() {
+        if settings.recursive {
+            for entry in WalkDir::new(path).sort_by_file_name() {
+                let entry = entry?;
+                if entry.file_type().is_file() {
+                    let relative = entry.path().strip_prefix(root)?.to_path_buf();
+                    push_found(entry.into_path(), relative, &filter, &mut inputs);
+                }
+            }
+        }
+    } else if path.is_file() {
+        push_found(
+            path.to_path_buf(),
+            relative.to_path_buf(),
+            &filter,
+            &mut inputs,
+        );
+    }
+    Ok(inputs)
+}
+
+fn resolve_path(
+    path: &str,
+    recursive: bool,
+    filter: &Filter,
+    inputs: &mut Vec<Input>,
+) -> Result<()> {
+    let local = Path::new(path);
+
+    if local.is_file() {
+        let name = local
+            .file_name()
+            .with_context(|| format!("file_name of {:?} is None", local))?;
+        inputs.push(Input {
+            source: ImageSource::LocalFile(local.to_path_buf()),
+            output: PathBuf::from(name),
+        });
+        return Ok(());
+    }
+
+    if local.is_dir() {
+        let walker = WalkDir::new(local)
+            .max_depth(if recursive { usize::MAX } else { 1 })
+            .sort_by_file_name();
+        for entry in walker {
+            let entry = entry?;
+            if entry.file_type().is_file() {
+                let relative = entry.path().strip_prefix(local)?.to_path_buf();
+                push_found(entry.into_path(), relative, filter, inputs);
+            }
+        }
+        return Ok(());
+    }
+
+    if let Ok(url) = Url::parse(path) {
+        let name = sanitise_file_name::sanitise(url.as_str());
+        inputs.push(Input {
+            source: ImageSource::RemoteUrl(url),
+            output: PathBuf::from(name),
+        });
+        return Ok(());
+    }
+
+    if path.contains(['*', '?', '[', '{']) {
+        let base = glob_base(local);
+        let mut matches = glob::glob(path)
+            .with_context(|| format!("invalid glob pattern: {}", path))?
+            .collect::<Result<Vec<_>, _>>()?;
+        matches.sort();
+        for found in matches.into_iter().filter(|p| p.is_file()) {
+            let relative = found.strip_prefix(&base)?.to_path_buf();
+            push_found(found, relative, filter, inputs);
+        }
+        return Ok(());
+    }
+
+    bail!("Unknown image source of: {}", path);
+}
+
+/// Pushes the image found in a directory or by a glob pattern, unless it's filtered out.
+fn push_found(path: PathBuf, relative: PathBuf, filter: &Filter, inputs: &mut Vec<Input>) {
+    let is_image = ImageFormat::from_path(&path).is_ok_and(|f| f.reading_enabled());
+    if is_image && filter.allows(&relative) {
+        inputs.push(Input {
+            source: ImageSource::LocalFile(path),
+            output: relative,
+        });
+    }
+}
+
+/// The leading part of the glob `pattern` having no wildcards, which the matched paths are relative to.
+fn glob_base(pattern: &Path) -> PathBuf {
+    let mut base = PathBuf::new();
+    let mut components = pattern.components().peekable();
+    while let Some(component) = components.next() {
+        let is_wildcard = component
+            .as_os_str()
+            .to_str()
+            .is_some_and(|c| c.contains(['*', '?', '[', '{']));
+        // The last component without wildcards is a file name rather than a directory.
+        if is_wildcard || components.peek().is_none() {
+            break;
+        }
+        base.push(component);
+    }
+    base
+}
+
+/// Include and exclude filters of the found images.
+struct Filter {
+    include: Option<GlobSet>,
+    exclude: GlobSet,
+}
+
+impl Filter {
+    fn new(settings: &InputSettings) -> Result<Self> {
+        let include = if settings.include.is_empty() {
+            None
+        } else {
+            Some(glob_set(&settings.include)?)
+        };
+        Ok(Self {
+            include,
+            exclude: glob_set(&settings.exclude)?,
+        })
+    }
+
+    fn allows(&self, relative: &Path) -> bool {
+        self.include.as_ref().is_none_or(|i| i.is_match(relative))
+            && !self.exclude.is_match(relative)
+    }
+}
+
+fn glob_set(patterns: &[String]) -> Result<GlobSet> {
+    let mut set = GlobSetBuilder::new();
+    for pattern in patterns {
+        set.add(Glob::new(pattern)?);
+    }
+    Ok(set.build()?)
+}
+
+/// Renames the outputs colliding with the previous ones, by suffixing their names with `-1`, `-2` and so on.
+fn deduplicate_outputs(inputs: &mut [Input]) {
+    let mut taken = inputs
+        .iter()
+        .map(|i| collision_key(&i.output))
+        .collect::<HashSet<_>>();
+    let mut seen = HashSet::new();
+    for input in inputs {
+        if seen.insert(collision_key(&input.output)) {
+            continue;
+        }
+
+        let renamed = suffixed(&input.output, |key| taken.contains(key));
+        warn!(
+            "{:?} collides with another image, so it's written to {:?}",
+            input.output, renamed
+        );
+        taken.insert(collision_key(&renamed));
+        seen.insert(collision_key(&renamed));
+        input.output = renamed;
+    }
+}
+
+/// Outputs claimed by the sources of the images, so the ones found while watching never overwrite the others.
+#[derive(Debug, Default)]
+pub struct Outputs {
+    /// Canonical paths of the local sources by the collision keys of their outputs, or [`None`] for the remote ones.
+    claims: HashMap<String, Option<PathBuf>>,
+    /// Outputs by the canonical paths of their local sources, so a changed image keeps its output.
+    by_source: HashMap<PathBuf, PathBuf>,
+}
+
+impl Outputs {
+    /// Claims the `output` for the `source`, being the canonical path of a local image or [`None`] for a remote one.
+    ///
+    /// The first claim of an output wins, so the later ones of other sources are ignored.
+    pub fn claim(&mut self, output: &Path, source: Option<&Path>) {
+        let claimed = self
+            .claims
+            .entry(collision_key(output))
+            .or_insert_with(|| source.map(Path::to_path_buf));
+        if let Some(source) = source.filter(|s| claimed.as_deref() == Some(*s)) {
+            self.by_source
+                .entry(source.to_path_buf())
+                .or_insert_with(|| output.to_path_buf());
+        }
+    }
+
+    /// Assigns the output to the image, which is the one of its source claimed earlier, or its own one, renamed the
+    /// same way [`resolve`] does, if it collides with the output of another source.
+    pub fn assign(&mut self, mut input: Input) -> Input {
+        let Some(source) = canonical_source(&input.source) else {
+            return input;
+        };
+        if let Some(output) = self.by_source.get(&source) {
+            input.output = output.clone();
+            return input;
+        }
+
+        let taken = |claims: &HashMap<_, Option<PathBuf>>, key: &str| {
+            claims.get(key).is_some_and(|s| s.as_ref() != Some(&source))
+        };
+        if taken(&self.claims, &collision_key(&input.output)) {
+            let renamed = suffixed(&input.output, |key| taken(&self.claims, key));
+            warn!(
+                "{:?} collides with another image, so it's written to {:?}",
+                input.output, renamed
+            );
+            input.output = renamed;
+        }
+        self.claim(&input.output, Some(&source));
+        input
+    }
+}
+
+/// Canonical path of a local image, telling the same file given by different paths apart from the other ones.
+pub fn canonical_source(source: &ImageSource) -> Option<PathBuf> {
+    match source {
+        ImageSource::LocalFile(path) => Some(fs::canonicalize(path).unwrap_or(path.clone())),
+        ImageSource::RemoteUrl(_) => None,
+    }
+}
+
+/// Key the outputs collide by, regardless of their extensions and letter case, as the extensions are replaced with
+/// the one of the output format, and file systems may be case-insensitive.
+fn collision_key(output: &Path) -> String {
+    output.with_extension("").to_string_lossy().to_lowercase()
+}
+
+/// Suffixes the name of the `output` with the first of `-1`, `-2` and so on, whose collision key isn't `taken`.
+fn suffixed(output: &Path, taken: impl Fn(&str) -> bool) -> PathBuf {
+    let stem = output.with_extension("");
+    let stem = stem.file_name().unwrap_or_default();
+    (1..)
+        .map(|n| {
+            let mut name = OsString::from(stem);
+            name.push(format!("-{n}"));
+            if let Some(ext) = output.extension() {
+                name.push(".");
+                name.push(ext);
+            }
+            output.with_file_name(name)
+        })
+        .find(|renamed| !taken(&collision_key(renamed)))
+        .expect("there are less outputs than suffixes")
+}
+
+#[cfg(test)]
+mod spec {
+    use tempfile::TempDir;
+
+    use super::*;
+
+    const NO_FILTERS: InputSettings = InputSettings {
+        recursive: false,
+        include: Vec::new(),
+        exclude: Vec::new(),
+    };
+
+    /// A directory with the files at the given paths.
+    fn tree(files: &[&str]) -> TempDir {
+        let dir = tempfile::tempdir().unwrap();
+        for file in files {
+            let path = dir.path().join(file);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, b"").unwrap();
+        }
+        dir
+    }
+
+    fn path(dir: &TempDir, file: &str) -> String {
+        dir.path().join(file).to_string_lossy().into_owned()
+    }
+
+    fn outputs(inputs: &[Input]) -> Vec<&str> {
+        inputs.iter().map(|i| i.output.to_str().unwrap()).collect()
+    }
+
+    #[test]
+    fn resolves_files_and_urls() {
+        let dir = tree(&["a.jpg", "notes.txt"]);
+
+        let inputs = resolve(
+            &[
+                path(&dir, "a.jpg"),
+                path(&dir, "notes.txt"),
+                "https://example.com/b.png".into(),
+            ],
+            &NO_FILTERS,
+        )
+        .unwrap()
+        .inputs;
+
+        assert_eq!(
+            outputs(&inputs),
+            ["a.jpg", "notes.txt", "https___example.com_b.png"]
+        );
+        assert_eq!(
+            inputs[0].source,
+            ImageSource::LocalFile(dir.path().join("a.jpg"))
+        );
+        assert!(matches!(inputs[2].source, ImageSource::RemoteUrl(_)));
+    }
+
+    #[test]
+    fn reports_unresolved_paths_along_with_images() {
+        let dir = tree(&["a.jpg"]);
+        let missing = path(&dir, "missing.jpg");
+
+        let resolved = resolve(&[missing.clone(), path(&dir, "a.jpg")], &NO_FILTERS).unwrap();
+
+        assert_eq!(outputs(&resolved.inputs), ["a.jpg"]);
+        assert_eq!(resolved.failed.len(), 1);
+        assert_eq!(resolved.failed[0].0, missing);
+        assert!(resolved.failed[0]
+            .1
+            .to_string()
+            .contains("Unknown image source"));
+    }
+
+    #[test]
+    fn finds_images_in_directories() {
+        let dir = tree(&[
+            "a.jpg",
+            "b.PNG",
+            "notes.txt",
+            "sub/c.gif",
+            "sub/deep/d.webp",
+        ]);
+        let root = path(&dir, "");
+
+        let flat = resolve(std::slice::from_ref(&root), &NO_FILTERS)
+            .unwrap()
+            .inputs;
+        assert_eq!(outputs(&flat), ["a.jpg", "b.PNG"]);
+
+        let recursive = InputSettings {
+            recursive: true,
+            ..NO_FILTERS
+        };
+        let all = resolve(&[root], &recursive).unwrap().inputs;
+        assert_eq!(
+            outputs(&all),
+            ["a.jpg", "b.PNG", "sub/c.gif", "sub/deep/d.webp"]
+        );
+    }
+
+    #[test]
+    fn expands_glob_patterns() {
+        let dir = tree(&["a.jpg", "b.png", "sub/c.jpg", "sub/deep/d.jpg"]);
+
+        let inputs = resolve(&[path(&dir, "**/*.jpg")], &NO_FILTERS)
+            .unwrap()
+            .inputs;
+
+        assert_eq!(outputs(&inputs), ["a.jpg", "sub/c.jpg", "sub/deep/d.jpg"]);
+    }
+
+    #[test]
+    fn applies_include_and_exclude_filters() {
+        let dir = tree(&["a.jpg", "b.png", "sub/c.jpg", "sub/d.png", "raw/e.jpg"]);
+        let settings = InputSettings {
+            recursive: true,
+            include: vec!["*.jpg".into()],
+            exclude: vec!["raw/**".into()],
+        };
+
+        let inputs = resolve(&[path(&dir, "")], &settings).unwrap().inputs;
+
+        assert_eq!(outputs(&inputs), ["a.jpg", "sub/c.jpg"]);
+    }
+
+    #[test]
+    fn resolves_found_images() {
+        let dir = tree(&[
+            "a.jpg",
+            "notes.txt",
+            "sub/b.jpg",
+            "sub/deep/d.png",
+            "raw/c.jpg",
+        ]);
+        let settings = InputSettings {
+            recursive: true,
+            include: Vec::new(),
+            exclude: vec!["raw/**".into()],
+        };
+        let found = |file: &str, settings: &InputSettings| {
+            resolve_found(dir.path(), &dir.path().join(file), settings)
+                .unwrap()
+                .into_iter()
+                .map(|i| i.output)
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(found("a.jpg", &settings), [PathBuf::from("a.jpg")]);
+        assert_eq!(found("sub/b.jpg", &settings), [PathBuf::from("sub/b.jpg")]);
+        assert!(found("sub/b.jpg", &NO_FILTERS).is_empty());
+        assert!(found("notes.txt", &settings).is_empty());
+        assert!(found("raw/c.jpg", &settings).is_empty());
+        assert_eq!(
+            found("sub", &settings),
+            [PathBuf::from("sub/b.jpg"), PathBuf::from("sub/deep/d.png")]
+        );
+        assert!(found("raw", &settings).is_empty());
+        assert!(found("removed.jpg", &settings).is_empty());
+    }
+
+    #[test]
+    fn renames_colliding_outputs() {
+        let dir = tree(&[
+            "one/a.jpg",
+            "two/a.jpg",
+            "two/A.png",
+            "two/a-1.gif",
+            "x.y.jpg",
+            "z/x.y.png",
+        ]);
+
+        let inputs = resolve(
+            &[
+                path(&dir, "one/a.jpg"),
+                path(&dir, "two/a.jpg"),
+                path(&dir, "two/A.png"),
+                path(&dir, "two/a-1.gif"),
+                path(&dir, "x.y.jpg"),
+                path(&dir, "z/x.y.png"),
+            ],
+            &NO_FILTERS,
+        )
+        .unwrap()
+        .inputs;
+
+        assert_eq!(
+            outputs(&inputs),
+            [
+                "a.jpg",
+                "a-2.jpg",
+                "A-3.png",
+                "a-1.gif",
+                "x.y.jpg",
+                "x.y-1.png"
+            ]
+        );
+    }
+
+    #[test]
+    fn assigns_outputs_claimed_by_other_sources_renamed() {
+        let dir = tree(&["a.png", "a.jpg", "x/a.jpg", "b.jpg", "c.jpg"]);
+        let found = |file: &str| Input {
+            source: ImageSource::LocalFile(dir.path().join(file)),
+            output: PathBuf::from(file).file_name().unwrap().into(),
+        };
+        let canonical = |file: &str| fs::canonicalize(dir.path().join(file)).unwrap();
+
+        let mut outputs = Outputs::default();
+        outputs.claim(Path::new("a.png"), Some(&canonical("a.png")));
+        outputs.claim(Path::new("remote.png"), None);
+
+        assert_eq!(outputs.assign(found("a.png")).output, Path::new("a.png"));
+        assert_eq!(outputs.assign(found("a.jpg")).output, Path::new("a-1.jpg"));
+        assert_eq!(
+            outputs.assign(found("x/a.jpg")).output,
+            Path::new("a-2.jpg")
+        );
+        // A changed image keeps its output, even if it was renamed.
+        assert_eq!(outputs.assign(found("a.jpg")).output, Path::new("a-1.jpg"));
+        assert_eq!(outputs.assign(found("b.jpg")).output, Path::new("b.jpg"));
+
+        let claimed_by_remote = Input {
+            output: "REMOTE.jpg".into(),
+            ..found("c.jpg")
+        };
+        assert_eq!(
+            outputs.assign(claimed_by_remote).output,
+            Path::new("REMOTE-1.jpg")
+        );
+    }
+
+    #[test]
+    fn skips_repeated_files() {
+        let dir = tree(&["a.jpg", "b.jpg"]);
+
+        let inputs = resolve(
+            &[path(&dir, "a.jpg"), path(&dir, ""), path(&dir, "*.jpg")],
+            &NO_FILTERS,
+        )
+        .unwrap()
+        .inputs;
+
+        assert_eq!(outputs(&inputs), ["a.jpg", "b.jpg"]);
+    }
+}
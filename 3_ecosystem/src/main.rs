@@ -1,59 +1,186 @@
-use std::borrow::Cow;
+//! Optimizes JPEG, PNG, WebP and GIF images, configured with the command line options, `APP_`-prefixed environment
+//! variables and `3_ecosystem/config.json`, in the order of precedence.
+//!
+//! The file mirrors the nested settings, as `3_ecosystem/config.example.json` does, while the variables separate their
+//! levels with `__`, e.g. transcoding everything to WebP with
+//! ```text
+//! APP_OUTPUT_FORMAT=webp
+//! APP_JPEG__QUALITY=75
+//! APP_PNG__COMPRESSION=best
+//! APP_PNG__REDUCE_PALETTE=true
+//! APP_GIF__SPEED=10
+//! ```
+//! WebP is always encoded losslessly, so it has no settings of its own.
+
+mod cache;
+mod codec;
+mod fetch;
+mod input;
+mod metadata;
+mod report;
+mod transform;
+mod watch;
+
 use std::collections::HashMap;
 use std::fmt::Debug;
-use std::io::{BufWriter, Cursor};
 use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::sync::Arc;
+use std::time::Duration;
 use std::{fs, io};
 
-use anyhow::{bail, Context, Result};
+use anyhow::{ensure, Context, Result};
 use clap::Parser;
 use config::builder::BuilderState;
-use config::{Config, ConfigBuilder, FileFormat, Source};
+use config::{Config, ConfigBuilder, FileFormat};
 use futures::future;
-use image::codecs::jpeg::JpegEncoder;
-use image::{io::Reader as ImageReader, DynamicImage, ImageFormat};
 use serde::Deserialize;
 use tokio::runtime::Builder;
+use tokio::sync::Semaphore;
 use tokio::time::Instant;
-use tracing::info;
+use tracing::{error, info};
 use tracing_subscriber::EnvFilter;
-use url::Url;
+
+use crate::cache::{Cache, Entry};
+use crate::codec::{
+    EncodeSettings, GifSettings, Image, JpegSettings, OutputFormat, PngSettings, Quality,
+    DEFAULT_QUALITY,
+};
+use crate::fetch::{FetchSettings, Fetcher};
+use crate::input::{ImageSource, Input, InputSettings, Outputs, Resolved};
+use crate::metadata::{Metadata, MetadataSettings};
+use crate::report::{ImageReport, Outcome, Report, ReportFormat, ReportSettings};
+use crate::transform::{Fit, ResizeSettings};
 
 const DEFAULT_LOG_LEVEL: &str = "info";
 
 const CONFIG_FILE: &str = "3_ecosystem/config.json";
 const DEFAULT_OUTPUT_DIR: &str = "3_ecosystem/output";
-const DEFAULT_QUALITY: Quality = 75;
+const DEFAULT_PNG_COMPRESSION: &str = "best";
+const DEFAULT_GIF_SPEED: u8 = 10;
+const DEFAULT_FIT: &str = "contain";
+const DEFAULT_REPORT_FORMAT: &str = "table";
+const DEFAULT_WATCH_DEBOUNCE_MS: u64 = 500;
+const DEFAULT_MAX_DOWNLOADS: usize = 8;
+const DEFAULT_FETCH_TIMEOUT_SECS: u64 = 30;
+const DEFAULT_MAX_BODY_SIZE: u64 = 50 * 1024 * 1024;
+const DEFAULT_FETCH_RETRIES: u32 = 3;
+const DEFAULT_RETRY_BACKOFF_MS: u64 = 500;
+const DEFAULT_MAX_REDIRECTS: usize = 5;
 
 type NumberOfThreads = usize;
-type Quality = u8;
 
 #[derive(Parser)]
 #[command(about)]
 struct Cli {
+    /// Images to optimize: files, directories, glob patterns or URLs.
     #[arg(long)]
     images: Vec<String>,
 
+    /// Look for images in subdirectories of the given directories as well.
+    #[arg(long)]
+    recursive: bool,
+
+    /// Only optimize images found in directories or by glob patterns, which match any of these glob patterns.
+    #[arg(long)]
+    include: Vec<String>,
+
+    /// Skip images found in directories or by glob patterns, which match any of these glob patterns.
+    #[arg(long)]
+    exclude: Vec<String>,
+
+    /// Number of images decoded and encoded at once.
     #[arg(long)]
     max_threads: Option<NumberOfThreads>,
 
+    /// Number of images held in memory at once, twice `--max-threads` by default.
+    #[arg(long)]
+    max_in_flight: Option<usize>,
+
+    /// Number of remote images downloaded at once.
+    #[arg(long)]
+    max_downloads: Option<usize>,
+
+    /// Optimize all images, even the ones unchanged since the previous run.
+    #[arg(long)]
+    force: bool,
+
     #[arg(long)]
     output_dir: Option<String>,
 
-    #[arg(
-        long,
-        default_value_t = DEFAULT_QUALITY,
-        value_parser = clap::value_parser!(Quality).range(1..=100)
-    )]
-    quality: Quality,
+    /// Keep running, optimizing new and changed images in this directory until interrupted.
+    #[arg(long)]
+    watch: Option<String>,
+
+    /// Quality of JPEG images.
+    #[arg(long, value_parser = clap::value_parser!(Quality).range(1..=100))]
+    quality: Option<Quality>,
+
+    /// Transcode all images to this format instead of keeping their own.
+    #[arg(long, value_enum)]
+    output_format: Option<OutputFormat>,
+
+    /// Scale images down to this width at most.
+    #[arg(long)]
+    max_width: Option<u32>,
+
+    /// Scale images down to this height at most.
+    #[arg(long)]
+    max_height: Option<u32>,
+
+    /// How images are fitted into `--max-width` and `--max-height`.
+    #[arg(long, value_enum)]
+    fit: Option<Fit>,
+
+    /// Format of the report printed once all images are processed.
+    #[arg(long, value_enum)]
+    report: Option<ReportFormat>,
+
+    /// Write the report to this file instead of the standard output.
+    #[arg(long)]
+    report_file: Option<String>,
+
+    /// Metadata to keep: `icc` for the color profile, or names of EXIF tags. Everything else is stripped.
+    #[arg(long, value_delimiter = ',')]
+    keep_metadata: Vec<String>,
 }
 
 #[derive(Debug, Deserialize)]
 struct AppConfig {
     images: Option<Vec<String>>,
+    input: InputSettings,
     max_threads: NumberOfThreads,
+    max_in_flight: Option<usize>,
+    max_downloads: usize,
+    force: bool,
     output_dir: PathBuf,
-    quality: Quality,
+    watch: Option<PathBuf>,
+    /// Delay of optimizing a changed image in the watched directory, letting it be completely written.
+    watch_debounce_ms: u64,
+    output_format: Option<OutputFormat>,
+    jpeg: JpegSettings,
+    png: PngSettings,
+    gif: GifSettings,
+    resize: ResizeSettings,
+    metadata: MetadataSettings,
+    fetch: FetchSettings,
+    report: ReportSettings,
+}
+
+impl AppConfig {
+    /// While some images are decoded and encoded, the others may be read or downloaded meanwhile.
+    fn max_in_flight(&self) -> usize {
+        self.max_in_flight.unwrap_or(self.max_threads * 2)
+    }
+
+    fn encode_settings(&self) -> EncodeSettings {
+        EncodeSettings {
+            output_format: self.output_format,
+            jpeg: self.jpeg,
+            png: self.png,
+            gif: self.gif,
+        }
+    }
 }
 
 trait AddCustomConfigs: Sized {
@@ -65,14 +192,35 @@ impl<St: BuilderState> AddCustomConfigs for ConfigBuilder<St> {
     fn add_default_config(mut self) -> Result<Self> {
         let conf_values = HashMap::from([
             ("max_threads", num_cpus::get().to_string()),
+            ("max_downloads", DEFAULT_MAX_DOWNLOADS.to_string()),
             ("output_dir", DEFAULT_OUTPUT_DIR.to_string()),
-            ("quality", DEFAULT_QUALITY.to_string()),
+            ("jpeg.quality", DEFAULT_QUALITY.to_string()),
+            ("png.compression", DEFAULT_PNG_COMPRESSION.to_string()),
+            ("png.reduce_palette", true.to_string()),
+            ("gif.speed", DEFAULT_GIF_SPEED.to_string()),
+            ("input.recursive", false.to_string()),
+            ("force", false.to_string()),
+            ("resize.fit", DEFAULT_FIT.to_string()),
+            ("report.format", DEFAULT_REPORT_FORMAT.to_string()),
+            ("watch_debounce_ms", DEFAULT_WATCH_DEBOUNCE_MS.to_string()),
+            ("fetch.timeout_secs", DEFAULT_FETCH_TIMEOUT_SECS.to_string()),
+            ("fetch.max_body_size", DEFAULT_MAX_BODY_SIZE.to_string()),
+            ("fetch.retries", DEFAULT_FETCH_RETRIES.to_string()),
+            (
+                "fetch.retry_backoff_ms",
+                DEFAULT_RETRY_BACKOFF_MS.to_string(),
+            ),
+            ("fetch.max_redirects", DEFAULT_MAX_REDIRECTS.to_string()),
         ]);
 
         for (key, value) in conf_values {
             self = self.set_default(key, value)?;
         }
 
+        for key in ["input.include", "input.exclude", "metadata.keep"] {
+            self = self.set_default(key, Vec::<String>::new())?;
+        }
+
         Ok(self)
     }
 
@@ -80,11 +228,44 @@ impl<St: BuilderState> AddCustomConfigs for ConfigBuilder<St> {
         if !cli.images.is_empty() {
             self = self.set_override("images", cli.images)?;
         }
+        if cli.force {
+            self = self.set_override("force", true)?;
+        }
+        if cli.recursive {
+            self = self.set_override("input.recursive", true)?;
+        }
+        if !cli.include.is_empty() {
+            self = self.set_override("input.include", cli.include)?;
+        }
+        if !cli.exclude.is_empty() {
+            self = self.set_override("input.exclude", cli.exclude)?;
+        }
+        if !cli.keep_metadata.is_empty() {
+            self = self.set_override("metadata.keep", cli.keep_metadata)?;
+        }
 
         Ok(self
             .set_override_option("max_threads", cli.max_threads.map(|v| v.to_string()))?
+            .set_override_option("max_in_flight", cli.max_in_flight.map(|v| v.to_string()))?
+            .set_override_option("max_downloads", cli.max_downloads.map(|v| v.to_string()))?
             .set_override_option("output_dir", cli.output_dir)?
-            .set_override("quality", cli.quality.to_string())?)
+            .set_override_option("watch", cli.watch)?
+            .set_override_option("jpeg.quality", cli.quality.map(|v| v.to_string()))?
+            .set_override_option(
+                "output_format",
+                cli.output_format.map(|v| format!("{v:?}").to_lowercase()),
+            )?
+            .set_override_option("resize.max_width", cli.max_width.map(|v| v.to_string()))?
+            .set_override_option("resize.max_height", cli.max_height.map(|v| v.to_string()))?
+            .set_override_option(
+                "report.format",
+                cli.report.map(|v| format!("{v:?}").to_lowercase()),
+            )?
+            .set_override_option("report.path", cli.report_file)?
+            .set_override_option(
+                "resize.fit",
+                cli.fit.map(|v| format!("{v:?}").to_lowercase()),
+            )?)
     }
 }
 
@@ -102,129 +283,172 @@ fn build_config() -> Result<AppConfig> {
         .add_source(config::File::new(CONFIG_FILE, FileFormat::Json).required(false))
         .add_source(
             config::Environment::with_prefix("app")
+                .prefix_separator("_")
+                .separator("__")
                 .try_parsing(true)
                 .list_separator(" ")
-                .with_list_parse_key("images"),
+                .with_list_parse_key("images")
+                .with_list_parse_key("input.include")
+                .with_list_parse_key("input.exclude")
+                .with_list_parse_key("metadata.keep"),
         )
         .add_cli_config(cli)?
         .build()?;
 
     let mut config = config.try_deserialize::<AppConfig>()?;
+    // Otherwise, the images would wait for their turn forever.
+    ensure!(
+        config.max_threads > 0 && config.max_in_flight() > 0 && config.max_downloads > 0,
+        "max_threads, max_in_flight and max_downloads must be positive"
+    );
     if config.images.is_none() {
-        config.images = Some(get_images_from_stdin()?);
+        // Images already in the watched directory are optimized first, so it's fully in sync.
+        config.images = Some(match &config.watch {
+            Some(dir) => vec![dir.to_string_lossy().into_owned()],
+            None => get_images_from_stdin()?,
+        });
     }
 
     Ok(config)
 }
 
-type ImagePath = String;
-
-#[derive(Debug)]
-enum ImageSource {
-    LocalFile(PathBuf),
-    RemoteUrl(Url),
+/// Image read from a local file or downloaded, to be optimized off the async workers.
+enum Fetched {
+    /// Local file, which is read along with the optimizing.
+    Local(PathBuf),
+    Downloaded(Vec<u8>),
 }
 
-fn parse_image_source_by_path(image_path: ImagePath) -> Result<ImageSource> {
-    if Path::new(&image_path).is_file() {
-        return Ok(ImageSource::LocalFile(PathBuf::from(image_path)));
-    }
-
-    if let Ok(url) = Url::parse(&image_path) {
-        return Ok(ImageSource::RemoteUrl(url));
-    }
-
-    bail!("Unknown image source of: {}", image_path);
+/// Everything the images are optimized with, shared between the ones optimized concurrently.
+struct Optimizer {
+    config: AppConfig,
+    cache: Cache,
+    fetcher: Fetcher,
+    /// Bounds the images being optimized at once, so the memory they take doesn't grow with their number.
+    in_flight: Semaphore,
+    /// Bounds the remote images being downloaded at once.
+    downloads: Semaphore,
+    /// Bounds the images being read, decoded and encoded at once, so the CPU-bound work doesn't take the whole
+    /// blocking pool, which the downloads resolve their hosts in as well.
+    transcodes: Semaphore,
 }
 
-enum Image {
-    Jpeg(DynamicImage),
-}
+impl Optimizer {
+    fn new(config: AppConfig) -> Result<Self> {
+        // Any change of the optimizer itself may change the optimized images as well.
+        let cache_settings = (
+            env!("CARGO_PKG_VERSION"),
+            config.encode_settings(),
+            &config.resize,
+            &config.metadata,
+        );
+        let cache = Cache::load(&config.output_dir, &cache_settings)?;
+        let fetcher = Fetcher::new(config.fetch.clone())?;
+        let in_flight = Semaphore::new(config.max_in_flight());
+        let downloads = Semaphore::new(config.max_downloads);
+        let transcodes = Semaphore::new(config.max_threads);
+
+        Ok(Self {
+            config,
+            cache,
+            fetcher,
+            in_flight,
+            downloads,
+            transcodes,
+        })
+    }
 
-async fn get_image_from_source(image_source: &ImageSource) -> Result<Image> {
-    let (img, format) = match image_source {
-        ImageSource::LocalFile(image_path) => {
-            let img_reader = ImageReader::open(image_path)?.with_guessed_format()?;
-            let format = img_reader.format();
-            let img = img_reader.decode()?;
-            (img, format)
-        }
-        ImageSource::RemoteUrl(image_url) => {
-            let response = reqwest::get(image_url.path()).await?;
-            let img_reader =
-                ImageReader::new(Cursor::new(response.bytes().await?)).with_guessed_format()?;
-            let format = img_reader.format();
-            let img = img_reader.decode()?;
-            (img, format)
-        }
-    };
+    async fn optimize_img(self: &Arc<Self>, input: &Input) -> Result<Outcome> {
+        let _permit = self.in_flight.acquire().await?;
 
-    match format {
-        Some(format) => match format {
-            ImageFormat::Jpeg => Ok(Image::Jpeg(img)),
-            _ => {
-                bail!("Unsupported image format: {:?}", format);
+        let fetched = match &input.source {
+            ImageSource::LocalFile(image_path) => Fetched::Local(image_path.clone()),
+            ImageSource::RemoteUrl(image_url) => {
+                let _permit = self.downloads.acquire().await?;
+                Fetched::Downloaded(self.fetcher.fetch(image_url).await?)
             }
-        },
-        None => {
-            bail!("Unknown image format of {:?}", image_source);
-        }
+        };
+
+        // Everything else blocks on either the disk or the CPU, so it's kept off the async workers, in the blocking
+        // pool. Local images are read there as well, along with the transcoding they're waiting for anyway.
+        let _permit = self.transcodes.acquire().await?;
+        let (this, input) = (self.clone(), input.clone());
+        tokio::task::spawn_blocking(move || this.optimize_fetched(&input, fetched)).await?
     }
-}
 
-fn get_file_name_by_img_source(img_source: &ImageSource) -> Result<Cow<str>> {
-    Ok(match &img_source {
-        ImageSource::LocalFile(img_path) => Cow::Borrowed(
-            img_path
-                .file_name()
-                .with_context(|| format!("file_name of {:?} is None", img_path))?
-                .to_str()
-                .with_context(|| format!("OsStr.to_str() of {:?} is None", img_path))?,
-        ),
-        ImageSource::RemoteUrl(img_path) => {
-            Cow::Owned(sanitise_file_name::sanitise(img_path.as_str()))
+    fn optimize_fetched(&self, input: &Input, fetched: Fetched) -> Result<Outcome> {
+        let (bytes, source) = match fetched {
+            Fetched::Local(path) => (fs::read(&path)?, Some(fs::canonicalize(&path)?)),
+            Fetched::Downloaded(bytes) => (bytes, None),
+        };
+        let bytes_before = bytes.len() as u64;
+        let key = self.cache.key(&bytes);
+        if !self.config.force {
+            if let Some(entry) = self.cache.get(&input.output, &key) {
+                info!("{:?} is unchanged, so it's skipped", entry.output);
+                let output = self.config.output_dir.join(entry.output);
+                return Ok(Outcome {
+                    bytes_before,
+                    bytes_after: fs::metadata(&output)?.len(),
+                    output,
+                    unchanged: true,
+                });
+            }
         }
-    })
-}
 
-fn get_file_extension_from_image(image: &Image) -> &str {
-    match &image {
-        Image::Jpeg(_) => "jpg",
+        // The error is logged and reported along with the source, so it isn't named here again.
+        let (relative_output, bytes_after) = self.transcode(&bytes, &input.output)?;
+
+        self.cache.insert(
+            input.output.clone(),
+            Entry {
+                key,
+                output: relative_output.clone(),
+                source,
+            },
+        )?;
+
+        Ok(Outcome {
+            output: self.config.output_dir.join(relative_output),
+            bytes_before,
+            bytes_after,
+            unchanged: false,
+        })
     }
-}
-
-async fn optimize_img(
-    img_path: ImagePath,
-    quality: Quality,
-    output_dir: impl AsRef<Path>,
-) -> Result<()> {
-    let image_source = parse_image_source_by_path(img_path)?;
-    let image = get_image_from_source(&image_source).await?;
 
-    let file_name = get_file_name_by_img_source(&image_source)?;
-
-    let file_ext = get_file_extension_from_image(&image);
-
-    let output = fs::File::create(
-        output_dir
-            .as_ref()
-            .join(Path::new(file_name.as_ref()).with_extension(file_ext)),
-    )?;
+    /// Writes the image optimized from the `bytes` to the `output`, with the extension of its format.
+    ///
+    /// Returns the path of the written image, relative to the output directory, along with its size.
+    fn transcode(&self, bytes: &[u8], output: &Path) -> Result<(PathBuf, u64)> {
+        let settings = self.config.encode_settings();
+
+        let metadata = Metadata::read(bytes);
+        let orientation = metadata.orientation();
+        let image = Image::decode(bytes, settings.output_format)
+            .context("failed to decode")?
+            .map(|img| transform::resize(orientation.apply(img), &self.config.resize));
+
+        let relative_output = output.with_extension(image.format().extension());
+        let output = self.config.output_dir.join(&relative_output);
+        if let Some(parent) = output.parent() {
+            fs::create_dir_all(parent)?;
+        }
 
-    let writer = BufWriter::new(output);
+        let mut encoded = Vec::new();
+        image.encode(&settings, &mut encoded)?;
+        let encoded = metadata
+            .retain(&self.config.metadata)?
+            .embed(image.format(), encoded)?;
+        fs::write(&output, &encoded)?;
 
-    match image {
-        Image::Jpeg(image) => {
-            let mut encoder = JpegEncoder::new_with_quality(writer, quality);
-            encoder.encode_image(&image)?;
-        }
+        Ok((relative_output, encoded.len() as u64))
     }
-
-    Ok(())
 }
 
-fn main() -> Result<()> {
+fn main() -> Result<ExitCode> {
+    // Logs are kept apart from the report, so the latter can be piped.
     tracing_subscriber::fmt()
+        .with_writer(io::stderr)
         .with_env_filter(
             EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new(DEFAULT_LOG_LEVEL)),
         )
@@ -236,22 +460,124 @@ fn main() -> Result<()> {
 
     fs::create_dir_all(&config.output_dir)?;
 
+    // Images are decoded and encoded in the blocking pool, bounded by the optimizer itself, so the pool is left at its
+    // default size for everything else, e.g. resolving the hosts of the downloaded images.
     let runtime = Builder::new_multi_thread()
         .worker_threads(config.max_threads)
         .enable_all()
         .build()?;
 
-    let tasks = config.images.unwrap().into_iter().map(|img| async {
+    let start = Instant::now();
+    let Resolved { inputs, failed } =
+        input::resolve(config.images.as_deref().unwrap_or_default(), &config.input)?;
+    info!("found {} images to optimize", inputs.len());
+    // Reported as failed images, so the others are still optimized.
+    let unresolved = failed
+        .iter()
+        .map(|(path, e)| {
+            error!("failed to resolve {}: {:#}", path, e);
+            ImageReport::failed(path.clone(), e, start.elapsed())
+        })
+        .collect::<Vec<_>>();
+
+    let optimizer = Arc::new(Optimizer::new(config)?);
+    let (config, cache) = (&optimizer.config, &optimizer.cache);
+
+    // Every image waits for its turn, so only the ones in flight take memory.
+    let tasks = inputs.iter().map(|input| async {
         let start = Instant::now();
-        let result = optimize_img(img, config.quality, &config.output_dir).await;
-        info!(
-            "optimization of image took {:.2} seconds",
-            start.elapsed().as_secs_f64()
-        );
-        result
+        let result = optimizer.optimize_img(input).await;
+        if let Err(e) = &result {
+            error!("failed to optimize {}: {:#}", input.source, e);
+        }
+        ImageReport::new(input, &result, start.elapsed())
     });
 
-    runtime.block_on(future::try_join_all(tasks))?;
+    let mut images = unresolved;
+    images.extend(runtime.block_on(future::join_all(tasks)));
+    let report = Report::new(images, start.elapsed());
+
+    let pruned = cache.prune(inputs.iter().map(|i| i.output.as_path()))?;
+    if pruned > 0 {
+        info!("removed {} stale images", pruned);
+    }
+    cache.save()?;
+
+    match &config.report.path {
+        Some(path) => report.write(config.report.format, fs::File::create(path)?)?,
+        None => report.write(config.report.format, io::stdout().lock())?,
+    }
+
+    let Some(root) = &config.watch else {
+        return Ok(report.exit_code());
+    };
+
+    let optimize = |input: Input| {
+        let optimizer = optimizer.clone();
+        async move {
+            match optimizer.optimize_img(&input).await {
+                Ok(outcome) if !outcome.unchanged => info!(
+                    "optimized {} into {:?}: {} -> {} bytes",
+                    input.source, outcome.output, outcome.bytes_before, outcome.bytes_after,
+                ),
+                Ok(_) => {}
+                Err(e) => error!("failed to optimize {}: {:#}", input.source, e),
+            }
+            if let Err(e) = optimizer.cache.save() {
+                error!("failed to save the cache: {:#}", e);
+            }
+        }
+    };
+    let shutdown = async {
+        if let Err(e) = tokio::signal::ctrl_c().await {
+            error!("failed to listen for Ctrl-C: {}", e);
+            future::pending::<()>().await;
+        }
+        info!("shutting down");
+    };
+    // Images found while watching never overwrite the ones optimized already, in this run or the previous ones.
+    let mut outputs = Outputs::default();
+    for input in &inputs {
+        outputs.claim(
+            &input.output,
+            input::canonical_source(&input.source).as_deref(),
+        );
+    }
+    for (output, source) in cache.sources() {
+        outputs.claim(&output, source.as_deref());
+    }
+    runtime.block_on(watch::watch(
+        root,
+        &config.input,
+        Duration::from_millis(config.watch_debounce_ms),
+        config.max_in_flight(),
+        outputs,
+        optimize,
+        shutdown,
+    ))?;
+
+    // Images failed while watching are only logged, so it's the initial pass telling whether the run succeeded.
+    Ok(report.exit_code())
+}
 
-    Ok(())
+#[cfg(test)]
+mod spec {
+    use super::*;
+    use crate::codec::PngCompression;
+
+    #[test]
+    fn example_config_is_valid() {
+        let example = concat!(env!("CARGO_MANIFEST_DIR"), "/config.example.json");
+        let config = Config::builder()
+            .add_default_config()
+            .unwrap()
+            .add_source(config::File::new(example, FileFormat::Json))
+            .build()
+            .unwrap()
+            .try_deserialize::<AppConfig>()
+            .unwrap();
+
+        assert_eq!(config.output_format, Some(OutputFormat::WebP));
+        assert_eq!(config.png.compression, PngCompression::Best);
+    }
 }
@@ -0,0 +1,318 @@
+//! Reading, filtering and embedding of EXIF and ICC metadata.
+//!
+//! Encoders never write any metadata on their own, so everything is stripped, unless it's explicitly allowed to be
+//! kept.
+
+use std::io::{Cursor, Write as _};
+
+use anyhow::Result;
+use exif::{In, Tag};
+use flate2::{write::ZlibEncoder, Compression};
+use image::codecs::{jpeg::JpegDecoder, png::PngDecoder, webp::WebPDecoder};
+use image::{ImageDecoder, ImageFormat};
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+use crate::codec::OutputFormat;
+use crate::transform::Orientation;
+
+/// Allowlist entry keeping the ICC color profile.
+const ICC: &str = "icc";
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct MetadataSettings {
+    /// Metadata to keep: either `icc` for the color profile, or names of EXIF tags (e.g. `Copyright`).
+    pub keep: Vec<String>,
+}
+
+impl MetadataSettings {
+    fn keeps(&self, name: &str) -> bool {
+        self.keep.iter().any(|k| k.eq_ignore_ascii_case(name))
+    }
+}
+
+/// Metadata of a source image.
+#[derive(Default)]
+pub struct Metadata {
+    exif: Option<exif::Exif>,
+    icc: Option<Vec<u8>>,
+}
+
+impl Metadata {
+    /// Reads the metadata of an encoded image, ignoring any malformed one.
+    pub fn read(bytes: &[u8]) -> Self {
+        let exif = exif::Reader::new()
+            .read_from_container(&mut Cursor::new(bytes))
+            .ok();
+        let icc = match image::guess_format(bytes) {
+            Ok(ImageFormat::Jpeg) => JpegDecoder::new(bytes)
+                .ok()
+                .and_then(|mut d| d.icc_profile()),
+            Ok(ImageFormat::Png) => PngDecoder::new(bytes)
+                .ok()
+                .and_then(|mut d| d.icc_profile()),
+            Ok(ImageFormat::WebP) => WebPDecoder::new(bytes)
+                .ok()
+                .and_then(|mut d| d.icc_profile()),
+            _ => None,
+        };
+        Self { exif, icc }
+    }
+
+    pub fn orientation(&self) -> Orientation {
+        self.exif
+            .as_ref()
+            .and_then(|e| e.get_field(Tag::Orientation, In::PRIMARY))
+            .and_then(|f| f.value.get_uint(0))
+            .and_then(Orientation::from_exif)
+            .unwrap_or_default()
+    }
+
+    /// Leaves only the allowed metadata.
+    ///
+    /// The orientation is never kept, as it's expected to be already applied to the pixels.
+    pub fn retain(&self, settings: &MetadataSettings) -> Result<Kept> {
+        let icc = self.icc.clone().filter(|_| settings.keeps(ICC));
+
+        let exif = match &self.exif {
+            Some(exif) => {
+                let mut writer = exif::experimental::Writer::new();
+                let mut any = false;
+                for field in exif.fields() {
+                    if field.ifd_num == In::PRIMARY
+                        && field.tag != Tag::Orientation
+                        && settings.keeps(&field.tag.to_string())
+                    {
+                        writer.push_field(field);
+                        any = true;
+                    }
+                }
+                if any {
+                    let mut tiff = Cursor::new(Vec::new());
+                    writer.write(&mut tiff, exif.little_endian())?;
+                    Some(tiff.into_inner())
+                } else {
+                    None
+                }
+            }
+            None => None,
+        };
+
+        Ok(Kept { exif, icc })
+    }
+}
+
+/// Metadata to be embedded into an optimized image.
+#[derive(Debug, Default)]
+pub struct Kept {
+    /// EXIF attributes in the TIFF format.
+    exif: Option<Vec<u8>>,
+    icc: Option<Vec<u8>>,
+}
+
+impl Kept {
+    pub fn is_empty(&self) -> bool {
+        self.exif.is_none() && self.icc.is_none()
+    }
+
+    /// Embeds the metadata into the encoded image.
+    pub fn embed(&self, format: OutputFormat, bytes: Vec<u8>) -> Result<Vec<u8>> {
+        if self.is_empty() {
+            return Ok(bytes);
+        }
+        match format {
+            OutputFormat::Jpeg => Ok(self.embed_jpeg(bytes)),
+            OutputFormat::Png => self.embed_png(bytes),
+            OutputFormat::WebP | OutputFormat::Gif => {
+                warn!("keeping metadata is not supported for {format:?}, so it's stripped");
+                Ok(bytes)
+            }
+        }
+    }
+
+    fn embed_jpeg(&self, bytes: Vec<u8>) -> Vec<u8> {
+        const MAX_SEGMENT: usize = u16::MAX as usize - 2;
+        const ICC_HEADER: &[u8] = b"ICC_PROFILE\0";
+
+        let mut segments = Vec::new();
+        let mut segment = |marker: u8, parts: &[&[u8]]| {
+            let len = 2 + parts.iter().map(|p| p.len()).sum::<usize>();
+            segments.extend_from_slice(&[0xFF, marker]);
+            segments.extend_from_slice(&(len as u16).to_be_bytes());
+            parts.iter().for_each(|p| segments.extend_from_slice(p));
+        };
+
+        if let Some(exif) = self.exif.as_ref().filter(|e| e.len() + 6 <= MAX_SEGMENT) {
+            segment(0xE1, &[b"Exif\0\0", exif]);
+        }
+        if let Some(icc) = &self.icc {
+            // Large profiles are split into numbered chunks.
+            let chunks = icc
+                .chunks(MAX_SEGMENT - ICC_HEADER.len() - 2)
+                .collect::<Vec<_>>();
+            if chunks.len() <= usize::from(u8::MAX) {
+                for (i, chunk) in chunks.iter().enumerate() {
+                    segment(
+                        0xE2,
+                        &[ICC_HEADER, &[i as u8 + 1, chunks.len() as u8], chunk],
+                    );
+                }
+            }
+        }
+
+        // Metadata follows the SOI marker and the JFIF APP0 segment, if there is one.
+        let mut at = 2;
+        if bytes.get(2..4) == Some(&[0xFF, 0xE0]) {
+            at += 2 + usize::from(u16::from_be_bytes([bytes[4], bytes[5]]));
+        }
+        let mut out = Vec::with_capacity(bytes.len() + segments.len());
+        out.extend_from_slice(&bytes[..at]);
+        out.extend_from_slice(&segments);
+        out.extend_from_slice(&bytes[at..]);
+        out
+    }
+
+    fn embed_png(&self, bytes: Vec<u8>) -> Result<Vec<u8>> {
+        // Signature along with the IHDR chunk, which must go first.
+        const HEADER: usize = 8 + 4 + 4 + 13 + 4;
+
+        let mut chunks = Vec::new();
+        let mut chunk = |kind: &[u8; 4], data: &[u8]| {
+            let mut crc = crc32fast::Hasher::new();
+            crc.update(kind);
+            crc.update(data);
+            chunks.extend_from_slice(&(data.len() as u32).to_be_bytes());
+            chunks.extend_from_slice(kind);
+            chunks.extend_from_slice(data);
+            chunks.extend_from_slice(&crc.finalize().to_be_bytes());
+        };
+
+        if let Some(icc) = &self.icc {
+            let mut data = b"ICC Profile\0\0".to_vec();
+            let mut zlib = ZlibEncoder::new(&mut data, Compression::best());
+            zlib.write_all(icc)?;
+            zlib.finish()?;
+            chunk(b"iCCP", &data);
+        }
+        if let Some(exif) = &self.exif {
+            chunk(b"eXIf", exif);
+        }
+
+        let mut out = Vec::with_capacity(bytes.len() + chunks.len());
+        out.extend_from_slice(&bytes[..HEADER]);
+        out.extend_from_slice(&chunks);
+        out.extend_from_slice(&bytes[HEADER..]);
+        Ok(out)
+    }
+}
+
+#[cfg(test)]
+mod spec {
+    use exif::{Field, Value};
+    use image::{DynamicImage, RgbImage};
+
+    use super::*;
+
+    fn field(tag: Tag, value: Value) -> Field {
+        Field {
+            tag,
+            ifd_num: In::PRIMARY,
+            value,
+        }
+    }
+
+    fn tiff(fields: &[Field]) -> Vec<u8> {
+        let mut writer = exif::experimental::Writer::new();
+        fields.iter().for_each(|f| writer.push_field(f));
+        let mut out = Cursor::new(Vec::new());
+        writer.write(&mut out, false).unwrap();
+        out.into_inner()
+    }
+
+    fn encoded(format: ImageFormat) -> Vec<u8> {
+        let mut out = Cursor::new(Vec::new());
+        DynamicImage::ImageRgb8(RgbImage::new(4, 4))
+            .write_to(&mut out, format)
+            .unwrap();
+        out.into_inner()
+    }
+
+    fn keep(names: &[&str]) -> MetadataSettings {
+        MetadataSettings {
+            keep: names.iter().map(|n| n.to_string()).collect(),
+        }
+    }
+
+    /// A JPEG image with `Make`, `Copyright` and `Orientation` EXIF attributes, and an ICC profile.
+    fn jpeg_with_metadata() -> Vec<u8> {
+        let kept = Kept {
+            exif: Some(tiff(&[
+                field(Tag::Make, Value::Ascii(vec![b"Camera".to_vec()])),
+                field(Tag::Copyright, Value::Ascii(vec![b"Me".to_vec()])),
+                field(Tag::Orientation, Value::Short(vec![6])),
+            ])),
+            icc: Some(vec![42; 100]),
+        };
+        kept.embed(OutputFormat::Jpeg, encoded(ImageFormat::Jpeg))
+            .unwrap()
+    }
+
+    #[test]
+    fn reads_orientation_and_icc() {
+        let metadata = Metadata::read(&jpeg_with_metadata());
+
+        assert_eq!(metadata.orientation(), Orientation::Rotate90);
+        assert_eq!(metadata.icc, Some(vec![42; 100]));
+        assert_eq!(
+            Metadata::read(&encoded(ImageFormat::Png)).orientation(),
+            Orientation::Normal
+        );
+    }
+
+    #[test]
+    fn strips_everything_by_default() {
+        let kept = Metadata::read(&jpeg_with_metadata())
+            .retain(&keep(&[]))
+            .unwrap();
+
+        assert!(kept.is_empty());
+    }
+
+    #[test]
+    fn keeps_allowed_exif_tags_only() {
+        let kept = Metadata::read(&jpeg_with_metadata())
+            .retain(&keep(&["copyright", "Orientation"]))
+            .unwrap();
+        assert!(kept.icc.is_none());
+
+        let jpeg = kept
+            .embed(OutputFormat::Jpeg, encoded(ImageFormat::Jpeg))
+            .unwrap();
+        let exif = exif::Reader::new()
+            .read_from_container(&mut Cursor::new(&jpeg))
+            .unwrap();
+
+        let tags = exif.fields().map(|f| f.tag).collect::<Vec<_>>();
+        assert_eq!(tags, [Tag::Copyright]);
+        image::load_from_memory(&jpeg).unwrap();
+    }
+
+    #[test]
+    fn keeps_icc_profile_in_png() {
+        let kept = Metadata::read(&jpeg_with_metadata())
+            .retain(&keep(&[ICC, "Make"]))
+            .unwrap();
+
+        let png = kept
+            .embed(OutputFormat::Png, encoded(ImageFormat::Png))
+            .unwrap();
+
+        let mut decoder = PngDecoder::new(png.as_slice()).unwrap();
+        assert_eq!(decoder.icc_profile(), Some(vec![42; 100]));
+        let exif = exif::Reader::new()
+            .read_from_container(&mut Cursor::new(&png))
+            .unwrap();
+        assert!(exif.get_field(Tag::Make, In::PRIMARY).is_some());
+        image::load_from_memory(&png).unwrap();
+    }
+}
@@ -0,0 +1,373 @@
+//! Summary of an optimization run, either human-readable or machine-readable.
+
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::ExitCode;
+use std::time::Duration;
+
+use anyhow::Result;
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+
+use crate::input::Input;
+
+/// Exit code of a run, in which all the images have failed.
+const FAILURE: u8 = 1;
+/// Exit code of a run, in which some of the images have failed, while the others were optimized.
+const PARTIAL_FAILURE: u8 = 2;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum ReportFormat {
+    Table,
+    Json,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct ReportSettings {
+    pub format: ReportFormat,
+    /// File to write the report to, instead of the standard output.
+    pub path: Option<PathBuf>,
+}
+
+/// Result of a successfully processed image.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Outcome {
+    pub output: PathBuf,
+    pub bytes_before: u64,
+    pub bytes_after: u64,
+    /// Whether the image was unchanged since the previous run, so its optimized version was reused.
+    pub unchanged: bool,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Status {
+    Optimized,
+    Unchanged,
+    Failed,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct ImageReport {
+    pub input: String,
+    pub output: Option<PathBuf>,
+    pub status: Status,
+    pub bytes_before: Option<u64>,
+    pub bytes_after: Option<u64>,
+    /// Size of the source image relative to the optimized one.
+    pub compression_ratio: Option<f64>,
+    pub duration_secs: f64,
+    /// Error the image has failed with, along with all its causes.
+    pub error: Option<String>,
+}
+
+impl ImageReport {
+    pub fn new(input: &Input, result: &Result<Outcome>, duration: Duration) -> Self {
+        let input = input.source.to_string();
+        let duration_secs = duration.as_secs_f64();
+        match result {
+            Ok(outcome) => Self {
+                input,
+                output: Some(outcome.output.clone()),
+                status: if outcome.unchanged {
+                    Status::Unchanged
+                } else {
+                    Status::Optimized
+                },
+                bytes_before: Some(outcome.bytes_before),
+                bytes_after: Some(outcome.bytes_after),
+                compression_ratio: ratio(outcome.bytes_before, outcome.bytes_after),
+                duration_secs,
+                error: None,
+            },
+            Err(e) => Self::failed(input, e, duration),
+        }
+    }
+
+    /// Reports the `input` failed with the `error`, which may be a path not resolved into any images.
+    pub fn failed(input: String, error: &anyhow::Error, duration: Duration) -> Self {
+        Self {
+            input,
+            output: None,
+            status: Status::Failed,
+            bytes_before: None,
+            bytes_after: None,
+            compression_ratio: None,
+            duration_secs: duration.as_secs_f64(),
+            error: Some(format!("{error:#}")),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct Summary {
+    pub total: usize,
+    pub optimized: usize,
+    pub unchanged: usize,
+    pub failed: usize,
+    /// Total size of the successfully processed images.
+    pub bytes_before: u64,
+    /// Total size of the images optimized from the successfully processed ones.
+    pub bytes_after: u64,
+    pub compression_ratio: Option<f64>,
+    pub duration_secs: f64,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct Report {
+    pub images: Vec<ImageReport>,
+    pub summary: Summary,
+}
+
+impl Report {
+    /// Summarizes the `images`, processed concurrently within the `duration`.
+    pub fn new(images: Vec<ImageReport>, duration: Duration) -> Self {
+        let mut summary = Summary {
+            total: images.len(),
+            duration_secs: duration.as_secs_f64(),
+            ..Summary::default()
+        };
+        for image in &images {
+            match image.status {
+                Status::Optimized => summary.optimized += 1,
+                Status::Unchanged => summary.unchanged += 1,
+                Status::Failed => summary.failed += 1,
+            }
+            summary.bytes_before += image.bytes_before.unwrap_or_default();
+            summary.bytes_after += image.bytes_after.unwrap_or_default();
+        }
+        summary.compression_ratio = ratio(summary.bytes_before, summary.bytes_after);
+
+        Self { images, summary }
+    }
+
+    /// Exit code telling whether all, some or none of the images have failed.
+    pub fn exit_code(&self) -> ExitCode {
+        match self.summary.failed {
+            0 => ExitCode::SUCCESS,
+            failed if failed == self.summary.total => ExitCode::from(FAILURE),
+            _ => ExitCode::from(PARTIAL_FAILURE),
+        }
+    }
+
+    pub fn write(&self, format: ReportFormat, mut writer: impl Write) -> Result<()> {
+        match format {
+            ReportFormat::Json => {
+                serde_json::to_writer_pretty(&mut writer, self)?;
+                writeln!(writer)?;
+            }
+            ReportFormat::Table => self.write_table(writer)?,
+        }
+        Ok(())
+    }
+
+    fn write_table(&self, mut writer: impl Write) -> Result<()> {
+        const HEADER: [&str; 7] = [
+            "INPUT", "OUTPUT", "BEFORE", "AFTER", "RATIO", "TIME", "STATUS",
+        ];
+
+        let rows = self
+            .images
+            .iter()
+            .map(|image| {
+                let status = match (&image.status, &image.error) {
+                    (Status::Failed, Some(error)) => format!("failed: {error}"),
+                    (status, _) => format!("{status:?}").to_lowercase(),
+                };
+                [
+                    image.input.clone(),
+                    image
+                        .output
+                        .as_ref()
+                        .map_or_else(|| "-".into(), |o| o.display().to_string()),
+                    image.bytes_before.map_or_else(|| "-".into(), human_size),
+                    image.bytes_after.map_or_else(|| "-".into(), human_size),
+                    image
+                        .compression_ratio
+                        .map_or_else(|| "-".into(), |r| format!("{r:.2}x")),
+                    format!("{:.2}s", image.duration_secs),
+                    status,
+                ]
+            })
+            .collect::<Vec<_>>();
+
+        let mut widths = HEADER.map(str::len);
+        for row in &rows {
+            for (width, cell) in widths.iter_mut().zip(row) {
+                *width = (*width).max(cell.chars().count());
+            }
+        }
+
+        let header = HEADER.map(String::from);
+        for row in std::iter::once(&header).chain(&rows) {
+            let line = row
+                .iter()
+                .zip(widths)
+                .enumerate()
+                .map(|(i, (cell, width))| match i {
+                    // Sizes, ratios and durations are aligned to the right, so they're easier to compare.
+                    2..=5 => format!("{cell:>width$}"),
+                    _ => format!("{cell:<width$}"),
+                })
+                .collect::<Vec<_>>()
+                .join("  ");
+            writeln!(writer, "{}", line.trim_end())?;
+        }
+
+        let s = &self.summary;
+        writeln!(
+            writer,
+            "\n{} images: {} optimized, {} unchanged, {} failed; {} -> {} ({}) in {:.2}s",
+            s.total,
+            s.optimized,
+            s.unchanged,
+            s.failed,
+            human_size(s.bytes_before),
+            human_size(s.bytes_after),
+            s.compression_ratio
+                .map_or_else(|| "-".into(), |r| format!("{r:.2}x")),
+            s.duration_secs,
+        )?;
+        Ok(())
+    }
+}
+
+fn ratio(before: u64, after: u64) -> Option<f64> {
+    (after > 0).then(|| before as f64 / after as f64)
+}
+
+fn human_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["B", "KiB", "MiB", "GiB"];
+
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{bytes} B")
+    } else {
+        format!("{size:.1} {}", UNITS[unit])
+    }
+}
+
+#[cfg(test)]
+mod spec {
+    use anyhow::anyhow;
+
+    use super::*;
+    use crate::input::ImageSource;
+
+    fn input(path: &str) -> Input {
+        Input {
+            source: ImageSource::LocalFile(path.into()),
+            output: path.into(),
+        }
+    }
+
+    fn optimized(before: u64, after: u64, unchanged: bool) -> Result<Outcome> {
+        Ok(Outcome {
+            output: "out/a.jpg".into(),
+            bytes_before: before,
+            bytes_after: after,
+            unchanged,
+        })
+    }
+
+    fn report(results: &[Result<Outcome>]) -> Report {
+        let images = results
+            .iter()
+            .map(|r| ImageReport::new(&input("a.jpg"), r, Duration::from_millis(250)))
+            .collect();
+        Report::new(images, Duration::from_secs(1))
+    }
+
+    #[test]
+    fn summarizes_images() {
+        let failure = Err(anyhow!("corrupted").context("failed to decode"));
+        let report = report(&[
+            optimized(4096, 1024, false),
+            optimized(100, 100, true),
+            failure,
+        ]);
+
+        assert_eq!(
+            report.summary,
+            Summary {
+                total: 3,
+                optimized: 1,
+                unchanged: 1,
+                failed: 1,
+                bytes_before: 4196,
+                bytes_after: 1124,
+                compression_ratio: Some(4196.0 / 1124.0),
+                duration_secs: 1.0,
+            },
+        );
+        assert_eq!(report.images[0].compression_ratio, Some(4.0));
+        assert_eq!(
+            report.images[2].error.as_deref(),
+            Some("failed to decode: corrupted")
+        );
+    }
+
+    #[test]
+    fn exit_code_reflects_failures() {
+        let failure = || Err(anyhow!("failed"));
+
+        assert_eq!(
+            report(&[optimized(1, 1, false)]).exit_code(),
+            ExitCode::SUCCESS
+        );
+        assert_eq!(report(&[]).exit_code(), ExitCode::SUCCESS);
+        assert_eq!(
+            report(&[optimized(1, 1, false), failure()]).exit_code(),
+            ExitCode::from(PARTIAL_FAILURE),
+        );
+        assert_eq!(
+            report(&[failure(), failure()]).exit_code(),
+            ExitCode::from(FAILURE)
+        );
+    }
+
+    #[test]
+    fn writes_json() {
+        let mut out = Vec::new();
+        report(&[optimized(2048, 1024, false), Err(anyhow!("boom"))])
+            .write(ReportFormat::Json, &mut out)
+            .unwrap();
+
+        let json = serde_json::from_slice::<serde_json::Value>(&out).unwrap();
+        assert_eq!(json["images"][0]["status"], "optimized");
+        assert_eq!(json["images"][0]["input"], "a.jpg");
+        assert_eq!(json["images"][0]["output"], "out/a.jpg");
+        assert_eq!(json["images"][0]["bytes_before"], 2048);
+        assert_eq!(json["images"][0]["bytes_after"], 1024);
+        assert_eq!(json["images"][0]["compression_ratio"], 2.0);
+        assert_eq!(json["images"][0]["duration_secs"], 0.25);
+        assert_eq!(json["images"][1]["status"], "failed");
+        assert_eq!(json["images"][1]["error"], "boom");
+        assert_eq!(json["summary"]["failed"], 1);
+    }
+
+    #[test]
+    fn writes_table() {
+        let mut out = Vec::new();
+        report(&[optimized(2048, 1024, false), Err(anyhow!("boom"))])
+            .write(ReportFormat::Table, &mut out)
+            .unwrap();
+
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "\
+INPUT  OUTPUT      BEFORE    AFTER  RATIO   TIME  STATUS
+a.jpg  out/a.jpg  2.0 KiB  1.0 KiB  2.00x  0.25s  optimized
+a.jpg  -                -        -      -  0.25s  failed: boom
+
+2 images: 1 optimized, 0 unchanged, 1 failed; 2.0 KiB -> 1.0 KiB (2.00x) in 1.00s
+",
+        );
+    }
+}
@@ -0,0 +1,210 @@
+//! Geometric transformations applied to images before encoding.
+
+use clap::ValueEnum;
+use image::imageops::FilterType;
+use image::DynamicImage;
+use serde::{Deserialize, Serialize};
+
+/// How an image is fitted into the box of `max_width` x `max_height`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum Fit {
+    /// Scale down to fit entirely inside the box, preserving the aspect ratio.
+    #[default]
+    Contain,
+    /// Scale down to cover the whole box, preserving the aspect ratio, and crop the overflow around the center.
+    Cover,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, Serialize)]
+pub struct ResizeSettings {
+    pub max_width: Option<u32>,
+    pub max_height: Option<u32>,
+    pub fit: Fit,
+}
+
+/// Images are never upscaled, so they don't grow without gaining any detail.
+pub fn resize(img: DynamicImage, settings: &ResizeSettings) -> DynamicImage {
+    let (width, height) = (img.width(), img.height());
+    let max_width = settings.max_width.unwrap_or(u32::MAX).max(1);
+    let max_height = settings.max_height.unwrap_or(u32::MAX).max(1);
+
+    // Covering a box open in one dimension is the same as fitting into it.
+    let fit = match (settings.fit, settings.max_width, settings.max_height) {
+        (Fit::Cover, Some(_), Some(_)) => Fit::Cover,
+        _ => Fit::Contain,
+    };
+
+    match fit {
+        Fit::Contain => {
+            if width <= max_width && height <= max_height {
+                return img;
+            }
+            img.resize(max_width, max_height, FilterType::Lanczos3)
+        }
+        Fit::Cover => {
+            let scale = f64::max(
+                f64::from(max_width) / f64::from(width),
+                f64::from(max_height) / f64::from(height),
+            )
+            .min(1.0);
+            let scaled_width = ((f64::from(width) * scale).round() as u32).max(1);
+            let scaled_height = ((f64::from(height) * scale).round() as u32).max(1);
+            let img = if scale < 1.0 {
+                img.resize_exact(scaled_width, scaled_height, FilterType::Lanczos3)
+            } else {
+                img
+            };
+
+            let crop_width = scaled_width.min(max_width);
+            let crop_height = scaled_height.min(max_height);
+            if (crop_width, crop_height) == (scaled_width, scaled_height) {
+                return img;
+            }
+            img.crop_imm(
+                (scaled_width - crop_width) / 2,
+                (scaled_height - crop_height) / 2,
+                crop_width,
+                crop_height,
+            )
+        }
+    }
+}
+
+/// EXIF orientation of an image, telling how its pixels should be transformed to be displayed upright.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Orientation {
+    #[default]
+    Normal,
+    FlipHorizontal,
+    Rotate180,
+    FlipVertical,
+    Transpose,
+    Rotate90,
+    Transverse,
+    Rotate270,
+}
+
+impl Orientation {
+    /// Parses the value of the EXIF `Orientation` tag.
+    pub fn from_exif(value: u32) -> Option<Self> {
+        Some(match value {
+            1 => Orientation::Normal,
+            2 => Orientation::FlipHorizontal,
+            3 => Orientation::Rotate180,
+            4 => Orientation::FlipVertical,
+            5 => Orientation::Transpose,
+            6 => Orientation::Rotate90,
+            7 => Orientation::Transverse,
+            8 => Orientation::Rotate270,
+            _ => return None,
+        })
+    }
+
+    /// Transforms the pixels, so the image no longer needs any orientation to be displayed upright.
+    pub fn apply(self, img: DynamicImage) -> DynamicImage {
+        match self {
+            Orientation::Normal => img,
+            Orientation::FlipHorizontal => img.fliph(),
+            Orientation::Rotate180 => img.rotate180(),
+            Orientation::FlipVertical => img.flipv(),
+            Orientation::Transpose => img.rotate90().fliph(),
+            Orientation::Rotate90 => img.rotate90(),
+            Orientation::Transverse => img.rotate270().fliph(),
+            Orientation::Rotate270 => img.rotate270(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod spec {
+    use image::{GenericImageView as _, Rgba, RgbaImage};
+
+    use super::*;
+
+    fn img(width: u32, height: u32) -> DynamicImage {
+        DynamicImage::ImageRgba8(RgbaImage::from_fn(width, height, |x, y| {
+            Rgba([x as u8, y as u8, 0, 255])
+        }))
+    }
+
+    fn resized(width: u32, height: u32, settings: ResizeSettings) -> (u32, u32) {
+        resize(img(width, height), &settings).dimensions()
+    }
+
+    const fn max(width: Option<u32>, height: Option<u32>, fit: Fit) -> ResizeSettings {
+        ResizeSettings {
+            max_width: width,
+            max_height: height,
+            fit,
+        }
+    }
+
+    #[test]
+    fn contains_preserving_aspect_ratio() {
+        let settings = max(Some(100), Some(100), Fit::Contain);
+
+        assert_eq!(resized(400, 200, settings), (100, 50));
+        assert_eq!(resized(200, 400, settings), (50, 100));
+        assert_eq!(
+            resized(400, 200, max(Some(100), None, Fit::Contain)),
+            (100, 50)
+        );
+        assert_eq!(
+            resized(400, 200, max(None, Some(20), Fit::Contain)),
+            (40, 20)
+        );
+    }
+
+    #[test]
+    fn covers_and_crops_to_box() {
+        let settings = max(Some(100), Some(100), Fit::Cover);
+
+        assert_eq!(resized(400, 200, settings), (100, 100));
+        assert_eq!(resized(150, 50, settings), (100, 50));
+        assert_eq!(
+            resized(400, 200, max(Some(100), None, Fit::Cover)),
+            (100, 50)
+        );
+    }
+
+    #[test]
+    fn never_upscales() {
+        for fit in [Fit::Contain, Fit::Cover] {
+            assert_eq!(resized(40, 20, max(Some(100), Some(100), fit)), (40, 20));
+        }
+    }
+
+    #[test]
+    fn crops_around_center() {
+        let cropped = resize(img(30, 10), &max(Some(10), Some(10), Fit::Cover));
+
+        assert_eq!(cropped.get_pixel(0, 0), Rgba([10, 0, 0, 255]));
+    }
+
+    #[test]
+    fn applies_exif_orientation() {
+        let src = img(3, 2);
+
+        for (value, (width, height), corner) in [
+            (1, (3, 2), (0, 0)),
+            (2, (3, 2), (2, 0)),
+            (3, (3, 2), (2, 1)),
+            (4, (3, 2), (0, 1)),
+            (5, (2, 3), (0, 0)),
+            (6, (2, 3), (0, 1)),
+            (7, (2, 3), (2, 1)),
+            (8, (2, 3), (2, 0)),
+        ] {
+            let rotated = Orientation::from_exif(value).unwrap().apply(src.clone());
+
+            assert_eq!(rotated.dimensions(), (width, height), "orientation {value}");
+            assert_eq!(
+                rotated.get_pixel(0, 0),
+                Rgba([corner.0, corner.1, 0, 255]),
+                "orientation {value}",
+            );
+        }
+        assert_eq!(Orientation::from_exif(9), None);
+    }
+}
@@ -0,0 +1,282 @@
+//! Optimizing images as they appear in a watched directory.
+
+use std::collections::{HashMap, HashSet};
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::Result;
+use notify::RecursiveMode;
+use notify_debouncer_mini::{new_debouncer, DebounceEventResult};
+use tokio::sync::{mpsc, Mutex, Semaphore};
+use tokio::task::JoinSet;
+use tracing::{info, warn};
+
+use crate::input::{self, Input, InputSettings, Outputs};
+
+/// Watches the `root` directory, calling `optimize` for every new or changed image in it, until `shutdown` completes.
+///
+/// Changes are debounced, so an image being written is optimized once it's complete. At most `concurrency` images are
+/// optimized at once, and the ones being optimized on shutdown are finished, while the queued ones are dropped.
+///
+/// Images colliding with the `outputs` of the others are renamed, while the ones written to the same output, e.g. an
+/// image changed again while being optimized, are optimized one after another.
+pub async fn watch<F, Fut>(
+    root: &Path,
+    settings: &InputSettings,
+    debounce: Duration,
+    concurrency: usize,
+    mut outputs: Outputs,
+    optimize: F,
+    shutdown: impl Future<Output = ()>,
+) -> Result<()>
+where
+    F: Fn(Input) -> Fut,
+    Fut: Future<Output = ()> + Send + 'static,
+{
+    let root = root.canonicalize()?;
+
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    let mut debouncer = new_debouncer(debounce, move |events: DebounceEventResult| {
+        // Fails only once the watching is over, so the events aren't needed anymore.
+        let _ = tx.send(events);
+    })?;
+    let mode = if settings.recursive {
+        RecursiveMode::Recursive
+    } else {
+        RecursiveMode::NonRecursive
+    };
+    debouncer.watcher().watch(&root, mode)?;
+    info!("watching {:?} for images", root);
+
+    let permits = Arc::new(Semaphore::new(concurrency));
+    let mut writing = HashMap::<PathBuf, Arc<Mutex<()>>>::new();
+    let mut in_flight = JoinSet::new();
+    tokio::pin!(shutdown);
+
+    loop {
+        tokio::select! {
+            () = &mut shutdown => break,
+            Some(events) = rx.recv() => {
+                let events = match events {
+                    Ok(events) => events,
+                    Err(e) => {
+                        warn!("failed to watch {:?}: {}", root, e);
+                        continue;
+                    }
+                };
+                // Files created along with their directory may be reported only by the directory itself, or by both.
+                let mut seen = HashSet::new();
+                let inputs = events.into_iter().flat_map(|event| {
+                    input::resolve_found(&root, &event.path, settings).unwrap_or_else(|e| {
+                        warn!("failed to resolve {:?}: {}", event.path, e);
+                        Vec::new()
+                    })
+                });
+                // Only the images still waiting for or being optimized need their outputs locked.
+                writing.retain(|_, lock| Arc::strong_count(lock) > 1);
+                for input in inputs.map(|input| outputs.assign(input)) {
+                    if !seen.insert(input.output.clone()) {
+                        continue;
+                    }
+                    let output = writing.entry(input.output.clone()).or_default().clone();
+                    let permits = permits.clone();
+                    let task = optimize(input);
+                    in_flight.spawn(async move {
+                        // Taken before the permit, so an image waiting for its output doesn't hold up the others.
+                        let _output = output.lock_owned().await;
+                        // Closed on shutdown, so the queued images are dropped.
+                        if let Ok(_permit) = permits.acquire_owned().await {
+                            task.await;
+                        }
+                    });
+                }
+            }
+            Some(_) = in_flight.join_next(), if !in_flight.is_empty() => {}
+        }
+    }
+
+    permits.close();
+    if !in_flight.is_empty() {
+        info!("finishing images being optimized before shutting down");
+    }
+    while in_flight.join_next().await.is_some() {}
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod spec {
+    use std::fs;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use tokio::sync::oneshot;
+    use tokio::time::timeout;
+
+    use super::*;
+
+    const DEBOUNCE: Duration = Duration::from_millis(50);
+    const TIMEOUT: Duration = Duration::from_secs(10);
+
+    fn settings(recursive: bool) -> InputSettings {
+        InputSettings {
+            recursive,
+            include: Vec::new(),
+            exclude: Vec::new(),
+        }
+    }
+
+    #[tokio::test]
+    async fn optimizes_new_and_changed_images() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().to_path_buf();
+        let (found_tx, mut found) = mpsc::unbounded_channel::<PathBuf>();
+        let (stop, stopped) = oneshot::channel::<()>();
+
+        let watching = tokio::spawn(async move {
+            watch(
+                &root,
+                &settings(true),
+                DEBOUNCE,
+                1,
+                Outputs::default(),
+                move |input| {
+                    let found_tx = found_tx.clone();
+                    async move { found_tx.send(input.output).unwrap() }
+                },
+                async {
+                    let _ = stopped.await;
+                },
+            )
+            .await
+        });
+        // Lets the watcher start.
+        tokio::time::sleep(Duration::from_millis(200)).await;
+
+        fs::create_dir(dir.path().join("sub")).unwrap();
+        fs::write(dir.path().join("sub/a.jpg"), b"new").unwrap();
+        fs::write(dir.path().join("notes.txt"), b"ignored").unwrap();
+        let next = timeout(TIMEOUT, found.recv()).await.unwrap();
+        assert_eq!(next, Some(PathBuf::from("sub/a.jpg")));
+
+        fs::write(dir.path().join("sub/a.jpg"), b"changed").unwrap();
+        let next = timeout(TIMEOUT, found.recv()).await.unwrap();
+        assert_eq!(next, Some(PathBuf::from("sub/a.jpg")));
+
+        stop.send(()).unwrap();
+        timeout(TIMEOUT, watching).await.unwrap().unwrap().unwrap();
+        // A change may be reported across debounced batches, so the image may be found again, unlike anything else.
+        while let Ok(next) = found.try_recv() {
+            assert_eq!(next, PathBuf::from("sub/a.jpg"));
+        }
+    }
+
+    #[tokio::test]
+    async fn renames_images_colliding_with_others_and_optimizes_each_output_at_once() {
+        let dir = tempfile::tempdir().unwrap();
+        let elsewhere = tempfile::tempdir().unwrap();
+        let other = elsewhere.path().join("a.png");
+        fs::write(&other, b"image").unwrap();
+        let mut outputs = Outputs::default();
+        outputs.claim(Path::new("a.png"), Some(&other.canonicalize().unwrap()));
+
+        let root = dir.path().to_path_buf();
+        let (found_tx, mut found) = mpsc::unbounded_channel::<PathBuf>();
+        let active = Arc::new(AtomicUsize::new(0));
+        let overlapped = Arc::new(AtomicUsize::new(0));
+        let (stop, stopped) = oneshot::channel::<()>();
+
+        let (active_, overlapped_) = (active.clone(), overlapped.clone());
+        let watching = tokio::spawn(async move {
+            watch(
+                &root,
+                &settings(false),
+                DEBOUNCE,
+                2,
+                outputs,
+                move |input| {
+                    let found_tx = found_tx.clone();
+                    let (active, overlapped) = (active_.clone(), overlapped_.clone());
+                    async move {
+                        if active.fetch_add(1, Ordering::SeqCst) > 0 {
+                            overlapped.fetch_add(1, Ordering::SeqCst);
+                        }
+                        found_tx.send(input.output).unwrap();
+                        tokio::time::sleep(Duration::from_millis(300)).await;
+                        active.fetch_sub(1, Ordering::SeqCst);
+                    }
+                },
+                async {
+                    let _ = stopped.await;
+                },
+            )
+            .await
+        });
+        tokio::time::sleep(Duration::from_millis(200)).await;
+
+        fs::write(dir.path().join("a.jpg"), b"new").unwrap();
+        let next = timeout(TIMEOUT, found.recv()).await.unwrap();
+        assert_eq!(next, Some(PathBuf::from("a-1.jpg")));
+
+        // Changed again while the previous version is still being optimized.
+        fs::write(dir.path().join("a.jpg"), b"changed").unwrap();
+        let next = timeout(TIMEOUT, found.recv()).await.unwrap();
+        assert_eq!(next, Some(PathBuf::from("a-1.jpg")));
+
+        stop.send(()).unwrap();
+        timeout(TIMEOUT, watching).await.unwrap().unwrap().unwrap();
+        assert_eq!(overlapped.load(Ordering::SeqCst), 0);
+    }
+
+    #[tokio::test]
+    async fn finishes_in_flight_images_on_shutdown() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().to_path_buf();
+        let started = Arc::new(AtomicUsize::new(0));
+        let finished = Arc::new(AtomicUsize::new(0));
+        let (stop, stopped) = oneshot::channel::<()>();
+
+        let (started_, finished_) = (started.clone(), finished.clone());
+        let watching = tokio::spawn(async move {
+            watch(
+                &root,
+                &settings(false),
+                DEBOUNCE,
+                1,
+                Outputs::default(),
+                move |_| {
+                    let (started, finished) = (started_.clone(), finished_.clone());
+                    async move {
+                        started.fetch_add(1, Ordering::SeqCst);
+                        tokio::time::sleep(Duration::from_millis(500)).await;
+                        finished.fetch_add(1, Ordering::SeqCst);
+                    }
+                },
+                async {
+                    let _ = stopped.await;
+                },
+            )
+            .await
+        });
+        tokio::time::sleep(Duration::from_millis(200)).await;
+
+        for file in ["a.jpg", "b.jpg", "c.jpg"] {
+            fs::write(dir.path().join(file), b"image").unwrap();
+        }
+        timeout(TIMEOUT, async {
+            while started.load(Ordering::SeqCst) == 0 {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .unwrap();
+
+        stop.send(()).unwrap();
+        timeout(TIMEOUT, watching).await.unwrap().unwrap().unwrap();
+
+        // Only a single image is optimized at once, so the others are still queued.
+        assert_eq!(started.load(Ordering::SeqCst), 1);
+        assert_eq!(finished.load(Ordering::SeqCst), 1);
+    }
+}
@@ -0,0 +1,274 @@
+//! Optimization of remote images served by a local stand-in server, mixed with local ones.
+
+use std::convert::Infallible;
+use std::io::Cursor;
+use std::net::SocketAddr;
+use std::path::Path;
+use std::process::{Output, Stdio};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use hyper::header::{CONTENT_TYPE, LOCATION};
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{Body, Request, Response, Server, StatusCode};
+use image::{DynamicImage, ImageFormat, RgbImage};
+use tempfile::TempDir;
+use tokio::process::Command;
+
+fn fixture(format: ImageFormat) -> Vec<u8> {
+    let mut out = Cursor::new(Vec::new());
+    DynamicImage::ImageRgb8(RgbImage::from_fn(16, 16, |x, y| {
+        image::Rgb([x as u8 * 16, y as u8 * 16, 128])
+    }))
+    .write_to(&mut out, format)
+    .unwrap();
+    out.into_inner()
+}
+
+fn respond(status: StatusCode, content_type: &str, body: impl Into<Body>) -> Response<Body> {
+    Response::builder()
+        .status(status)
+        .header(CONTENT_TYPE, content_type)
+        .body(body.into())
+        .unwrap()
+}
+
+/// Serves the fixture images along with misbehaving endpoints, counting the requests to `/flaky`.
+async fn serve(req: Request<Body>, flaky: Arc<AtomicUsize>) -> Result<Response<Body>, Infallible> {
+    let path = req.uri().path();
+    Ok(match path {
+        "/images/photo.png" => respond(StatusCode::OK, "image/png", fixture(ImageFormat::Png)),
+        "/images/photo.jpg" => respond(StatusCode::OK, "image/jpeg", fixture(ImageFormat::Jpeg)),
+        "/mislabeled" => respond(StatusCode::OK, "image/gif", fixture(ImageFormat::Png)),
+        "/page" => respond(StatusCode::OK, "text/html", "<html></html>"),
+        "/missing" => respond(StatusCode::NOT_FOUND, "text/plain", "not found"),
+        "/large" => respond(StatusCode::OK, "image/png", vec![0; 64 * 1024]),
+        "/flaky" => {
+            if flaky.fetch_add(1, Ordering::SeqCst) < 2 {
+                respond(StatusCode::SERVICE_UNAVAILABLE, "text/plain", "try later")
+            } else {
+                respond(StatusCode::OK, "image/png", fixture(ImageFormat::Png))
+            }
+        }
+        "/slow" => {
+            tokio::time::sleep(Duration::from_secs(5)).await;
+            respond(StatusCode::OK, "image/png", fixture(ImageFormat::Png))
+        }
+        _ => match path.strip_prefix("/redirect/").map(str::parse::<u32>) {
+            Some(Ok(hops)) => Response::builder()
+                .status(StatusCode::FOUND)
+                .header(
+                    LOCATION,
+                    match hops {
+                        0 => "/images/photo.png".to_string(),
+                        n => format!("/redirect/{}", n - 1),
+                    },
+                )
+                .body(Body::empty())
+                .unwrap(),
+            _ => respond(StatusCode::NOT_FOUND, "text/plain", "not found"),
+        },
+    })
+}
+
+/// Starts the stand-in server, returning its address and the counter of requests to `/flaky`.
+fn start_server() -> (SocketAddr, Arc<AtomicUsize>) {
+    let flaky = Arc::new(AtomicUsize::new(0));
+    let counter = flaky.clone();
+    let make_service = make_service_fn(move |_| {
+        let flaky = counter.clone();
+        async move { Ok::<_, Infallible>(service_fn(move |req| serve(req, flaky.clone()))) }
+    });
+    let server = Server::bind(&([127, 0, 0, 1], 0).into()).serve(make_service);
+    let addr = server.local_addr();
+    tokio::spawn(server);
+    (addr, flaky)
+}
+
+/// Runs the optimizer in a clean directory, so no config file gets in the way.
+async fn optimize(dir: &TempDir, urls: &[String]) -> Output {
+    let mut cmd = Command::new(env!("CARGO_BIN_EXE_step_3"));
+    for url in urls {
+        cmd.args(["--images", url]);
+    }
+    cmd.args(["--output-dir", "out", "--report", "json"])
+        .current_dir(dir.path())
+        .env("APP_FETCH__TIMEOUT_SECS", "1")
+        .env("APP_FETCH__MAX_BODY_SIZE", "16384")
+        .env("APP_FETCH__RETRIES", "2")
+        .env("APP_FETCH__RETRY_BACKOFF_MS", "10")
+        .env("APP_FETCH__MAX_REDIRECTS", "3")
+        .env("RUST_LOG", "warn")
+        .stdin(Stdio::null())
+        .output()
+        .await
+        .unwrap()
+}
+
+fn outputs(dir: &TempDir) -> Vec<String> {
+    let mut files = std::fs::read_dir(dir.path().join("out"))
+        .unwrap()
+        .map(|e| e.unwrap().file_name().into_string().unwrap())
+        .filter(|name| !name.starts_with('.'))
+        .collect::<Vec<_>>();
+    files.sort();
+    files
+}
+
+fn stderr(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stderr).into_owned()
+}
+
+/// Error of the only image in the run report.
+fn error(output: &Output) -> String {
+    let report = serde_json::from_slice::<serde_json::Value>(&output.stdout).unwrap();
+    report["images"][0]["error"]
+        .as_str()
+        .unwrap_or_default()
+        .to_owned()
+}
+
+fn assert_image(path: &Path, format: ImageFormat) {
+    let bytes = std::fs::read(path).unwrap();
+    assert_eq!(image::guess_format(&bytes).unwrap(), format);
+}
+
+#[tokio::test]
+async fn downloads_images_by_full_url() {
+    let (addr, _) = start_server();
+    let dir = tempfile::tempdir().unwrap();
+
+    let output = optimize(
+        &dir,
+        &[
+            format!("http://{addr}/images/photo.png"),
+            format!("http://{addr}/images/photo.jpg"),
+        ],
+    )
+    .await;
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    let files = outputs(&dir);
+    assert_eq!(files.len(), 2, "{files:?}");
+    assert!(files.iter().all(|f| f.starts_with("http___127.0.0.1_")));
+    assert_image(&dir.path().join("out").join(&files[0]), ImageFormat::Jpeg);
+    assert_image(&dir.path().join("out").join(&files[1]), ImageFormat::Png);
+}
+
+#[tokio::test]
+async fn retries_transient_failures() {
+    let (addr, flaky) = start_server();
+    let dir = tempfile::tempdir().unwrap();
+
+    let output = optimize(&dir, &[format!("http://{addr}/flaky")]).await;
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(flaky.load(Ordering::SeqCst), 3);
+    assert_eq!(outputs(&dir).len(), 1);
+}
+
+#[tokio::test]
+async fn follows_limited_redirects() {
+    let (addr, _) = start_server();
+    let dir = tempfile::tempdir().unwrap();
+
+    let output = optimize(&dir, &[format!("http://{addr}/redirect/2")]).await;
+    assert!(output.status.success(), "{}", stderr(&output));
+
+    let output = optimize(&dir, &[format!("http://{addr}/redirect/3")]).await;
+    assert!(!output.status.success());
+    assert!(
+        error(&output).contains("too many redirects"),
+        "{}",
+        error(&output)
+    );
+}
+
+#[tokio::test]
+async fn rejects_invalid_responses() {
+    let (addr, _) = start_server();
+
+    for (path, expected) in [
+        ("/page", "content of type text/html is not an image"),
+        ("/missing", "responded with 404 Not Found"),
+        ("/large", "image is larger than 16384 bytes"),
+        ("/slow", "timed out"),
+    ] {
+        let dir = tempfile::tempdir().unwrap();
+
+        let output = optimize(&dir, &[format!("http://{addr}{path}")]).await;
+
+        assert_eq!(output.status.code(), Some(1), "{path}");
+        assert!(
+            error(&output).contains(expected),
+            "{path}: {}",
+            error(&output)
+        );
+    }
+}
+
+#[tokio::test]
+async fn trusts_sniffed_content_over_declared_type() {
+    let (addr, _) = start_server();
+    let dir = tempfile::tempdir().unwrap();
+
+    let output = optimize(&dir, &[format!("http://{addr}/mislabeled")]).await;
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stderr(&output).contains("doesn't match the Png image"));
+    assert_image(
+        &dir.path().join("out").join(&outputs(&dir)[0]),
+        ImageFormat::Png,
+    );
+}
+
+#[tokio::test]
+async fn keeps_going_after_failures() {
+    let (addr, _) = start_server();
+    let dir = tempfile::tempdir().unwrap();
+
+    let output = optimize(
+        &dir,
+        &[
+            format!("http://{addr}/missing"),
+            format!("http://{addr}/images/photo.png"),
+        ],
+    )
+    .await;
+
+    assert_eq!(output.status.code(), Some(2));
+    let report = serde_json::from_slice::<serde_json::Value>(&output.stdout).unwrap();
+    assert_eq!(report["images"][0]["status"], "failed");
+    assert_eq!(report["images"][1]["status"], "optimized");
+    assert_eq!(report["summary"]["failed"], 1);
+    assert_eq!(outputs(&dir).len(), 1);
+}
+
+#[tokio::test]
+async fn reports_unresolvable_paths_along_with_images() {
+    let (addr, _) = start_server();
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::write(dir.path().join("local.png"), fixture(ImageFormat::Png)).unwrap();
+
+    let output = optimize(
+        &dir,
+        &[
+            "missing.png".into(),
+            "local.png".into(),
+            format!("http://{addr}/images/photo.jpg"),
+        ],
+    )
+    .await;
+
+    assert_eq!(output.status.code(), Some(2), "{}", stderr(&output));
+    let report = serde_json::from_slice::<serde_json::Value>(&output.stdout).unwrap();
+    assert_eq!(report["images"][0]["input"], "missing.png");
+    assert_eq!(report["images"][0]["status"], "failed");
+    assert!(error(&output).contains("Unknown image source"));
+    assert_eq!(report["images"][1]["status"], "optimized");
+    assert_eq!(report["images"][2]["status"], "optimized");
+    assert_eq!(report["summary"]["total"], 3);
+    assert_eq!(report["summary"]["failed"], 1);
+    assert_eq!(outputs(&dir).len(), 2);
+}